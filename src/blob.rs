@@ -1,29 +1,105 @@
-use crate::{git_errors::errors::ErrorType, hash::GitHash};
-use std::{fs::File, io::Write, path::PathBuf};
+use crate::{
+    files::file_mode::FileMode, git_errors::errors::ErrorType, git_object::GitObject, hash::GitHash,
+};
+use std::{fs, path::Path};
+
+// igual que git: si hay un byte nulo en los primeros 8000 bytes se considera binario
+const BINARY_CHECK_SIZE: usize = 8000;
 
 pub struct Blob {
     // path : PathBuf,
-    content: String,
+    content: Vec<u8>,
 }
 
 impl Blob {
-    pub(crate) fn new(content: String) -> Self {
+    pub(crate) fn new(content: Vec<u8>) -> Self {
         Self { content }
         // Self{ path, content }
     }
 
-    pub(crate) fn write_to_working_directory(&self, path: &PathBuf) -> Result<(), ErrorType> {
+    pub(crate) fn write_to_working_directory(
+        &self,
+        path: &Path,
+        mode: FileMode,
+    ) -> Result<(), ErrorType> {
         //todo verificar si esta bien que lo cree
-        let mut file = File::create(path)?;
-        file.write_all(self.content.as_bytes())?;
-        Ok(())
+        mode.write_content(path, &self.content)
+    }
+
+    /// Escribe el blob con ese hash en el working directory, creando los directorios que falten.
+    /// De los submodules solo se crea el directorio vacio, como hace git
+    pub(crate) fn checkout(
+        hash: &GitHash,
+        mode: FileMode,
+        path: &Path,
+        path_objects: &Path,
+    ) -> Result<(), ErrorType> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        match mode {
+            FileMode::Gitlink => mode.write_content(path, &[]),
+            _ => GitObject::read_blob(hash, path_objects)?.write_to_working_directory(path, mode),
+        }
     }
 
-    pub(crate) fn get_content(&self) -> String {
+    pub(crate) fn get_content(&self) -> Vec<u8> {
         self.content.clone()
     }
 
+    /// Devuelve el contenido como texto, None si no es utf-8 valido o es binario
+    pub(crate) fn get_text(&self) -> Option<&str> {
+        match self.is_binary() {
+            true => None,
+            false => std::str::from_utf8(&self.content).ok(),
+        }
+    }
+
     pub(crate) fn get_hash(&self) -> GitHash {
-        GitHash::hash_blob(&self.content.as_bytes().to_vec())
+        GitHash::hash_blob(&self.content)
+    }
+
+    pub(crate) fn is_binary(&self) -> bool {
+        Self::is_binary_content(&self.content)
+    }
+
+    /// Heuristica de git para detectar contenido binario (imagenes, comprimidos, ejecutables...)
+    pub fn is_binary_content(content: &[u8]) -> bool {
+        content
+            .iter()
+            .take(BINARY_CHECK_SIZE)
+            .any(|&byte| byte == 0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn binary_detection() {
+        assert!(!Blob::new(b"hola\nmundo\n".to_vec()).is_binary());
+        assert!(!Blob::new(Vec::new()).is_binary());
+        assert!(Blob::new(vec![0x89, b'P', b'N', b'G', 0, 0, 0, 0x0d]).is_binary());
+        // un nulo despues de los primeros 8000 bytes no cuenta
+        let mut late_null = vec![b'a'; BINARY_CHECK_SIZE];
+        late_null.push(0);
+        assert!(!Blob::is_binary_content(&late_null));
+    }
+
+    #[test]
+    fn text_of_binary_or_invalid_utf8_is_none() {
+        assert_eq!(Blob::new(b"texto".to_vec()).get_text(), Some("texto"));
+        assert_eq!(Blob::new(vec![b'a', 0, b'b']).get_text(), None);
+        assert_eq!(Blob::new(vec![0xff, 0xfe, b'a']).get_text(), None);
+    }
+
+    #[test]
+    fn hash_of_raw_bytes() {
+        let content = vec![0, 159, 146, 150, 255];
+        assert_eq!(
+            Blob::new(content.clone()).get_hash(),
+            GitHash::hash_blob(&content)
+        );
     }
 }
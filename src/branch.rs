@@ -17,6 +17,7 @@ use crate::{
     hash::GitHash,
     refs::BranchRef,
     repo_paths::RepoPaths,
+    rev_parse::RevParse,
 };
 
 #[derive(Clone)]
@@ -33,7 +34,8 @@ impl Branch {
         branch_name: &str,
         // path_objects: &Path,
     ) -> Result<Self, ErrorType> {
-        if !path_branches.join(branch_name).exists() {
+        Self::validate_name(branch_name)?;
+        if !path_branches.join(branch_name).is_file() {
             return Err(ErrorType::CommandError(CommandError::InvalidBranch(
                 branch_name.to_string(),
             )));
@@ -53,26 +55,13 @@ impl Branch {
         match args.len() {
             0 => {
                 let head_branch_name = fs::read_to_string(repo_paths.get_head())?;
+                if head.is_detached() {
+                    println!("(HEAD detached at {}) <--HEAD", &head_branch_name[..7]);
+                }
                 Self::display_branches(head_branch_name.clone(), path_branches)?;
                 Ok(format!("Display branch {}.", head_branch_name))
             }
-            1 => {
-                // create branch
-                let name = &args[0];
-                let head_commit_hash = match head.get_last_commit_hash() {
-                    Some(h) => h,
-                    None => {
-                        return Err(ErrorType::RepositoryError(
-                            "Cannot branch if there are no commits".to_string(),
-                        ))
-                    }
-                };
-
-                Self::new(name, &repo_paths.get_refs_heads(), head_commit_hash)?;
-                let result = format!("Branch {name} successfully created.");
-                println!("{}", result.clone());
-                Ok(result)
-            }
+            1 => Self::create_branch(repo_paths, &head, &args[0], None),
             _ => {
                 let option: &str = &args[0];
 
@@ -106,6 +95,9 @@ impl Branch {
                         repo_paths.get_head(),
                     )?;
                     return Ok(format!("Delete branch {}.", option));
+                } else if args.len() == 2 && !option.starts_with('-') {
+                    // branch <name> <start-point>
+                    Self::create_branch(repo_paths, &head, option, Some(&args[1]))
                 } else {
                     return Err(ErrorType::CommandError(CommandError::InvalidArgument(
                         option.to_string(),
@@ -115,6 +107,29 @@ impl Branch {
         }
     }
 
+    // crea la branch en el commit de la revision, o en HEAD si no se indica
+    fn create_branch(
+        repo_paths: &RepoPaths,
+        head: &BranchRef,
+        name: &str,
+        start_point: Option<&str>,
+    ) -> Result<String, ErrorType> {
+        let commit_hash = match (start_point, head.get_last_commit_hash()) {
+            (Some(revision), _) => RevParse::resolve_commit(repo_paths, revision)?.get_hash(),
+            (None, Some(hash)) => hash,
+            (None, None) => {
+                return Err(ErrorType::RepositoryError(
+                    "Cannot branch if there are no commits".to_string(),
+                ))
+            }
+        };
+
+        Self::new(name, &repo_paths.get_refs_heads(), commit_hash)?;
+        let result = format!("Branch {name} successfully created.");
+        println!("{}", result.clone());
+        Ok(result)
+    }
+
     fn display_branches(head_branch_name: String, path_refs: PathBuf) -> Result<(), ErrorType> {
         let branches = Self::list_branches(&path_refs)?;
 
@@ -127,30 +142,58 @@ impl Branch {
         Ok(())
     }
 
+    /// Branches con su ultimo commit. Los nombres jerarquicos (feature/login) estan guardados
+    /// en subdirectorios
     pub fn list_branches(path_branches: &Path) -> Result<HashMap<String, GitHash>, ErrorType> {
         let mut branches = HashMap::new();
         if !path_branches.exists() {
             return Ok(branches);
         }
+        Self::list_branches_rec(path_branches, "", &mut branches)?;
+        Ok(branches)
+    }
 
-        let entries = fs::read_dir(path_branches)?;
-        let branch_names: Vec<String> = entries
-            .filter_map(|entry| {
-                let path = entry.ok()?.path();
-                if path.is_file() {
-                    path.file_name()?.to_str().map(|s| s.to_owned())
-                } else {
-                    None
-                }
-            })
-            .collect();
-        for branch_name in branch_names {
-            let path = path_branches.join(&branch_name);
-            let hash = fs::read_to_string(path)?;
-            let hash = GitHash::new(&hash)?;
-            branches.insert(branch_name, hash);
+    fn list_branches_rec(
+        path_dir: &Path,
+        prefix: &str,
+        branches: &mut HashMap<String, GitHash>,
+    ) -> Result<(), ErrorType> {
+        for entry in fs::read_dir(path_dir)? {
+            let path = entry?.path();
+            let Some(file_name) = path.file_name().and_then(|n| n.to_str()) else {
+                continue;
+            };
+            let branch_name = format!("{prefix}{file_name}");
+            if path.is_dir() {
+                Self::list_branches_rec(&path, &format!("{branch_name}/"), branches)?;
+            } else if path.is_file() {
+                let hash = GitHash::new(&fs::read_to_string(&path)?)?;
+                branches.insert(branch_name, hash);
+            }
         }
-        Ok(branches)
+        Ok(())
+    }
+
+    /// Si el nombre sirve para una ref (branch o tag). Nombres como feature/login valen, pero
+    /// cada parte tiene que ser un nombre de archivo valido dentro del directorio de las refs
+    /// y no puede tener los caracteres que usan las revisiones (~ ^ : .. @{)
+    pub(crate) fn is_valid_ref_name(name: &str) -> bool {
+        name.split('/').all(|part| !part.is_empty() && part != ".")
+            && !name.contains(|c: char| c.is_whitespace() || "\\~^:?*[".contains(c))
+            && !name.contains("..")
+            && !name.contains("@{")
+            && !name.starts_with('-')
+    }
+
+    /// Error si el nombre no sirve para una branch (ver is_valid_ref_name). Tambien lo usa el
+    /// server con los nombres de las refs que recibe en un push
+    pub fn validate_name(name: &str) -> Result<(), ErrorType> {
+        if !Self::is_valid_ref_name(name) {
+            return Err(ErrorType::CommandError(InvalidBranch(format!(
+                "'{name}' is not a valid branch name"
+            ))));
+        }
+        Ok(())
     }
 
     fn set_upstream(
@@ -158,9 +201,9 @@ impl Branch {
         local_branch_name: String,
         repo_paths: &RepoPaths,
     ) -> Result<(), ErrorType> {
-        let binding = remote_branch.split('/').collect::<Vec<&str>>();
-        let (remote_name, remote_branch_name) = match binding.as_slice() {
-            [remote_name, remote_branch_name] => {
+        // el nombre de la branch remota puede tener mas '/' (origin/feature/login)
+        let (remote_name, remote_branch_name) = match remote_branch.split_once('/') {
+            Some((remote_name, remote_branch_name)) if !remote_branch_name.is_empty() => {
                 (remote_name.to_string(), remote_branch_name.to_string())
             }
             _ => {
@@ -200,10 +243,27 @@ impl Branch {
     }
 
     pub fn new(name: &str, path_branches: &Path, hash: GitHash) -> Result<Self, ErrorType> {
+        Self::validate_name(name)?;
         let path_branch = path_branches.join(name);
-        // if !path_branch.exists() {
-        //     fs::create_dir_all(path_branches)?;
-        // }
+        if path_branch.is_dir() {
+            return Err(ErrorType::CommandError(InvalidBranch(format!(
+                "'{name}' exists as a directory of branches (like '{name}/...')"
+            ))));
+        }
+        // feature/login no se puede crear si existe la branch feature
+        let mut parent = path_branch.parent();
+        while let Some(dir) = parent.filter(|dir| dir.starts_with(path_branches)) {
+            if dir.is_file() {
+                return Err(ErrorType::CommandError(InvalidBranch(format!(
+                    "'{}' exists; cannot create '{name}'",
+                    dir.strip_prefix(path_branches).unwrap_or(dir).display()
+                ))));
+            }
+            parent = dir.parent();
+        }
+        if let Some(dir) = path_branch.parent() {
+            fs::create_dir_all(dir)?;
+        }
         let mut file = File::create(path_branch)?;
         file.write_all(hash.as_str().as_bytes())?;
 
@@ -214,11 +274,21 @@ impl Branch {
         })
     }
 
+    /// Branch que no esta guardada en refs/heads, para mergear una revision cualquiera
+    pub(crate) fn from_revision(revision: &str, path_branches: &Path, hash: GitHash) -> Self {
+        Self {
+            path_branches: path_branches.to_path_buf(),
+            name: revision.to_string(),
+            last_commit_hash: hash,
+        }
+    }
+
     pub fn delete_branch(
         name: &str,
         path_refs: PathBuf,
         path_head: PathBuf,
     ) -> Result<(), ErrorType> {
+        Self::validate_name(name)?;
         let head_branch_name = fs::read_to_string(path_head)?;
         if head_branch_name == name {
             return Err(ErrorType::CommandError(CommandError::InvalidBranch(
@@ -226,13 +296,24 @@ impl Branch {
             )));
         }
         let path_branch = path_refs.join(name);
-        if !path_branch.exists() {
+        if !path_branch.is_file() {
             return Err(ErrorType::CommandError(InvalidBranch(format!(
                 "Branch doesn't exist: {:?}",
-                path_branch.file_name().unwrap_or_default()
+                name
             ))));
         }
-        fs::remove_file(path_branch)?;
+        fs::remove_file(&path_branch)?;
+
+        // borra los directorios que quedaron vacios (feature/ al borrar feature/login)
+        let mut parent = path_branch.parent();
+        while let Some(dir) = parent.filter(|dir| *dir != path_refs && dir.starts_with(&path_refs))
+        {
+            if fs::read_dir(dir)?.next().is_some() {
+                break;
+            }
+            fs::remove_dir(dir)?;
+            parent = dir.parent();
+        }
         Ok(())
     }
 
@@ -259,3 +340,50 @@ impl Branch {
         Ok(commit)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{self, commit_file};
+
+    fn init_repo(name: &str) -> Result<RepoPaths, ErrorType> {
+        let repo_paths = test_utils::init_repo(name)?;
+        commit_file(&repo_paths, "a.txt", "a")?;
+        Ok(repo_paths)
+    }
+
+    fn branch(repo_paths: &RepoPaths, args: &[&str]) -> Result<String, ErrorType> {
+        let args = args.iter().map(|arg| arg.to_string()).collect();
+        Branch::branch_command(repo_paths, args)
+    }
+
+    #[test]
+    fn hierarchical_branch_names() -> Result<(), ErrorType> {
+        let repo_paths = init_repo("git_rustico_tests_branch_hierarchy")?;
+        let path_branches = repo_paths.get_refs_heads();
+        branch(&repo_paths, &["feature/login"])?;
+        branch(&repo_paths, &["feature/ui/menu"])?;
+        assert!(path_branches.join("feature/ui/menu").is_file());
+
+        let mut names: Vec<String> = Branch::list_branches(&path_branches)?.into_keys().collect();
+        names.sort();
+        assert_eq!(names, ["feature/login", "feature/ui/menu", "master"]);
+
+        // una branch no puede ser a la vez archivo y directorio
+        assert!(branch(&repo_paths, &["feature"]).is_err());
+        assert!(branch(&repo_paths, &["master/x"]).is_err());
+        assert!(branch(&repo_paths, &["feature/../x"]).is_err());
+        assert!(branch(&repo_paths, &["feature//x"]).is_err());
+
+        BranchRef::checkout_command(repo_paths.clone(), vec!["feature/login".to_string()])?;
+        assert_eq!(fs::read_to_string(repo_paths.get_head())?, "feature/login");
+        BranchRef::checkout_command(repo_paths.clone(), vec!["master".to_string()])?;
+
+        branch(&repo_paths, &["-d", "feature/ui/menu"])?;
+        assert!(!path_branches.join("feature/ui").exists());
+        assert!(path_branches.join("feature/login").is_file());
+
+        fs::remove_dir_all(repo_paths.get_home())?;
+        Ok(())
+    }
+}
@@ -1,7 +1,11 @@
+use crate::branch::Branch;
 use crate::git_errors::command_error::CommandError::{InvalidHash, UnknownOption};
 use crate::git_errors::errors::ErrorType;
+use crate::git_object::GitObject;
+use crate::hash::GitHash;
+use crate::repo_paths::RepoPaths;
 use flate2::read::ZlibDecoder;
-use std::fs::File;
+use std::fs::{self, File};
 use std::io::Read;
 use std::path::Path;
 
@@ -17,9 +21,19 @@ const DIR_OF_OBJECTS_FILES: &str = ".git-rustico/objects";
 ///     -s tamaño en bytes del archivo.
 ///     -t typo de objeto que es el archivo.
 /// La opcion a elegir es lowercase.
+/// En lugar del hash se puede pasar el nombre de un tag, se usa el object al que apunta su ref.
 /// Caso en que no exista el archivo, la opcion sea incorrecta o falle la lectura del archivo
 /// retornara error.
 pub fn cat_file(option: &str, hash_oject: &str) -> Result<String, ErrorType> {
+    Ok(String::from_utf8_lossy(&cat_file_bytes(option, hash_oject)?).to_string())
+}
+
+/// Igual que cat_file pero devuelve los bytes tal cual estan guardados,
+/// para poder mostrar blobs binarios sin corromperlos.
+pub fn cat_file_bytes(option: &str, hash_oject: &str) -> Result<Vec<u8>, ErrorType> {
+    let path_tags = RepoPaths::new(std::env::current_dir()?)?.get_refs_tags();
+    let hash_tag = resolve_tag_name(hash_oject, &path_tags)?;
+    let hash_oject = hash_tag.as_deref().unwrap_or(hash_oject);
     if hash_oject.len() != 40 {
         return Err(ErrorType::CommandError(InvalidHash(
             "Hash length must be 40".to_string(),
@@ -27,8 +41,8 @@ pub fn cat_file(option: &str, hash_oject: &str) -> Result<String, ErrorType> {
     }
     match option {
         "-p" => Ok(cat_file_objects_content(hash_oject, DIR_OF_OBJECTS_FILES)?),
-        "-s" => Ok(cat_file_object_size(hash_oject, DIR_OF_OBJECTS_FILES)?),
-        "-t" => Ok(cat_file_object_type(hash_oject, DIR_OF_OBJECTS_FILES)?),
+        "-s" => Ok(cat_file_object_size(hash_oject, DIR_OF_OBJECTS_FILES)?.into_bytes()),
+        "-t" => Ok(cat_file_object_type(hash_oject, DIR_OF_OBJECTS_FILES)?.into_bytes()),
         _ => Err(ErrorType::CommandError(UnknownOption(
             "-p, -s or -t".to_string(),
             option.to_string(),
@@ -38,43 +52,43 @@ pub fn cat_file(option: &str, hash_oject: &str) -> Result<String, ErrorType> {
 
 // TODO EL TREE NO ES LEGIBLE PQ TIENE BINARIO
 /// Retorna el contenido basado en su tipo.
-fn cat_file_objects_content(hash_oject: &str, directory: &str) -> Result<String, ErrorType> {
-    let text = decoder_object(hash_oject, directory)?;
-    let content = match text.split('\0').last() {
-        None => {
-            return Err(ErrorType::FormatError(
-                "without separation by null character.".to_string(),
-            ))
-        }
-        Some(a) => a,
-    };
-    Ok(content.to_owned())
+fn cat_file_objects_content(hash_oject: &str, directory: &str) -> Result<Vec<u8>, ErrorType> {
+    let bytes = decoder_object(hash_oject, directory)?;
+    match bytes.iter().position(|&byte| byte == b'\0') {
+        None => Err(ErrorType::FormatError(
+            "without separation by null character.".to_string(),
+        )),
+        Some(null) => Ok(bytes[null + 1..].to_vec()),
+    }
+}
+
+// solo el header (<type> <size>) es texto, el contenido puede ser binario
+fn decoder_header(hash_oject: &str, directory: &str) -> Result<String, ErrorType> {
+    let bytes = decoder_object(hash_oject, directory)?;
+    let header = bytes
+        .split(|&byte| byte == b'\0')
+        .next()
+        .unwrap_or_default();
+    Ok(String::from_utf8_lossy(header).to_string())
 }
 
 /// Retorna el tamaño del objeto.
 fn cat_file_object_size(hash_oject: &str, directory: &str) -> Result<String, ErrorType> {
-    let text = decoder_object(hash_oject, directory)?;
-    let size = match text.split('\0').next() {
+    let text = decoder_header(hash_oject, directory)?;
+    let size = match text.split_whitespace().last() {
         None => {
             return Err(ErrorType::FormatError(
-                "without separation by null character.".to_string(),
+                "without separation by whitespace character.".to_string(),
             ))
         }
-        Some(t) => match t.split_whitespace().last() {
-            None => {
-                return Err(ErrorType::FormatError(
-                    "without separation by whitespace character.".to_string(),
-                ))
-            }
-            Some(t) => t,
-        },
+        Some(t) => t,
     };
     Ok(size.to_string())
 }
 
 /// Retorna el tipo de archivo.
 fn cat_file_object_type(hash_oject: &str, directory: &str) -> Result<String, ErrorType> {
-    let text = decoder_object(hash_oject, directory)?;
+    let text = decoder_header(hash_oject, directory)?;
     match text.split_whitespace().next() {
         None => Err(ErrorType::FormatError(
             "without separation by whitespace character.".to_string(),
@@ -83,6 +97,18 @@ fn cat_file_object_type(hash_oject: &str, directory: &str) -> Result<String, Err
     }
 }
 
+/// Si existe un tag con ese nombre retorna el hash guardado en su ref.
+fn resolve_tag_name(name: &str, path_tags: &Path) -> Result<Option<String>, ErrorType> {
+    if !Branch::is_valid_ref_name(name) {
+        return Ok(None);
+    }
+    let path = path_tags.join(name);
+    if !path.is_file() {
+        return Ok(None);
+    }
+    Ok(Some(fs::read_to_string(path)?.trim().to_string()))
+}
+
 fn open_file_for_cat_file(hash_object: &str, directory: &str) -> Result<File, ErrorType> {
     let (dir, file) = hash_object.split_at(2);
     let path = format!("{}/{}/{}", directory, dir, file);
@@ -94,15 +120,24 @@ fn open_file_for_cat_file(hash_object: &str, directory: &str) -> Result<File, Er
     Ok(File::open(path)?)
 }
 
-fn read_decoder(file: &mut File) -> Result<String, ErrorType> {
-    let mut buffer = String::from("");
+fn read_decoder(file: &mut File) -> Result<Vec<u8>, ErrorType> {
+    let mut buffer = Vec::new();
     let mut decoder = ZlibDecoder::new(file);
-    decoder.read_to_string(&mut buffer)?;
+    decoder.read_to_end(&mut buffer)?;
     Ok(buffer)
 }
 
-fn decoder_object(hash_oject: &str, directory: &str) -> Result<String, ErrorType> {
-    let mut file = open_file_for_cat_file(hash_oject, directory)?;
+fn decoder_object(hash_oject: &str, directory: &str) -> Result<Vec<u8>, ErrorType> {
+    let mut file = match open_file_for_cat_file(hash_oject, directory) {
+        Ok(file) => file,
+        // no es un loose object, se busca en los packfiles
+        Err(_) => {
+            let mut bytes = Vec::new();
+            GitObject::open_object(&GitHash::new(hash_oject)?, Path::new(directory))?
+                .read_to_end(&mut bytes)?;
+            return Ok(bytes);
+        }
+    };
     let text = read_decoder(&mut file)?;
     Ok(text)
 }
@@ -147,7 +182,7 @@ mod tests {
 
         // el archivo original solo contenia "asd" por lo que es de tamaño 3 y es un blob.
         // se utilizo el comando save del file info para crearlo.
-        assert_eq!(lectura, "blob 3\0asd".to_string());
+        assert_eq!(lectura, b"blob 3\0asd".to_vec());
         Ok(())
     }
 
@@ -177,7 +212,18 @@ mod tests {
             "10500012fca9b4425b50de67a7258a12cba0c076",
             DIRECTORIO_DE_ARCHIVOS_DE_PRUEBA,
         )?;
-        assert_eq!(content_file, "asd");
+        assert_eq!(content_file, b"asd".to_vec());
+        Ok(())
+    }
+
+    #[test]
+    fn cat_file_no_lee_tags_fuera_de_refs_tags() -> Result<(), ErrorType> {
+        let path_tags = Path::new("tests/tests_files/objects/10");
+        assert_eq!(
+            resolve_tag_name("../10/500012fca9b4425b50de67a7258a12cba0c076", path_tags)?,
+            None
+        );
+        assert_eq!(resolve_tag_name("/etc/hostname", path_tags)?, None);
         Ok(())
     }
 }
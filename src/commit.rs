@@ -1,36 +1,48 @@
 use std::{
-    collections::{HashMap, HashSet},
+    cell::OnceCell,
+    collections::HashMap,
     fmt::Write,
     fs::File,
     path::{Path, PathBuf},
 };
 
-use chrono::{DateTime, Local, TimeZone};
+use chrono::{DateTime, FixedOffset, Local, TimeZone};
 use serde::{ser::SerializeStruct, Serialize};
 
 use crate::{
+    commit_graph::CommitGraph,
     config::RepoConfig,
-    files::object_type::ObjectType,
+    files::{file_mode::FileMode, object_type::ObjectType},
     git_errors::{command_error::CommandError, errors::ErrorType},
     git_object::GitObject,
     hash::GitHash,
     index::Index,
     refs::BranchRef,
     repo_paths::RepoPaths,
+    rev_walk::RevWalk,
     tree::Tree,
     user::User,
 };
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone)]
 pub struct Commit {
     hash: GitHash,
-    tree: Tree,
+    // el tree se lee recien cuando se lo pide, recorrer la historia solo necesita los headers
+    tree: OnceCell<Tree>,
+    path_objects: PathBuf,
+    // hash con el que el commit referencia al tree, se guarda para regenerar el contenido
+    // exactamente igual aunque el tree haya sido escrito por otra version (o por git)
+    tree_hash: GitHash,
     parent_hash: Option<GitHash>,
     second_parent_hash: Option<GitHash>,
+    // el resto de los padres de un merge de mas de dos branches (octopus)
+    other_parent_hashes: Vec<GitHash>,
     author: User,
-    author_date: DateTime<Local>,
+    author_date: DateTime<FixedOffset>,
     committer: User,
-    committer_date: DateTime<Local>,
+    committer_date: DateTime<FixedOffset>,
+    // headers que no usamos (gpgsig, encoding, mergetag...), se mantienen tal cual
+    extra_headers: Vec<String>,
     message: String,
 }
 
@@ -45,14 +57,19 @@ impl Commit {
         message: &str,
         user: User,
     ) -> Result<Self, ErrorType> {
-        let date = Local::now();
+        let date = Local::now().fixed_offset();
+        let tree_hash = tree.get_hash()?;
 
         let content = Self::generate_content(
-            &tree.get_hash()?,
-            parent_hash.clone(),
-            second_parent_hash.clone(),
+            &tree_hash,
+            &parent_hash
+                .iter()
+                .chain(second_parent_hash.iter())
+                .cloned()
+                .collect::<Vec<GitHash>>(),
             (&user, &date),
             (&user, &date),
+            &[],
             message,
         )?;
 
@@ -64,13 +81,17 @@ impl Commit {
 
         Ok(Commit {
             hash: commit_hash,
-            tree,
+            tree: OnceCell::from(tree),
+            path_objects: PathBuf::new(),
+            tree_hash,
             parent_hash,
             second_parent_hash,
+            other_parent_hashes: Vec::new(),
             author: user.clone(),
             author_date: date,
             committer: user,
             committer_date: date,
+            extra_headers: Vec::new(),
             message: message.to_string(),
         })
         // todo : meter el save aca adentro???
@@ -81,7 +102,10 @@ impl Commit {
     }
 
     pub fn save(&self, path_objetcts: &Path) -> Result<(), ErrorType> {
-        self.tree.save(path_objetcts)?;
+        // si el tree no se cargo es porque el commit se leyo de objects, el tree ya esta guardado
+        if let Some(tree) = self.tree.get() {
+            tree.save(path_objetcts)?;
+        }
         let content = self.get_content()?;
         GitObject::save_commit(content, path_objetcts)?;
         Ok(())
@@ -90,31 +114,28 @@ impl Commit {
     // que se guarde como Option<> para no calcular dos veces
     pub fn get_content(&self) -> Result<Vec<u8>, ErrorType> {
         Self::generate_content(
-            &self.tree.get_hash()?,
-            self.parent_hash.clone(),
-            self.second_parent_hash.clone(),
+            &self.tree_hash,
+            &self.get_parents(),
             (&self.author, &self.author_date),
             (&self.committer, &self.committer_date),
+            &self.extra_headers,
             &self.message,
         )
     }
 
     fn generate_content(
         hash_tree: &GitHash,
-        parent_hash: Option<GitHash>,
-        second_parent_hash: Option<GitHash>,
-        author: (&User, &chrono::DateTime<Local>),
-        committer: (&User, &chrono::DateTime<Local>),
+        parents: &[GitHash],
+        author: (&User, &DateTime<FixedOffset>),
+        committer: (&User, &DateTime<FixedOffset>),
+        extra_headers: &[String],
         message: &str,
     ) -> Result<Vec<u8>, ErrorType> {
         let mut result = String::new();
         writeln!(result, "tree {hash_tree}")?;
-        if let Some(parent_hash) = parent_hash {
+        for parent_hash in parents {
             writeln!(result, "parent {}", parent_hash.as_str())?;
         }
-        if let Some(second_parent_hash) = second_parent_hash {
-            writeln!(result, "parent {}", second_parent_hash.as_str())?;
-        }
 
         let offset: String = author.1.offset().to_string().split(':').collect();
         writeln!(
@@ -136,6 +157,10 @@ impl Commit {
             offset
         )?;
 
+        for line in extra_headers {
+            writeln!(result, "{line}")?;
+        }
+
         writeln!(result)?;
 
         writeln!(result, "{}", message)?;
@@ -148,6 +173,11 @@ impl Commit {
         let path_index = repo_paths.get_index();
 
         let mut index = Index::open(&path_index)?;
+        if !index.unmerged_paths().is_empty() {
+            return Err(ErrorType::RepositoryError(
+                "Committing is not possible because you have unmerged files.\nFix them up in the work tree, and then use 'git-rustico add <file>' as appropriate to mark resolution".to_string(),
+            ));
+        }
 
         let mut head = BranchRef::open(repo_paths.get_head(), &repo_paths.get_refs_heads())?;
 
@@ -176,6 +206,7 @@ impl Commit {
         let commit = Self::new(index_tree, parent_hash, second_parent_hash, &message, user)?;
 
         commit.save(&repo_paths.get_objects())?;
+        CommitGraph::update(&repo_paths.get_objects(), &[commit.get_hash()])?;
 
         index.reset_previous_blob_hash();
 
@@ -203,76 +234,6 @@ impl Commit {
         Ok(message)
     }
 
-    pub fn get_commits_history(&self, path_objects: &PathBuf) -> Result<Vec<Commit>, ErrorType> {
-        let mut commits = HashSet::new();
-        let mut final_history = Vec::new();
-        let history = self.get_commits_history_rec(path_objects)?;
-        for commit in history {
-            if commits.contains(&commit.get_hash()) {
-                continue;
-            }
-            commits.insert(commit.get_hash());
-            final_history.push(commit);
-        }
-        Ok(final_history)
-    }
-
-    // todo : cuando uno tiene 2 padres solo tomar los "distintos" del segundo padre,
-    // es decir hasta que se vuelven a juntar
-    // funcion recursiva que devuelve el historial de commits accesibles desde un commit
-    pub fn get_commits_history_rec(
-        &self,
-        path_objects: &PathBuf,
-    ) -> Result<Vec<Commit>, ErrorType> {
-        let mut history = Vec::new();
-        history.push((*self).clone());
-
-        if let Some(parent1_hash) = &self.parent_hash {
-            let parent1_commit = GitObject::read_commit(parent1_hash, path_objects)?;
-            let mut sub_history = parent1_commit.get_commits_history(path_objects)?;
-
-            if let Some(parent2_hash) = &self.second_parent_hash {
-                let parent2_commit = GitObject::read_commit(parent2_hash, path_objects)?;
-                let parent2_sub_history = parent2_commit.get_commits_history(path_objects)?;
-                sub_history = Self::commits_ordered_by_date(sub_history, parent2_sub_history)?;
-            }
-            history.append(&mut sub_history);
-        }
-
-        Ok(history)
-    }
-
-    fn commits_ordered_by_date(
-        branch1_history: Vec<Commit>,
-        branch2_history: Vec<Commit>,
-    ) -> Result<Vec<Commit>, ErrorType> {
-        let mut commits1: HashSet<GitHash> = HashSet::new();
-        for commit in &branch1_history {
-            commits1.insert(commit.get_hash());
-        }
-        let mut last_common_ancestor = None;
-        for commit in &branch2_history {
-            if commits1.contains(&commit.get_hash()) {
-                last_common_ancestor = Some(commit.get_hash());
-            }
-        }
-
-        let mut interleaved_commits: Vec<Commit> = [branch1_history, branch2_history].concat();
-        interleaved_commits.sort_by(|a, b| b.author_date.cmp(&a.author_date));
-
-        let mut i = 0;
-        while i < interleaved_commits.len() {
-            if let Some(ancestor_hash) = &last_common_ancestor {
-                if interleaved_commits[0].get_hash() == *ancestor_hash {
-                    break;
-                }
-            }
-            i += 1;
-        }
-
-        Ok(interleaved_commits[0..i - 1].to_vec())
-    }
-
     // imprime el display de un commit
     pub(crate) fn log_display(&self, refs: Vec<String>) {
         let mut commit_line = format!("commit: {}", self.hash);
@@ -293,8 +254,18 @@ impl Commit {
         println!("{}", content);
     }
 
-    pub(crate) fn get_files_vec(&self) -> Vec<(PathBuf, GitHash)> {
-        self.tree.get_files_vec()
+    pub(crate) fn get_files_vec(&self) -> Result<Vec<(PathBuf, GitHash)>, ErrorType> {
+        Ok(self.get_tree()?.get_files_vec())
+    }
+
+    /// Modo y hash de cada archivo del tree del commit, indexados por path
+    pub(crate) fn get_files_map(&self) -> Result<HashMap<PathBuf, (FileMode, GitHash)>, ErrorType> {
+        Ok(self
+            .get_tree()?
+            .get_files_with_mode_vec()
+            .into_iter()
+            .map(|(path, mode, hash)| (path, (mode, hash)))
+            .collect())
     }
 
     // parsea el contenido de un commit object y devuelve la instancia
@@ -305,32 +276,36 @@ impl Commit {
     ) -> Result<Self, ErrorType> {
         let mut parent_hash = None;
         let mut second_parent_hash = None;
+        let mut other_parent_hashes = Vec::new();
+        let mut extra_headers = Vec::new();
 
         let mut found = HashMap::new();
 
-        let mut lines = content.lines().peekable();
-        while let Some(line) = lines.next() {
-            if line.is_empty() {
-                //message
-                let mut mssg = String::new();
-                for line in lines.by_ref() {
-                    mssg.push_str(line);
-                    mssg.push('\n');
-                }
-                mssg.pop();
-                found.insert("message".to_string(), mssg);
-                break;
+        // el mensaje es todo lo que sigue a la primera linea vacia, sin el ultimo salto de linea
+        let (headers, message) = content
+            .split_once("\n\n")
+            .ok_or(Self::missing_field(hash, "message"))?;
+        let message = message.strip_suffix('\n').unwrap_or(message);
+        found.insert("message".to_string(), message.to_string());
+
+        for line in headers.lines() {
+            // las lineas que empiezan con espacio continuan el header anterior (ej: gpgsig)
+            if line.starts_with(' ') {
+                extra_headers.push(line.to_string());
+                continue;
             }
             if let Some((category, data)) = line.split_once(' ') {
-                if category == "parent" {
-                    if parent_hash.is_none() {
-                        parent_hash = Some(GitHash::new(data)?)
-                    } else {
+                match category {
+                    "parent" if parent_hash.is_none() => parent_hash = Some(GitHash::new(data)?),
+                    "parent" if second_parent_hash.is_none() => {
                         second_parent_hash = Some(GitHash::new(data)?)
                     }
-                    continue;
+                    "parent" => other_parent_hashes.push(GitHash::new(data)?),
+                    "tree" | "author" | "committer" => {
+                        found.insert(category.to_string(), data.to_string());
+                    }
+                    _ => extra_headers.push(line.to_string()),
                 }
-                found.insert(category.to_string(), data.to_string());
             }
         }
 
@@ -338,7 +313,6 @@ impl Commit {
             .get("tree")
             .ok_or(Self::missing_field(hash, "tree"))
             .and_then(|data| GitHash::new(data))?;
-        let tree = GitObject::read_tree(&tree_hash, path_objects)?;
 
         let (author, author_date) = found
             .get("author")
@@ -357,13 +331,17 @@ impl Commit {
 
         Ok(Self {
             hash: hash.clone(),
-            tree,
+            tree: OnceCell::new(),
+            path_objects: path_objects.to_path_buf(),
+            tree_hash,
             parent_hash,
             second_parent_hash,
+            other_parent_hashes,
             author,
             author_date,
             committer,
             committer_date,
+            extra_headers,
             message,
         })
     }
@@ -374,24 +352,23 @@ impl Commit {
         ))
     }
 
-    fn parse_user_line(fields: &str) -> Result<(User, DateTime<Local>), ErrorType> {
-        let fields: Vec<&str> = fields.split(' ').collect();
-        if fields.len() != 4 {
-            return Err(ErrorType::FormatError(
-                "Invalid user format in commit file".to_string(),
-            ));
-        }
+    // formato: <name> <<mail>> <timestamp> <offset>, el nombre puede tener espacios
+    pub(crate) fn parse_user_line(
+        fields: &str,
+    ) -> Result<(User, DateTime<FixedOffset>), ErrorType> {
+        let invalid = || ErrorType::FormatError("Invalid user format in commit file".to_string());
+
+        let (user, time) = fields.rsplit_once("> ").ok_or_else(invalid)?;
+        let (name, mail) = user.rsplit_once(" <").ok_or(ErrorType::FormatError(
+            "User mail in commit must be between '<' '>'".to_string(),
+        ))?;
+        let user = User::new(name, mail);
 
-        let mail = fields[1]
-            .strip_prefix('<')
-            .and_then(|m| m.strip_suffix('>'))
-            .ok_or(ErrorType::FormatError(
-                "User mail in commit must be between '<' '>'".to_string(),
-            ))?;
-        let user = User::new(fields[0], mail);
-        let seconds: i64 = fields[2].parse::<i64>()?;
+        let (seconds, offset) = time.split_once(' ').ok_or_else(invalid)?;
+        let seconds: i64 = seconds.parse::<i64>()?;
+        let offset = Self::parse_offset(offset).ok_or_else(invalid)?;
 
-        let time = match Local.timestamp_opt(seconds, 0) {
+        let time = match offset.timestamp_opt(seconds, 0) {
             chrono::LocalResult::None => {
                 return Err(ErrorType::FormatError(
                     "invalid date time in commit".to_string(),
@@ -404,26 +381,54 @@ impl Commit {
         Ok((user, time))
     }
 
-    pub(crate) fn last_common_ancestor(
+    // +0200 / -0300
+    fn parse_offset(offset: &str) -> Option<FixedOffset> {
+        if offset.len() != 5 || !offset.is_char_boundary(1) {
+            return None;
+        }
+        let (sign, digits) = offset.split_at(1);
+        let hours: i32 = digits.get(..2)?.parse().ok()?;
+        let minutes: i32 = digits.get(2..)?.parse().ok()?;
+        let seconds = (hours * 60 + minutes) * 60;
+        match sign {
+            "+" => FixedOffset::east_opt(seconds),
+            "-" => FixedOffset::west_opt(seconds),
+            _ => None,
+        }
+    }
+
+    /// Mejores ancestros comunes de self y other: los comunes que no son ancestros de otro
+    /// comun, del mas nuevo al mas viejo. Hay mas de uno cuando las historias se cruzaron
+    /// (criss-cross merge) y ninguno si no comparten historia.
+    pub fn merge_bases(
         &self,
         other: &Commit,
-        path_objects: &PathBuf,
-    ) -> Result<Option<Commit>, ErrorType> {
-        let history1 = self.get_commits_history(path_objects)?;
-        let history2 = other.get_commits_history(path_objects)?;
-
-        let mut hist1_set = HashSet::new();
-
-        for commit in history1 {
-            hist1_set.insert(commit.get_hash());
-        }
+        path_objects: &Path,
+    ) -> Result<Vec<Commit>, ErrorType> {
+        let mut walk = RevWalk::new(path_objects);
+        walk.merge_bases(&self.get_hash(), &other.get_hash())?
+            .into_iter()
+            .map(|hash| walk.read(&hash).cloned())
+            .collect()
+    }
 
-        for commit in history2 {
-            if hist1_set.contains(&commit.get_hash()) {
-                return Ok(Some(commit));
-            }
-        }
-        Ok(None)
+    /// Cantidad de commits alcanzables desde self que no lo son desde other (ahead)
+    /// y al reves (behind)
+    pub(crate) fn ahead_behind(
+        &self,
+        other: &Commit,
+        path_objects: &Path,
+    ) -> Result<(usize, usize), ErrorType> {
+        let mut walk = RevWalk::new(path_objects);
+        walk.push(self.get_hash());
+        walk.hide(other.get_hash());
+        let ahead = walk.hashes()?.len();
+
+        walk.reset();
+        walk.push(other.get_hash());
+        walk.hide(self.get_hash());
+        let behind = walk.hashes()?.len();
+        Ok((ahead, behind))
     }
 
     // pub(crate) fn get_blob(
@@ -449,11 +454,38 @@ impl Commit {
     }
 
     pub fn get_date(&self) -> DateTime<Local> {
-        self.author_date
+        self.author_date.with_timezone(&Local)
+    }
+
+    /// Tree del commit, se lee de objects la primera vez que se pide
+    pub(crate) fn get_tree(&self) -> Result<&Tree, ErrorType> {
+        if let Some(tree) = self.tree.get() {
+            return Ok(tree);
+        }
+        let tree = GitObject::read_tree(&self.tree_hash, &self.path_objects)?;
+        Ok(self.tree.get_or_init(|| tree))
+    }
+
+    pub(crate) fn get_tree_hash(&self) -> GitHash {
+        self.tree_hash.clone()
+    }
+
+    pub(crate) fn get_parent_hash(&self) -> Option<GitHash> {
+        self.parent_hash.clone()
+    }
+
+    /// Padres del commit, primero el de la branch en la que se hizo
+    pub(crate) fn get_parents(&self) -> Vec<GitHash> {
+        self.parent_hash
+            .iter()
+            .chain(self.second_parent_hash.iter())
+            .chain(self.other_parent_hashes.iter())
+            .cloned()
+            .collect()
     }
 
-    pub(crate) fn get_tree(&self) -> &Tree {
-        &self.tree
+    pub(crate) fn get_committer_date(&self) -> DateTime<FixedOffset> {
+        self.committer_date
     }
 
     pub fn set_parent_hash(&mut self, new_hash: Option<GitHash>) -> Result<(), ErrorType> {
@@ -472,6 +504,13 @@ impl Commit {
     }
 }
 
+// el hash identifica al contenido, no importa si el tree ya se cargo o no
+impl PartialEq for Commit {
+    fn eq(&self, other: &Self) -> bool {
+        self.hash == other.hash
+    }
+}
+
 impl Serialize for Commit {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
@@ -606,3 +645,75 @@ impl Serialize for Commit {
 //         }
 //     }
 // }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TREE_HASH: &str = "bcb17e0f3bc2007a51885fbc40a2343bdbc140b3";
+
+    #[test]
+    fn same_hash_as_git() -> Result<(), ErrorType> {
+        let user = User::new("Ada Lovelace", "ada@example.com");
+        let date = FixedOffset::west_opt(3 * 3600)
+            .and_then(|offset| offset.timestamp_opt(1700000000, 0).single())
+            .ok_or(ErrorType::FormatError("invalid date".to_string()))?;
+        let content = Commit::generate_content(
+            &GitHash::new(TREE_HASH)?,
+            &[],
+            (&user, &date),
+            (&user, &date),
+            &[],
+            "first commit",
+        )?;
+        // git hash-object -t commit con el mismo contenido
+        assert_eq!(
+            GitHash::hash_commit(&content),
+            GitHash::new("90a370414d1607d5374f6f90a4a4062cfc7a084c")?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn user_line_with_spaces_and_offset() -> Result<(), ErrorType> {
+        let (user, date) =
+            Commit::parse_user_line("Ada King Lovelace <ada@example.com> 1700000000 +0530")?;
+        assert_eq!(user, User::new("Ada King Lovelace", "ada@example.com"));
+        assert_eq!(date.timestamp(), 1700000000);
+        assert_eq!(date.offset().local_minus_utc(), 5 * 3600 + 30 * 60);
+        assert!(Commit::parse_user_line("ada ada@example.com 1700000000 +0000").is_err());
+        assert!(Commit::parse_user_line("ada <ada@example.com> 1700000000 0000").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn content_roundtrip_is_byte_exact() -> Result<(), ErrorType> {
+        let path_objects = std::env::temp_dir().join("git_rustico_tests_commit_roundtrip");
+        let _ = std::fs::remove_dir_all(&path_objects);
+        let mut tree = Tree::new();
+        tree.add(
+            Path::new("file"),
+            GitHash::new("d00491fd7e5bb6fa28c517a0bb32b8b506539d4d")?,
+        );
+        tree.save(&path_objects)?;
+
+        let content = format!(
+            "tree {}\n\
+             author Ada Lovelace <ada@example.com> 1700000000 -0300\n\
+             committer Grace Hopper <grace@example.com> 1700000100 +0100\n\
+             encoding ISO-8859-1\n\
+             gpgsig -----BEGIN PGP SIGNATURE-----\n \n firma\n -----END PGP SIGNATURE-----\n\
+             \n\
+             titulo\n\ncuerpo del mensaje\n",
+            tree.get_hash()?
+        );
+        let hash = GitHash::hash_commit(&content.as_bytes().to_vec());
+        let commit = Commit::from_object(&hash, content.clone(), &path_objects)?;
+
+        assert_eq!(commit.get_message(), "titulo\n\ncuerpo del mensaje");
+        assert_eq!(commit.get_content()?, content.into_bytes());
+
+        std::fs::remove_dir_all(&path_objects)?;
+        Ok(())
+    }
+}
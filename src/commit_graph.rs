@@ -0,0 +1,447 @@
+use crate::commit::Commit;
+use crate::git_errors::errors::ErrorType;
+use crate::git_object::GitObject;
+use crate::hash::GitHash;
+use std::collections::HashMap;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+////////////////////////////////////////////////////////////////////////////////////////
+// COMMIT GRAPH (objects/info/commit-graph version 1)
+////////////////////////////////////////////////////////////////////////////////////////
+
+// external docs:
+// https://git-scm.com/docs/gitformat-commit-graph
+
+// header          8      -> CGPH + version (1) + hash version (1, sha1) + amount of chunks + 0
+// chunk table (c+1) * 12 -> id and offset of every chunk, an id 0 marks where the last one ends
+// OIDF          256 * 4  -> amount of commits whose first hash byte is <= i
+// OIDL            n * 20 -> sorted commit hashes
+// CDAT            n * 36 -> tree hash, position in OIDL of both parents (or NO_PARENT),
+//                           generation (30 bits) and commit date (34 bits)
+// EDGE            e * 4  -> only if there are merges of more than 2 parents: their second
+//                           parent in CDAT is EXTRA_EDGES | index in EDGE, where the positions
+//                           of the parents from the second on are listed, LAST_EDGE marks the last
+// checksum
+
+const GRAPH_SIGNATURE: [u8; 4] = *b"CGPH";
+const GRAPH_VERSION: u8 = 1;
+const HASH_VERSION: u8 = 1;
+const CHUNK_FANOUT: [u8; 4] = *b"OIDF";
+const CHUNK_LOOKUP: [u8; 4] = *b"OIDL";
+const CHUNK_DATA: [u8; 4] = *b"CDAT";
+const CHUNK_EDGES: [u8; 4] = *b"EDGE";
+const HEADER_SIZE: usize = 8;
+const CHUNK_ENTRY_SIZE: usize = 12;
+const FANOUT_ENTRIES: usize = 256;
+const HASH_SIZE: usize = 20;
+const DATA_SIZE: usize = HASH_SIZE + 16;
+const NO_PARENT: u32 = 0x70000000;
+const EXTRA_EDGES: u32 = 0x80000000;
+const LAST_EDGE: u32 = 0x80000000;
+const MAX_GENERATION: u32 = 0x3FFFFFFF;
+const MAX_DATE: i64 = 0x3FFFFFFFF;
+const INFO_DIR: &str = "info";
+const GRAPH_FILE: &str = "commit-graph";
+
+/// Generacion de los commits que no estan en el commit-graph
+pub(crate) const GENERATION_INFINITY: u32 = u32::MAX;
+
+/// Lo que el commit-graph guarda de cada commit
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct GraphEntry {
+    tree: GitHash,
+    parents: Vec<GitHash>,
+    date: i64,
+    generation: u32,
+}
+
+impl GraphEntry {
+    pub(crate) fn from_commit(commit: &Commit, generation: u32) -> Self {
+        Self {
+            tree: commit.get_tree_hash(),
+            parents: commit.get_parents(),
+            date: commit.get_committer_date().timestamp().clamp(0, MAX_DATE),
+            generation,
+        }
+    }
+
+    pub(crate) fn get_parents(&self) -> &[GitHash] {
+        &self.parents
+    }
+
+    /// Fecha del committer en segundos
+    pub(crate) fn get_date(&self) -> i64 {
+        self.date
+    }
+
+    /// 1 para los commits sin padres, uno mas que el mayor de sus padres para el resto.
+    /// Un commit nunca es ancestro de otro con generacion menor o igual
+    pub(crate) fn get_generation(&self) -> u32 {
+        self.generation
+    }
+}
+
+/// Parents, tree, fecha y generacion de los commits, para recorrer la historia sin leer
+/// los objects. Si un commit esta, tambien estan todos sus ancestros.
+#[derive(Debug, Default)]
+pub struct CommitGraph {
+    entries: HashMap<GitHash, GraphEntry>,
+}
+
+impl CommitGraph {
+    /// Lee objects/info/commit-graph, si no existe el commit-graph esta vacio
+    pub fn open(path_objects: &Path) -> Result<Self, ErrorType> {
+        let path = Self::get_path(path_objects);
+        if !path.is_file() {
+            return Ok(Self::default());
+        }
+        Self::parse(&fs::read(path)?)
+    }
+
+    /// Agrega los commits alcanzables desde tips que todavia no estan y lo guarda.
+    /// Si el commit-graph que habia no se puede leer se arma uno nuevo
+    pub fn update(path_objects: &Path, tips: &[GitHash]) -> Result<(), ErrorType> {
+        let mut graph = Self::open(path_objects).unwrap_or_default();
+        if graph.add(tips, path_objects)? {
+            graph.save(path_objects)?;
+        }
+        Ok(())
+    }
+
+    /// Vuelve a escribir el commit-graph solo con los commits alcanzables desde tips
+    pub fn rebuild(path_objects: &Path, tips: &[GitHash]) -> Result<(), ErrorType> {
+        let mut graph = Self::default();
+        graph.add(tips, path_objects)?;
+        graph.save(path_objects)
+    }
+
+    pub(crate) fn get(&self, hash: &GitHash) -> Option<&GraphEntry> {
+        self.entries.get(hash)
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    // agrega los commits que faltan, cada uno despues de sus padres para saber su generacion.
+    // Devuelve si se agrego alguno
+    fn add(&mut self, tips: &[GitHash], path_objects: &Path) -> Result<bool, ErrorType> {
+        let mut read: HashMap<GitHash, Commit> = HashMap::new();
+        let mut pending: Vec<(GitHash, bool)> =
+            tips.iter().map(|hash| (hash.clone(), false)).collect();
+
+        while let Some((hash, parents_added)) = pending.pop() {
+            if self.entries.contains_key(&hash) {
+                continue;
+            }
+            if !parents_added {
+                if !read.contains_key(&hash) {
+                    read.insert(hash.clone(), GitObject::read_commit(&hash, path_objects)?);
+                }
+                let parents = read[&hash].get_parents();
+                pending.push((hash, true));
+                for parent in parents {
+                    if !self.entries.contains_key(&parent) {
+                        pending.push((parent, false));
+                    }
+                }
+                continue;
+            }
+            let commit = &read[&hash];
+            let generation = commit
+                .get_parents()
+                .iter()
+                .filter_map(|parent| self.entries.get(parent))
+                .map(|entry| entry.generation)
+                .max()
+                .unwrap_or(0);
+            let entry = GraphEntry::from_commit(commit, (generation + 1).min(MAX_GENERATION));
+            self.entries.insert(hash, entry);
+        }
+        Ok(!read.is_empty())
+    }
+
+    fn save(&self, path_objects: &Path) -> Result<(), ErrorType> {
+        let path = Self::get_path(path_objects);
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        // se escribe aparte y se renombra, nunca queda un commit-graph a medias
+        let path_tmp = path.with_extension("lock");
+        fs::write(&path_tmp, self.build()?)?;
+        fs::rename(path_tmp, path)?;
+        Ok(())
+    }
+
+    fn get_path(path_objects: &Path) -> PathBuf {
+        path_objects.join(INFO_DIR).join(GRAPH_FILE)
+    }
+
+    fn build(&self) -> Result<Vec<u8>, ErrorType> {
+        let mut hashes: Vec<&GitHash> = self.entries.keys().collect();
+        hashes.sort();
+        let positions: HashMap<&GitHash, u32> = hashes
+            .iter()
+            .enumerate()
+            .map(|(position, hash)| (*hash, position as u32))
+            .collect();
+
+        let names = hashes
+            .iter()
+            .map(|hash| hash.to_hex())
+            .collect::<Result<Vec<_>, _>>()?;
+        let mut counts = [0u32; FANOUT_ENTRIES];
+        for name in &names {
+            counts[name[0] as usize] += 1;
+        }
+        let mut fanout = Vec::new();
+        let mut amount = 0;
+        for count in counts {
+            amount += count;
+            fanout.write_all(&amount.to_be_bytes())?;
+        }
+        let lookup = names.concat();
+
+        let mut data = Vec::new();
+        let mut edges = Vec::new();
+        for hash in &hashes {
+            let entry = &self.entries[*hash];
+            let mut parents = entry
+                .parents
+                .iter()
+                .map(|parent| positions.get(parent).copied().unwrap_or(NO_PARENT));
+            let first = parents.next().unwrap_or(NO_PARENT);
+            let second = match entry.parents.len() {
+                0..=2 => parents.next().unwrap_or(NO_PARENT),
+                _ => {
+                    let start = EXTRA_EDGES | (edges.len() / 4) as u32;
+                    let mut parents = parents.peekable();
+                    while let Some(position) = parents.next() {
+                        let last = parents.peek().is_none();
+                        let position = if last { position | LAST_EDGE } else { position };
+                        edges.write_all(&position.to_be_bytes())?;
+                    }
+                    start
+                }
+            };
+            data.write_all(&entry.tree.to_hex()?)?;
+            data.write_all(&first.to_be_bytes())?;
+            data.write_all(&second.to_be_bytes())?;
+            let date_high = (entry.date >> 32) as u32 & 0x3;
+            data.write_all(&((entry.generation << 2) | date_high).to_be_bytes())?;
+            data.write_all(&(entry.date as u32).to_be_bytes())?;
+        }
+
+        let mut chunks = vec![
+            (CHUNK_FANOUT, fanout),
+            (CHUNK_LOOKUP, lookup),
+            (CHUNK_DATA, data),
+        ];
+        if !edges.is_empty() {
+            chunks.push((CHUNK_EDGES, edges));
+        }
+
+        let mut graph = GRAPH_SIGNATURE.to_vec();
+        graph.write_all(&[GRAPH_VERSION, HASH_VERSION, chunks.len() as u8, 0])?;
+        let mut start = HEADER_SIZE + (chunks.len() + 1) * CHUNK_ENTRY_SIZE;
+        for (id, chunk) in &chunks {
+            graph.write_all(id)?;
+            graph.write_all(&(start as u64).to_be_bytes())?;
+            start += chunk.len();
+        }
+        graph.write_all(&[0; 4])?;
+        graph.write_all(&(start as u64).to_be_bytes())?;
+        for (_, chunk) in &chunks {
+            graph.write_all(chunk)?;
+        }
+
+        let checksum = GitHash::hash_sha1(&graph);
+        graph.write_all(&checksum.to_hex()?)?;
+        Ok(graph)
+    }
+
+    fn parse(graph: &[u8]) -> Result<Self, ErrorType> {
+        let invalid =
+            |reason: &str| ErrorType::FormatError(format!("invalid commit-graph: {reason}"));
+        if graph.len() < HEADER_SIZE + HASH_SIZE
+            || graph[..4] != GRAPH_SIGNATURE
+            || graph[4] != GRAPH_VERSION
+            || graph[5] != HASH_VERSION
+        {
+            return Err(invalid("only version 1 with sha1 is supported"));
+        }
+        let (content, checksum) = graph.split_at(graph.len() - HASH_SIZE);
+        if GitHash::hash_sha1(&content.to_vec()).to_hex()? != checksum {
+            return Err(invalid("wrong checksum"));
+        }
+
+        let mut chunks = HashMap::new();
+        for i in 0..graph[6] as usize {
+            let start = HEADER_SIZE + i * CHUNK_ENTRY_SIZE;
+            let table = content
+                .get(start..start + 2 * CHUNK_ENTRY_SIZE)
+                .ok_or(invalid("truncated chunk table"))?;
+            let id = [table[0], table[1], table[2], table[3]];
+            let chunk_start = read_u64(table, 4) as usize;
+            let chunk_end = read_u64(table, CHUNK_ENTRY_SIZE + 4) as usize;
+            let chunk = content
+                .get(chunk_start..chunk_end)
+                .ok_or(invalid("chunk out of range"))?;
+            chunks.insert(id, chunk);
+        }
+        let (Some(lookup), Some(data)) = (chunks.get(&CHUNK_LOOKUP), chunks.get(&CHUNK_DATA))
+        else {
+            return Err(invalid("missing OIDL or CDAT chunk"));
+        };
+        let amount = lookup.len() / HASH_SIZE;
+        if lookup.len() % HASH_SIZE != 0 || data.len() != amount * DATA_SIZE {
+            return Err(invalid("chunk sizes don't match"));
+        }
+
+        let hashes = lookup
+            .chunks(HASH_SIZE)
+            .map(GitHash::from_hex)
+            .collect::<Result<Vec<_>, _>>()?;
+        let edges = chunks.get(&CHUNK_EDGES).copied().unwrap_or_default();
+        let mut entries = HashMap::new();
+        for (hash, entry) in hashes.iter().zip(data.chunks(DATA_SIZE)) {
+            let mut positions = vec![read_u32(entry, HASH_SIZE)];
+            let second = read_u32(entry, HASH_SIZE + 4);
+            if second & EXTRA_EDGES == 0 {
+                positions.push(second);
+            } else {
+                // desde el segundo, los padres de un merge octopus estan en EDGE
+                let mut edge = (second & !EXTRA_EDGES) as usize * 4;
+                loop {
+                    let position = edges
+                        .get(edge..edge + 4)
+                        .map(|bytes| read_u32(bytes, 0))
+                        .ok_or(invalid("edge out of range"))?;
+                    positions.push(position & !LAST_EDGE);
+                    if position & LAST_EDGE != 0 {
+                        break;
+                    }
+                    edge += 4;
+                }
+            }
+            let mut parents = Vec::new();
+            for position in positions {
+                if position == NO_PARENT {
+                    continue;
+                }
+                let parent = hashes
+                    .get(position as usize)
+                    .ok_or(invalid("parent out of range"))?;
+                parents.push(parent.clone());
+            }
+            let generation_date = read_u32(entry, HASH_SIZE + 8);
+            let date =
+                ((generation_date & 0x3) as i64) << 32 | read_u32(entry, HASH_SIZE + 12) as i64;
+            entries.insert(
+                hash.clone(),
+                GraphEntry {
+                    tree: GitHash::from_hex(&entry[..HASH_SIZE])?,
+                    parents,
+                    date,
+                    generation: generation_date >> 2,
+                },
+            );
+        }
+        Ok(Self { entries })
+    }
+}
+
+fn read_u32(bytes: &[u8], start: usize) -> u32 {
+    u32::from_be_bytes([
+        bytes[start],
+        bytes[start + 1],
+        bytes[start + 2],
+        bytes[start + 3],
+    ])
+}
+
+fn read_u64(bytes: &[u8], start: usize) -> u64 {
+    ((read_u32(bytes, start) as u64) << 32) | read_u32(bytes, start + 4) as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::files::object_type::ObjectType;
+    use crate::test_utils::commit_with_parents;
+
+    #[test]
+    fn incremental_update_and_roundtrip() -> Result<(), ErrorType> {
+        let path_objects = std::env::temp_dir().join("git_rustico_tests_commit_graph");
+        let _ = fs::remove_dir_all(&path_objects);
+
+        let a = commit_with_parents(&path_objects, &[], "a")?.get_hash();
+        let b = commit_with_parents(&path_objects, &[&a], "b")?.get_hash();
+        let c = commit_with_parents(&path_objects, &[&a], "c")?.get_hash();
+        CommitGraph::update(&path_objects, std::slice::from_ref(&b))?;
+        assert_eq!(CommitGraph::open(&path_objects)?.len(), 2);
+
+        let m = commit_with_parents(&path_objects, &[&b, &c], "m")?.get_hash();
+        CommitGraph::update(&path_objects, std::slice::from_ref(&m))?;
+        let graph = CommitGraph::open(&path_objects)?;
+        assert_eq!(graph.len(), 4);
+
+        let merge = graph
+            .get(&m)
+            .ok_or(ErrorType::FormatError("m".to_string()))?;
+        let read = GitObject::read_commit(&m, &path_objects)?;
+        assert_eq!(merge, &GraphEntry::from_commit(&read, 3));
+        assert_eq!(merge.get_parents(), [b.clone(), c]);
+        assert_eq!(graph.get(&a).map(|entry| entry.get_generation()), Some(1));
+
+        // solo quedan los alcanzables desde b
+        CommitGraph::rebuild(&path_objects, &[b])?;
+        assert_eq!(CommitGraph::open(&path_objects)?.len(), 2);
+
+        fs::remove_dir_all(&path_objects)?;
+        Ok(())
+    }
+    #[test]
+    fn octopus_merges_keep_every_parent() -> Result<(), ErrorType> {
+        let path_objects = std::env::temp_dir().join("git_rustico_tests_commit_graph_octopus");
+        let _ = fs::remove_dir_all(&path_objects);
+
+        let a = commit_with_parents(&path_objects, &[], "a")?;
+        let mut parents = Vec::new();
+        for content in ["b", "c", "d", "e"] {
+            parents.push(commit_with_parents(&path_objects, &[&a.get_hash()], content)?.get_hash());
+        }
+        let mut content = format!("tree {}\n", a.get_tree_hash());
+        for parent in &parents {
+            content.push_str(&format!("parent {parent}\n"));
+        }
+        content.push_str("author a <a> 0 +0000\ncommitter a <a> 0 +0000\n\noctopus\n");
+        let octopus = GitHash::hash_commit(&content.as_bytes().to_vec());
+        GitObject::save_object(content.into_bytes(), ObjectType::Commit, &path_objects)?;
+        // un merge de dos padres sigue guardando el segundo en CDAT
+        let after = commit_with_parents(&path_objects, &[&octopus, &parents[0]], "after")?;
+
+        CommitGraph::update(&path_objects, &[after.get_hash()])?;
+        let graph = CommitGraph::open(&path_objects)?;
+        let entry = graph
+            .get(&octopus)
+            .ok_or(ErrorType::FormatError("octopus".to_string()))?;
+        assert_eq!(entry.get_parents(), parents);
+        assert_eq!(entry.get_generation(), 3);
+        assert_eq!(
+            graph
+                .get(&after.get_hash())
+                .map(|entry| entry.get_parents().len()),
+            Some(2)
+        );
+
+        fs::remove_dir_all(&path_objects)?;
+        Ok(())
+    }
+}
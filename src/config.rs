@@ -1,5 +1,7 @@
 use crate::{
     git_errors::{command_error::CommandError::IncorrectOptionAmount, errors::ErrorType},
+    merge::ConflictStyle,
+    protocol::pack_file::PackOptions,
     repo_paths::RepoPaths,
     user::User,
 };
@@ -11,11 +13,17 @@ use std::{
 
 const USER_NAME_CATEGORY: &str = "user_name:";
 const USER_MAIL_CATEGORY: &str = "user_mail:";
+const PACK_WINDOW_CATEGORY: &str = "pack_window:";
+const PACK_DEPTH_CATEGORY: &str = "pack_depth:";
+const MERGE_CONFLICT_STYLE_CATEGORY: &str = "merge_conflict_style:";
 
 pub struct RepoConfig {
     path_config: PathBuf,
     user_name: Option<String>,
     user_mail: Option<String>,
+    pack_window: Option<usize>,
+    pack_depth: Option<usize>,
+    conflict_style: Option<ConflictStyle>,
     // remote : String
 }
 
@@ -28,6 +36,9 @@ impl RepoConfig {
         }
         let mut user_name = None;
         let mut user_mail = None;
+        let mut pack_window = None;
+        let mut pack_depth = None;
+        let mut conflict_style = None;
 
         let file = File::open(&path_config)?;
         let reader = BufReader::new(file);
@@ -36,6 +47,11 @@ impl RepoConfig {
                 match category {
                     USER_NAME_CATEGORY => user_name = Some(value.to_string()),
                     USER_MAIL_CATEGORY => user_mail = Some(value.to_string()),
+                    PACK_WINDOW_CATEGORY => pack_window = Some(Self::parse_number(value)?),
+                    PACK_DEPTH_CATEGORY => pack_depth = Some(Self::parse_number(value)?),
+                    MERGE_CONFLICT_STYLE_CATEGORY => {
+                        conflict_style = Some(ConflictStyle::parse(value)?)
+                    }
                     _ => {
                         return Err(ErrorType::FormatError(
                             "Invalid category '{category}' in .git-rustico/config".to_string(),
@@ -48,6 +64,15 @@ impl RepoConfig {
             path_config,
             user_name,
             user_mail,
+            pack_window,
+            pack_depth,
+            conflict_style,
+        })
+    }
+
+    fn parse_number(value: &str) -> Result<usize, ErrorType> {
+        value.trim().parse::<usize>().map_err(|_| {
+            ErrorType::FormatError(format!("Invalid number '{value}' in .git-rustico/config"))
         })
     }
 
@@ -59,6 +84,36 @@ impl RepoConfig {
         self.user_mail = Some(mail.to_string());
     }
 
+    pub fn set_pack_window(&mut self, window: &str) -> Result<(), ErrorType> {
+        self.pack_window = Some(Self::parse_number(window)?);
+        Ok(())
+    }
+
+    pub fn set_pack_depth(&mut self, depth: &str) -> Result<(), ErrorType> {
+        self.pack_depth = Some(Self::parse_number(depth)?);
+        Ok(())
+    }
+
+    pub fn set_conflict_style(&mut self, style: &str) -> Result<(), ErrorType> {
+        self.conflict_style = Some(ConflictStyle::parse(style)?);
+        Ok(())
+    }
+
+    /// Como se escriben los conflictos de los merges, por defecto sin la base
+    pub fn get_conflict_style(&self) -> ConflictStyle {
+        self.conflict_style.unwrap_or_default()
+    }
+
+    /// Opciones para comprimir con deltas los packfiles que se envian.
+    /// Las que no esten configuradas toman el valor por defecto.
+    pub fn get_pack_options(&self) -> PackOptions {
+        let default = PackOptions::default();
+        PackOptions::new(
+            self.pack_window.unwrap_or(default.get_window()),
+            self.pack_depth.unwrap_or(default.get_depth()),
+        )
+    }
+
     pub fn get_user(&self) -> Option<User> {
         match (&self.user_name, &self.user_mail) {
             (Some(n), Some(m)) => Some(User::new(n, m)),
@@ -83,6 +138,19 @@ impl RepoConfig {
                 format_args!("{USER_MAIL_CATEGORY} {}", umail)
             )?;
         }
+        if let Some(window) = &self.pack_window {
+            writeln!(content, "{PACK_WINDOW_CATEGORY} {window}")?;
+        }
+        if let Some(depth) = &self.pack_depth {
+            writeln!(content, "{PACK_DEPTH_CATEGORY} {depth}")?;
+        }
+        if let Some(style) = &self.conflict_style {
+            writeln!(
+                content,
+                "{MERGE_CONFLICT_STYLE_CATEGORY} {}",
+                style.as_str()
+            )?;
+        }
 
         let mut file = File::create(&self.path_config)?;
         file.write_all(&content)?;
@@ -91,6 +159,9 @@ impl RepoConfig {
 
     // user_name: theo
     // user_mail: mail
+    // pack_window: 10
+    // pack_depth: 50
+    // merge_conflict_style: diff3
 
     //ARGS: ["--user-name", "theo", "--user-mail", "mail"]
     pub fn config_command(repo_paths: RepoPaths, args: Vec<String>) -> Result<String, ErrorType> {
@@ -115,11 +186,24 @@ impl RepoConfig {
                         config.set_user_mail(arg);
                         result.push(format!("Set user mail {}.", arg))
                     }
+                    "--pack-window" => {
+                        config.set_pack_window(arg)?;
+                        result.push(format!("Set pack window {}.", arg))
+                    }
+                    "--pack-depth" => {
+                        config.set_pack_depth(arg)?;
+                        result.push(format!("Set pack depth {}.", arg))
+                    }
+                    "--merge-conflict-style" => {
+                        config.set_conflict_style(arg)?;
+                        result.push(format!("Set merge conflict style {}.", arg))
+                    }
                     _ => {
                         return Err(ErrorType::CommandError(
                             crate::git_errors::command_error::CommandError::UnknownOption(
                                 arg.to_string(),
-                                "--user-name or --user-mail".to_string(),
+                                "--user-name, --user-mail, --pack-window, --pack-depth or --merge-conflict-style"
+                                    .to_string(),
                             ),
                         ))
                     }
@@ -1,17 +1,6 @@
 pub struct Diff;
 
-/// en los diff guardo todo:
-/// Add(linea)
-/// Remove(linea)
-/// Same(linea)
-///
-/// itero sobre los dos diffs simultaneamente
-/// si ambos son Same escribo la linea y avanzo ambas pos
-/// si uno es remove y el otro same no escribo nada y avanzo los dos
-/// si uno es add y el otro same escribo el add y avanzo solo el add
-/// si uno es add y el otro remove conflicto hasta que los dos sean same?
-/// si ambos son add conflicto hasta que ambos sean same?
-
+/// Que paso con cada linea del texto original en el modificado
 #[derive(PartialEq, Debug)]
 pub enum ModificationType {
     Same(String),
@@ -19,18 +8,45 @@ pub enum ModificationType {
     Remove(String),
 }
 
+/// Parte del resultado de mergear dos versiones de un texto con su base
+#[derive(PartialEq, Debug)]
+pub enum MergeChunk {
+    /// Lineas sin conflicto: iguales en las tres, o cambiadas por una sola version o por las
+    /// dos de la misma forma
+    Resolved(Vec<String>),
+    /// Las dos versiones cambiaron de forma distinta las mismas lineas (o lineas pegadas)
+    Conflict {
+        base: Vec<String>,
+        head: Vec<String>,
+        branch: Vec<String>,
+    },
+}
+
+// cambio de una version respecto de la base: reemplaza las lineas [start, end) de la base
+// por lines. Con start == end es un agregado antes de la linea start
+#[derive(Debug)]
+struct Hunk {
+    start: usize,
+    end: usize,
+    lines: Vec<String>,
+}
+
 impl Diff {
     // Given two texts (one is a modified version of the first) it returns a vector
     // of ModificationType enum which represents what happened to that line in the change.
     // It can be either the same in both, removed from the original or added in the modified
     // version. The length of the vec is the length of the original plus the ammount of added lines.
     pub fn diff(original: &str, modified: &str) -> Vec<ModificationType> {
-        let mut diff: Vec<ModificationType> = Vec::new();
-
         let original_lines: Vec<&str> = original.lines().collect();
         let modified_lines: Vec<&str> = modified.lines().collect();
+        Self::diff_lines(&original_lines, &modified_lines)
+    }
+
+    // diff de textos ya separados en lineas
+    fn diff_lines(original_lines: &[&str], modified_lines: &[&str]) -> Vec<ModificationType> {
+        let mut diff: Vec<ModificationType> = Vec::new();
 
-        let lcs = Self::longest_common_line_subsequence(&original_lines, &modified_lines);
+        let lcs = Self::longest_common_line_subsequence(original_lines, modified_lines);
 
         let mut i = 0;
         let mut j = 0;
@@ -67,6 +83,123 @@ impl Diff {
         diff
     }
 
+    /// Merge de tres versiones (diff3): se comparan head y branch contra la base y se aplican
+    /// los cambios de cada una. Donde las dos cambiaron lineas superpuestas o pegadas de la base
+    /// de distinta forma queda un conflicto.
+    ///
+    /// Las lineas conservan su fin de linea (\n o \r\n), la ultima puede no tenerlo. Juntarlas
+    /// tal cual devuelve el texto sin cambiar los fines de linea.
+    pub fn diff3(base: &str, head: &str, branch: &str) -> Vec<MergeChunk> {
+        let base_lines: Vec<&str> = base.split_inclusive('\n').collect();
+        let head_lines: Vec<&str> = head.split_inclusive('\n').collect();
+        let branch_lines: Vec<&str> = branch.split_inclusive('\n').collect();
+        let head_hunks = Self::hunks(&Self::diff_lines(&base_lines, &head_lines));
+        let branch_hunks = Self::hunks(&Self::diff_lines(&base_lines, &branch_lines));
+        let base_lines: Vec<String> = base_lines.iter().map(|line| line.to_string()).collect();
+
+        let mut chunks = Vec::new();
+        let mut resolved = Vec::new();
+        let mut next_line = 0;
+        let (mut i, mut j) = (0, 0);
+        while i < head_hunks.len() || j < branch_hunks.len() {
+            // la region arranca con el primer hunk y crece mientras haya hunks que la toquen
+            let (head_from, branch_from) = (i, j);
+            let head_first = j == branch_hunks.len()
+                || (i < head_hunks.len() && head_hunks[i].start <= branch_hunks[j].start);
+            let (start, mut end) = match head_first {
+                true => (head_hunks[i].start, head_hunks[i].end),
+                false => (branch_hunks[j].start, branch_hunks[j].end),
+            };
+            match head_first {
+                true => i += 1,
+                false => j += 1,
+            }
+            loop {
+                if i < head_hunks.len() && head_hunks[i].start <= end {
+                    end = end.max(head_hunks[i].end);
+                    i += 1;
+                } else if j < branch_hunks.len() && branch_hunks[j].start <= end {
+                    end = end.max(branch_hunks[j].end);
+                    j += 1;
+                } else {
+                    break;
+                }
+            }
+
+            resolved.extend_from_slice(&base_lines[next_line..start]);
+            next_line = end;
+            let head_version = Self::apply(&base_lines, start, end, &head_hunks[head_from..i]);
+            let branch_version =
+                Self::apply(&base_lines, start, end, &branch_hunks[branch_from..j]);
+            if head_from == i || head_version == branch_version {
+                resolved.extend(branch_version);
+            } else if branch_from == j {
+                resolved.extend(head_version);
+            } else {
+                if !resolved.is_empty() {
+                    chunks.push(MergeChunk::Resolved(std::mem::take(&mut resolved)));
+                }
+                chunks.push(MergeChunk::Conflict {
+                    base: base_lines[start..end].to_vec(),
+                    head: head_version,
+                    branch: branch_version,
+                });
+            }
+        }
+        resolved.extend_from_slice(&base_lines[next_line..]);
+        if !resolved.is_empty() {
+            chunks.push(MergeChunk::Resolved(resolved));
+        }
+        chunks
+    }
+
+    // junta las lineas agregadas y borradas seguidas de un diff en hunks
+    fn hunks(diff: &[ModificationType]) -> Vec<Hunk> {
+        let mut hunks = Vec::new();
+        let mut current: Option<Hunk> = None;
+        let mut base_line = 0;
+        for modification in diff {
+            match modification {
+                ModificationType::Same(_) => {
+                    hunks.extend(current.take());
+                    base_line += 1;
+                }
+                ModificationType::Remove(_) => {
+                    let hunk = current.get_or_insert(Hunk {
+                        start: base_line,
+                        end: base_line,
+                        lines: Vec::new(),
+                    });
+                    hunk.end += 1;
+                    base_line += 1;
+                }
+                ModificationType::Add(line) => {
+                    let hunk = current.get_or_insert(Hunk {
+                        start: base_line,
+                        end: base_line,
+                        lines: Vec::new(),
+                    });
+                    hunk.lines.push(line.clone());
+                }
+            }
+        }
+        hunks.extend(current);
+        hunks
+    }
+
+    // lineas [start, end) de la base con los hunks (que estan dentro de ese rango) aplicados
+    fn apply(base_lines: &[String], start: usize, end: usize, hunks: &[Hunk]) -> Vec<String> {
+        let mut lines = Vec::new();
+        let mut position = start;
+        for hunk in hunks {
+            lines.extend_from_slice(&base_lines[position..hunk.start]);
+            lines.extend(hunk.lines.iter().cloned());
+            position = hunk.end;
+        }
+        lines.extend_from_slice(&base_lines[position..end]);
+        lines
+    }
+
     /// Given two vecs of &str representing the lines in two texts it returns a Vec of Strings
     /// which is the longest common subsequence of lines shared by both texts
     fn longest_common_line_subsequence(lines1: &[&str], lines2: &[&str]) -> Vec<String> {
@@ -184,6 +317,26 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_diff3_conflict_only_where_both_changed() {
+        let base = "1\n2\n3\n4\n5\n";
+        let head = "1\ntwo\n3\n4\n5\n6\n";
+        let branch = "1\nTWO\n3\n5\n";
+        let lines = |lines: &[&str]| lines.iter().map(|l| l.to_string()).collect::<Vec<_>>();
+        assert_eq!(
+            Diff::diff3(base, head, branch),
+            vec![
+                MergeChunk::Resolved(lines(&["1\n"])),
+                MergeChunk::Conflict {
+                    base: lines(&["2\n"]),
+                    head: lines(&["two\n"]),
+                    branch: lines(&["TWO\n"]),
+                },
+                MergeChunk::Resolved(lines(&["3\n", "5\n", "6\n"])),
+            ]
+        );
+    }
+
     #[test]
     fn test_diff_empty_input() {
         let original = "";
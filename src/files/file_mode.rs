@@ -0,0 +1,177 @@
+use core::fmt;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::git_errors::errors::ErrorType;
+
+const MODE_REGULAR: &str = "100644";
+const MODE_EXECUTABLE: &str = "100755";
+const MODE_SYMLINK: &str = "120000";
+const MODE_GITLINK: &str = "160000";
+
+/// Modo con el que se guarda cada archivo en los trees (y en el index).
+/// - 100644: archivo normal
+/// - 100755: archivo ejecutable
+/// - 120000: enlace simbolico, el blob guarda el path al que apunta
+/// - 160000: commit de un submodule (gitlink), no tiene blob
+#[derive(Clone, Debug, PartialEq, Copy)]
+pub enum FileMode {
+    Regular,
+    Executable,
+    Symlink,
+    Gitlink,
+}
+
+impl FileMode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            FileMode::Regular => MODE_REGULAR,
+            FileMode::Executable => MODE_EXECUTABLE,
+            FileMode::Symlink => MODE_SYMLINK,
+            FileMode::Gitlink => MODE_GITLINK,
+        }
+    }
+
+    /// Modo como entero, tal como se guarda en las entradas del index
+    pub(crate) fn as_u32(&self) -> u32 {
+        match self {
+            FileMode::Regular => 0o100644,
+            FileMode::Executable => 0o100755,
+            FileMode::Symlink => 0o120000,
+            FileMode::Gitlink => 0o160000,
+        }
+    }
+
+    /// Modo del archivo en el working directory. Los symlinks no se siguen.
+    pub(crate) fn from_path(path: &Path) -> Result<Self, ErrorType> {
+        let metadata = fs::symlink_metadata(path)?;
+        if metadata.file_type().is_symlink() {
+            return Ok(FileMode::Symlink);
+        }
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            if metadata.permissions().mode() & 0o111 != 0 {
+                return Ok(FileMode::Executable);
+            }
+        }
+        Ok(FileMode::Regular)
+    }
+
+    /// Contenido con el que se guarda el blob: para un symlink es el path al que apunta
+    pub(crate) fn read_content(&self, path: &Path) -> Result<Vec<u8>, ErrorType> {
+        match self {
+            FileMode::Symlink => Ok(fs::read_link(path)?
+                .to_string_lossy()
+                .to_string()
+                .into_bytes()),
+            _ => Ok(fs::read(path)?),
+        }
+    }
+
+    /// Escribe el contenido del blob en el working directory respetando el modo:
+    /// setea el bit de ejecucion o crea el symlink (en linux)
+    pub(crate) fn write_content(&self, path: &Path, content: &[u8]) -> Result<(), ErrorType> {
+        if let Ok(metadata) = fs::symlink_metadata(path) {
+            if !metadata.is_dir() {
+                fs::remove_file(path)?;
+            }
+        }
+        match self {
+            #[cfg(unix)]
+            FileMode::Symlink => {
+                let target = PathBuf::from(String::from_utf8_lossy(content).to_string());
+                std::os::unix::fs::symlink(target, path)?;
+            }
+            #[cfg(unix)]
+            FileMode::Executable => {
+                use std::os::unix::fs::PermissionsExt;
+                fs::write(path, content)?;
+                let mut permissions = fs::metadata(path)?.permissions();
+                permissions.set_mode(permissions.mode() | 0o111);
+                fs::set_permissions(path, permissions)?;
+            }
+            FileMode::Gitlink => fs::create_dir_all(path)?,
+            _ => fs::write(path, content)?,
+        }
+        Ok(())
+    }
+}
+
+impl TryFrom<&str> for FileMode {
+    type Error = ErrorType;
+
+    fn try_from(mode: &str) -> Result<Self, ErrorType> {
+        match mode {
+            MODE_REGULAR => Ok(FileMode::Regular),
+            MODE_EXECUTABLE => Ok(FileMode::Executable),
+            MODE_SYMLINK => Ok(FileMode::Symlink),
+            MODE_GITLINK => Ok(FileMode::Gitlink),
+            _ => Err(ErrorType::FormatError(format!(
+                "invalid UNIX filesystem mode ('{mode}')"
+            ))),
+        }
+    }
+}
+
+impl TryFrom<u32> for FileMode {
+    type Error = ErrorType;
+
+    fn try_from(mode: u32) -> Result<Self, ErrorType> {
+        Self::try_from(format!("{mode:o}").as_str())
+    }
+}
+
+impl fmt::Display for FileMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mode_roundtrip() -> Result<(), ErrorType> {
+        for mode in [
+            FileMode::Regular,
+            FileMode::Executable,
+            FileMode::Symlink,
+            FileMode::Gitlink,
+        ] {
+            assert_eq!(FileMode::try_from(mode.as_str())?, mode);
+            assert_eq!(FileMode::try_from(mode.as_u32())?, mode);
+        }
+        assert!(FileMode::try_from("100664").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn write_and_read_back_from_working_directory() -> Result<(), ErrorType> {
+        let dir = std::env::temp_dir().join("git_rustico_tests_file_mode");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir)?;
+
+        let script = dir.join("script.sh");
+        FileMode::Executable.write_content(&script, b"#!/bin/sh\n")?;
+        assert_eq!(FileMode::from_path(&script)?, FileMode::Executable);
+
+        let link = dir.join("link");
+        FileMode::Symlink.write_content(&link, b"script.sh")?;
+        assert_eq!(FileMode::from_path(&link)?, FileMode::Symlink);
+        assert_eq!(
+            FileMode::Symlink.read_content(&link)?,
+            b"script.sh".to_vec()
+        );
+
+        // reescribir un ejecutable como archivo normal no conserva el bit
+        FileMode::Regular.write_content(&script, b"echo\n")?;
+        assert_eq!(FileMode::from_path(&script)?, FileMode::Regular);
+
+        fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+}
@@ -1,39 +1,180 @@
-use std::path::{Path, PathBuf};
+use std::{
+    fs,
+    io::Read,
+    path::{Path, PathBuf},
+};
+
+use chrono::NaiveDateTime;
+
+use crate::{
+    files::file_mode::FileMode, git_errors::errors::ErrorType, git_object::GitObject,
+    hash::GitHash, protocol::pack_index::read_array, utils,
+};
+
+// ctime, mtime, dev, ino, mode, uid, gid, size (4 bytes c/u), hash (20) y flags (2)
+const ENTRY_FIXED_SIZE: usize = 62;
+const ENTRY_ALIGNMENT: usize = 8;
+const NAME_LENGTH_MASK: u16 = 0x0fff;
+const STAGE_SHIFT: u16 = 12;
+const STAGE_MASK: u16 = 0x3;
+const EXTENDED_FLAG: u16 = 0x4000;
+// formato de la fecha en los index de texto viejos
+const LEGACY_DATE_FORMAT: &str = "%Y/%m/%d-%H:%M:%S";
+
+/// Datos del stat del archivo que se guardan en cada entrada del index.
+/// Igual que git, los valores se truncan a 32 bits
+#[derive(Debug, PartialEq, Clone, Default)]
+pub(crate) struct StatData {
+    ctime: (u32, u32),
+    mtime: (u32, u32),
+    dev: u32,
+    ino: u32,
+    uid: u32,
+    gid: u32,
+    size: u32,
+}
 
-use chrono::{DateTime, Utc};
+impl StatData {
+    /// Stat del archivo en el working directory. Los symlinks no se siguen
+    pub(crate) fn from_path(path: &Path) -> Result<Self, ErrorType> {
+        let metadata = fs::symlink_metadata(path)?;
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::MetadataExt;
+            Ok(Self {
+                ctime: (metadata.ctime() as u32, metadata.ctime_nsec() as u32),
+                mtime: (metadata.mtime() as u32, metadata.mtime_nsec() as u32),
+                dev: metadata.dev() as u32,
+                ino: metadata.ino() as u32,
+                uid: metadata.uid(),
+                gid: metadata.gid(),
+                size: metadata.size() as u32,
+            })
+        }
+        #[cfg(not(unix))]
+        {
+            let mtime = metadata
+                .modified()?
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap_or_default();
+            let mtime = (mtime.as_secs() as u32, mtime.subsec_nanos());
+            Ok(Self {
+                ctime: mtime,
+                mtime,
+                size: metadata.len() as u32,
+                ..Default::default()
+            })
+        }
+    }
 
-use crate::{git_errors::errors::ErrorType, git_object::GitObject, hash::GitHash, utils};
+    pub(crate) fn get_mtime(&self) -> (u32, u32) {
+        self.mtime
+    }
+
+    /// Compara los datos que cambian cuando se modifica el archivo: tamaño, mtime, inode y ctime
+    fn matches(&self, other: &StatData) -> bool {
+        self.size == other.size
+            && self.mtime == other.mtime
+            && self.ino == other.ino
+            && self.ctime == other.ctime
+    }
+
+    // los index viejos solo tienen la fecha de modificacion con precision de segundos
+    fn from_legacy_date(date: &str) -> Self {
+        let seconds = NaiveDateTime::parse_from_str(date, LEGACY_DATE_FORMAT)
+            .map(|date| date.and_utc().timestamp() as u32)
+            .unwrap_or_default();
+        Self {
+            mtime: (seconds, 0),
+            ..Default::default()
+        }
+    }
+}
+
+/// Resultado de comparar una entrada del index con el archivo del working directory
+#[derive(Debug, PartialEq)]
+pub(crate) enum StatCheck {
+    /// el stat coincide con el de la entrada, no hizo falta leer el archivo
+    Unchanged,
+    /// el contenido no cambio pero se actualizo el stat de la entrada
+    Refreshed,
+    /// el contenido o el modo cambiaron
+    Changed,
+}
 
 // todo rename IndexFileInfo
-/// Struct que guarda la información que tiene cada linea del index
+/// Struct que guarda la información que tiene cada entrada del index
 #[derive(Debug, PartialEq, Clone)]
 pub struct IndexFileInfo {
     path: PathBuf,
-    mod_date: String,
+    stat: StatData,
     current_blob_hash: GitHash,
-    previous_blob_hash: Option<GitHash>, // Useful to implement restore --stashed and to verify changes since commit
+    previous_blob_hash: Option<GitHash>, // Hash antes del ultimo add, None si no cambio desde el ultimo commit
+    mode: FileMode,
+    stage: u8,
 }
 
 impl IndexFileInfo {
     pub(crate) fn new(local_path: PathBuf, home_path: &Path) -> Result<Self, ErrorType> {
         let global_path = home_path.join(&local_path);
         utils::verify_path_exists(&global_path)?;
-        let mod_date = Self::date_modified_as_string(&global_path)?;
+        let stat = StatData::from_path(&global_path)?;
+        let mode = FileMode::from_path(&global_path)?;
         let current_blob_hash = Self::current_blob_hash(&global_path)?;
 
-        // this is initialized as Some(hash) so it can be recognized by status command as a new file
+        // this is initialized as Some(hash) so it can be recognized as a new file
         // it will change to None only when a commit containing this file is created
-        // see "status()" in status.rs and "FileInfo::added_since_commit(&self)"
         let previous_blob_hash = Some(current_blob_hash.clone());
 
         Ok(IndexFileInfo {
             path: local_path,
-            mod_date,
+            stat,
             current_blob_hash,
             previous_blob_hash,
+            mode,
+            stage: 0,
         })
     }
 
+    /// Entrada para un archivo de un tree, sin leer el working directory.
+    /// El stat queda vacio, asi el proximo status compara el contenido del archivo
+    pub(crate) fn from_tree_entry(path: PathBuf, mode: FileMode, hash: GitHash) -> Self {
+        IndexFileInfo {
+            path,
+            stat: StatData::default(),
+            current_blob_hash: hash,
+            previous_blob_hash: None,
+            mode,
+            stage: 0,
+        }
+    }
+
+    /// Entrada de un archivo en conflicto: 1 es la base, 2 la version de HEAD y 3 la que se mergea
+    pub(crate) fn with_stage(mut self, stage: u8) -> Self {
+        self.stage = stage;
+        self
+    }
+
+    pub(crate) fn get_stage(&self) -> u8 {
+        self.stage
+    }
+
+    /// Vuelve la entrada al blob del ultimo commit (restore --staged). El archivo del working
+    /// directory no cambia, por eso se invalida el stat
+    pub(crate) fn restore_blob(&mut self, mode: FileMode, hash: GitHash) {
+        self.mode = mode;
+        self.current_blob_hash = hash;
+        self.previous_blob_hash = None;
+        self.stat = StatData::default();
+    }
+
+    /// Guarda el stat actual del archivo, despues de escribir el blob de la entrada en el
+    /// working directory
+    pub(crate) fn refresh_stat(&mut self, home_path: &Path) -> Result<(), ErrorType> {
+        self.stat = StatData::from_path(&home_path.join(&self.path))?;
+        Ok(())
+    }
+
     pub(crate) fn get_path(&self) -> PathBuf {
         self.path.clone()
     }
@@ -42,17 +183,25 @@ impl IndexFileInfo {
         self.current_blob_hash.clone()
     }
 
+    pub(crate) fn get_mode(&self) -> FileMode {
+        self.mode
+    }
+
     /// Verifica si hubo un cambio en el working directory desde el ultimo add o commit
     pub(crate) fn verify_change(
         &mut self,
         path_objects: &Path,
         home_path: &Path,
+        index_timestamp: Option<(u32, u32)>,
     ) -> Result<(), ErrorType> {
-        let current_date = Self::date_modified_as_string(&home_path.join(&self.path))?;
-        if current_date != self.mod_date {
-            self.mod_date = current_date;
-
-            let new_content = std::fs::read(home_path.join(&self.path))?;
+        let global_path = home_path.join(&self.path);
+        // chmod no cambia la fecha de modificacion, el modo se revisa siempre
+        self.mode = FileMode::from_path(&global_path)?;
+        let current_stat = StatData::from_path(&global_path)?;
+        if !current_stat.matches(&self.stat) || self.is_racy(index_timestamp) {
+            self.stat = current_stat;
+
+            let new_content = self.mode.read_content(&global_path)?;
             let current_blob_hash = GitHash::hash_blob(&new_content);
 
             if current_blob_hash != self.current_blob_hash {
@@ -74,82 +223,210 @@ impl IndexFileInfo {
         Ok(())
     }
 
-    // todo delegar a GitTimeDate
-    /// Devuelve la fecha de modificacion del archivo en el working directory
-    fn date_modified_as_string(path: &PathBuf) -> Result<String, ErrorType> {
-        // utils::verify_path_exists(path)?;
-        let file_metadata = std::fs::metadata(path)?;
-
-        let system_time = file_metadata.modified()?;
-        let modified_time: DateTime<Utc> = system_time.into();
-        let format = "%Y/%m/%d-%H:%M:%S";
-        Ok(modified_time.format(format).to_string())
-    }
-
-    /// Devuelve el hash del archivo en el current working directory
+    /// Devuelve el hash del archivo en el current working directory.
+    /// Para un symlink es el hash del path al que apunta
     fn current_blob_hash(path: &PathBuf) -> Result<GitHash, ErrorType> {
         utils::verify_path_exists(path)?;
-        let content = std::fs::read(path)?;
+        let content = FileMode::from_path(path)?.read_content(path)?;
         Ok(GitHash::hash_blob(&content))
     }
 
-    /// Convierte una instancia en una linea con el formato del archivo index.
-    /// formato : [path fecha_modificacion current_blob_hash previous_blob_hash]
-    pub(crate) fn to_index_line(&self) -> String {
-        let previous = match &self.previous_blob_hash {
-            Some(h) => h.as_str(),
-            None => "None",
-        };
-        format!(
-            "{} {} {} {}\n",
-            self.path.display(),
-            self.mod_date,
-            self.current_blob_hash,
-            previous
-        )
+    /// Convierte una instancia en una entrada del index (formato DIRC version 2):
+    /// stat, modo, hash, flags (stage y largo del path) y el path terminado con
+    /// bytes nulos hasta completar un multiplo de 8
+    pub(crate) fn to_index_entry(&self) -> Result<Vec<u8>, ErrorType> {
+        let stat = &self.stat;
+        let path = utils::path_to_bytes(&self.path);
+        let mut entry = Vec::with_capacity(ENTRY_FIXED_SIZE + path.len() + ENTRY_ALIGNMENT);
+        for field in [
+            stat.ctime.0,
+            stat.ctime.1,
+            stat.mtime.0,
+            stat.mtime.1,
+            stat.dev,
+            stat.ino,
+            self.mode.as_u32(),
+            stat.uid,
+            stat.gid,
+            stat.size,
+        ] {
+            entry.extend(field.to_be_bytes());
+        }
+        entry.extend(self.current_blob_hash.to_hex()?);
+        let name_length = path.len().min(NAME_LENGTH_MASK as usize) as u16;
+        let flags = ((self.stage as u16 & STAGE_MASK) << STAGE_SHIFT) | name_length;
+        entry.extend(flags.to_be_bytes());
+        entry.extend(path);
+        let entry_size = (entry.len() + ENTRY_ALIGNMENT) & !(ENTRY_ALIGNMENT - 1);
+        entry.resize(entry_size, 0);
+        Ok(entry)
+    }
+
+    /// Lee una entrada del index (formato DIRC version 2).
+    /// El previous_blob_hash no forma parte de la entrada, lo completa el index
+    pub(crate) fn read_entry<R: Read>(stream: &mut R) -> Result<Self, ErrorType> {
+        let mut fields = [0u32; 10];
+        for field in fields.iter_mut() {
+            *field = u32::from_be_bytes(read_array(stream)?);
+        }
+        let [ctime_sec, ctime_nsec, mtime_sec, mtime_nsec, dev, ino, mode, uid, gid, size] = fields;
+        let hash: [u8; 20] = read_array(stream)?;
+        let flags = u16::from_be_bytes(read_array(stream)?);
+        if flags & EXTENDED_FLAG != 0 {
+            return Err(ErrorType::FormatError(
+                "extended index entries are not supported in index version 2".to_string(),
+            ));
+        }
+
+        // si el path es muy largo el largo queda en 0xfff y hay que buscar el byte nulo
+        let name_length = (flags & NAME_LENGTH_MASK) as usize;
+        let mut path = vec![0; name_length];
+        stream.read_exact(&mut path)?;
+        let mut read = ENTRY_FIXED_SIZE + name_length;
+        if name_length == NAME_LENGTH_MASK as usize {
+            loop {
+                let [byte] = read_array(stream)?;
+                read += 1;
+                if byte == 0 {
+                    break;
+                }
+                path.push(byte);
+            }
+        }
+        let entry_size = (ENTRY_FIXED_SIZE + path.len() + ENTRY_ALIGNMENT) & !(ENTRY_ALIGNMENT - 1);
+        let mut padding = vec![0; entry_size - read];
+        stream.read_exact(&mut padding)?;
+
+        Ok(Self {
+            path: utils::bytes_to_path(&path),
+            stat: StatData {
+                ctime: (ctime_sec, ctime_nsec),
+                mtime: (mtime_sec, mtime_nsec),
+                dev,
+                ino,
+                uid,
+                gid,
+                size,
+            },
+            current_blob_hash: GitHash::from_hex(&hash)?,
+            previous_blob_hash: None,
+            mode: FileMode::try_from(mode)?,
+            stage: ((flags >> STAGE_SHIFT) & STAGE_MASK) as u8,
+        })
     }
 
     // todo quedo viejo, cambiar por el refactor de objects
     pub(crate) fn save(&self, path_objects: &Path, home_path: &Path) -> Result<(), ErrorType> {
-        let content = std::fs::read(home_path.join(&self.path))?;
+        let content = self.mode.read_content(&home_path.join(&self.path))?;
         GitObject::save_blob(content, path_objects)
     }
 
-    pub(crate) fn has_changed(&self, home_path: &Path) -> Result<bool, ErrorType> {
-        let current_hash = Self::current_blob_hash(&home_path.join(&self.path))?;
-        Ok(current_hash != self.current_blob_hash)
+    /// Verifica si el archivo del working directory cambio respecto de la entrada.
+    /// Solo se hashea el contenido si el stat no coincide o si la entrada es racy.
+    /// Si el contenido es el mismo se guarda el stat nuevo, asi la proxima vez alcanza con el stat
+    pub(crate) fn has_changed(
+        &mut self,
+        home_path: &Path,
+        index_timestamp: Option<(u32, u32)>,
+    ) -> Result<StatCheck, ErrorType> {
+        let global_path = home_path.join(&self.path);
+        if FileMode::from_path(&global_path)? != self.mode {
+            return Ok(StatCheck::Changed);
+        }
+        let current_stat = StatData::from_path(&global_path)?;
+        let same_stat = current_stat.matches(&self.stat);
+        if same_stat && !self.is_racy(index_timestamp) {
+            return Ok(StatCheck::Unchanged);
+        }
+
+        if Self::current_blob_hash(&global_path)? != self.current_blob_hash {
+            if same_stat {
+                // cambio el contenido sin cambiar el stat: se invalida el stat guardado (igual que
+                // git) para que se siga detectando aunque el index se vuelva a escribir despues
+                self.stat.mtime = (0, 0);
+            }
+            return Ok(StatCheck::Changed);
+        }
+        self.stat = current_stat;
+        Ok(StatCheck::Refreshed)
     }
 
-    pub(crate) fn added_since_commit(&self) -> bool {
-        self.previous_blob_hash.is_some()
+    /// Una entrada es racy si el archivo se modifico en el mismo instante (o despues) en que se
+    /// escribio el index: un cambio en ese instante no se ve en el stat y hay que leer el contenido.
+    /// Sin la fecha del index toda entrada se considera racy
+    fn is_racy(&self, index_timestamp: Option<(u32, u32)>) -> bool {
+        match index_timestamp {
+            Some(timestamp) => self.stat.mtime >= timestamp,
+            None => true,
+        }
     }
 
     pub(crate) fn reset_previous_blob_hash(&mut self) {
         self.previous_blob_hash = None
     }
+
+    pub(crate) fn get_previous_blob_hash(&self) -> Option<GitHash> {
+        self.previous_blob_hash.clone()
+    }
+
+    pub(crate) fn set_previous_blob_hash(&mut self, hash: GitHash) {
+        self.previous_blob_hash = Some(hash)
+    }
 }
 
 impl TryFrom<&str> for IndexFileInfo {
     type Error = ErrorType;
-    // recibe una linea del index y devuelve un file info si el formato estaba bien, sino error
+
     fn try_from(line: &str) -> Result<IndexFileInfo, ErrorType> {
-        let vec: Vec<&str> = line.split(' ').collect();
-        if vec.len() != 4 {
-            return Err(ErrorType::FormatError("the format for file index is:'path modification_date current_blob_hash previous_blob_hash' separated by spaces.Previous blob hash may be None".into()));
-        }
-        let path = PathBuf::from(vec[0]);
-        let mod_date = vec[1].to_string();
-        let current_blob_hash = GitHash::new(vec[2])?;
-        let previous_blob_hash = match vec[3] {
+        Self::try_from(line.as_bytes())
+    }
+}
+
+impl TryFrom<&[u8]> for IndexFileInfo {
+    type Error = ErrorType;
+    // recibe una linea del index de texto (formato anterior al DIRC) y devuelve un file info
+    // si el formato estaba bien, sino error
+    fn try_from(line: &[u8]) -> Result<IndexFileInfo, ErrorType> {
+        let format_error = || {
+            ErrorType::FormatError("the format for file index is:'path modification_date current_blob_hash previous_blob_hash mode' separated by spaces.Previous blob hash may be None".into())
+        };
+
+        // el path puede tener espacios o bytes que no son utf-8, por eso se parsea desde el final.
+        // los index viejos no tienen el modo
+        let last = line.rsplit(|&byte| byte == b' ').next().unwrap_or_default();
+        let has_mode = std::str::from_utf8(last).is_ok_and(|m| FileMode::try_from(m).is_ok());
+        let amount = if has_mode { 5 } else { 4 };
+
+        let mut fields: Vec<&[u8]> = line.rsplitn(amount, |&byte| byte == b' ').collect();
+        if fields.len() != amount {
+            return Err(format_error());
+        }
+        fields.reverse();
+        let path = utils::bytes_to_path(fields[0]);
+        let text: Vec<&str> = fields[1..]
+            .iter()
+            .map(|field| std::str::from_utf8(field))
+            .collect::<Result<_, _>>()
+            .map_err(|_| format_error())?;
+
+        let stat = StatData::from_legacy_date(text[0]);
+        let current_blob_hash = GitHash::new(text[1])?;
+        let previous_blob_hash = match text[2] {
             "None" => None,
-            _ => Some(GitHash::new(vec[3])?),
+            hash => Some(GitHash::new(hash)?),
+        };
+        let mode = match text.get(3) {
+            Some(mode) => FileMode::try_from(*mode)?,
+            None => FileMode::Regular,
         };
 
         Ok(Self {
             path,
-            mod_date,
+            stat,
             current_blob_hash,
             previous_blob_hash,
+            mode,
+            stage: 0,
         })
     }
 }
@@ -217,20 +494,187 @@ mod tests {
     mod tests_try_from {
         use std::path::PathBuf;
 
-        use crate::{files::index_file_info::IndexFileInfo, git_errors::errors::ErrorType};
+        use crate::{
+            files::{file_mode::FileMode, index_file_info::IndexFileInfo},
+            git_errors::errors::ErrorType,
+            hash::GitHash,
+        };
 
         #[ignore]
         #[test]
         fn try_from_ok() -> Result<(), ErrorType> {
             let path = "tests/data/file_info/do_not_modify.txt";
-            let date = IndexFileInfo::date_modified_as_string(&PathBuf::from(path))?;
             let hash = IndexFileInfo::current_blob_hash(&PathBuf::from(path))?;
-            let line: &str = &format!("{path} {date} {hash} None");
+            let line: &str = &format!("{path} 2023/10/17-18:15:00 {hash} None 100644");
 
             let file_info = IndexFileInfo::try_from(line)?;
 
-            assert_eq!(file_info.to_index_line().trim_end(), line);
+            assert_eq!(file_info.get_path(), PathBuf::from(path));
+            assert_eq!(file_info.get_hash(), hash);
+
+            Ok(())
+        }
+
+        #[test]
+        fn try_from_mode() -> Result<(), ErrorType> {
+            let hash = "e69de29bb2d1d6434b8b29ae775ad8c2e48c5391";
+            let line = format!("run.sh 2023/10/17-18:15:00 {hash} None 100755");
+            let file_info = IndexFileInfo::try_from(line.as_str())?;
+            assert_eq!(file_info.get_mode(), FileMode::Executable);
+            assert_eq!(file_info.get_hash(), GitHash::new(hash)?);
+            assert_eq!(file_info.stat.mtime, (1697566500, 0));
+
+            // el path puede tener espacios
+            let line = format!("mi archivo.txt 2023/10/17-18:15:00 {hash} {hash} 100644");
+            let file_info = IndexFileInfo::try_from(line.as_str())?;
+            assert_eq!(file_info.get_path(), PathBuf::from("mi archivo.txt"));
+            assert_eq!(
+                file_info.get_previous_blob_hash(),
+                Some(GitHash::new(hash)?)
+            );
+
+            // index sin el modo (formato anterior)
+            let line = format!("file.txt 2023/10/17-18:15:00 {hash} None");
+            let file_info = IndexFileInfo::try_from(line.as_str())?;
+            assert_eq!(file_info.get_mode(), FileMode::Regular);
+            Ok(())
+        }
+
+        #[cfg(unix)]
+        #[test]
+        fn try_from_non_utf8_path() -> Result<(), ErrorType> {
+            let mut line = vec![b'd', b'i', b'r', b'/', 0xff, 0xfe];
+            line.extend(
+                b" 2023/10/17-18:15:00 e69de29bb2d1d6434b8b29ae775ad8c2e48c5391 None 100644",
+            );
+            let file_info = IndexFileInfo::try_from(line.as_slice())?;
+            assert_eq!(
+                file_info.get_path(),
+                crate::utils::bytes_to_path(&line[..6])
+            );
+            Ok(())
+        }
+    }
+
+    mod tests_index_entry {
+        use crate::{
+            files::index_file_info::IndexFileInfo, git_errors::errors::ErrorType, hash::GitHash,
+        };
+
+        fn entry_for(path: &[u8]) -> Result<IndexFileInfo, ErrorType> {
+            let mut line = path.to_vec();
+            line.extend(
+                b" 2023/10/17-18:15:00 e69de29bb2d1d6434b8b29ae775ad8c2e48c5391 None 100755",
+            );
+            IndexFileInfo::try_from(line.as_slice())
+        }
+
+        #[test]
+        fn entry_layout() -> Result<(), ErrorType> {
+            let entry = entry_for(b"file.txt")?.to_index_entry()?;
+
+            // 62 bytes fijos + 8 del nombre + 2 nulos de padding
+            assert_eq!(entry.len(), 72);
+            assert_eq!(entry[8..12], 1697566500u32.to_be_bytes());
+            assert_eq!(entry[24..28], 0o100755u32.to_be_bytes());
+            assert_eq!(
+                GitHash::from_hex(&entry[40..60])?,
+                GitHash::new("e69de29bb2d1d6434b8b29ae775ad8c2e48c5391")?
+            );
+            assert_eq!(entry[60..62], 8u16.to_be_bytes());
+            assert_eq!(&entry[62..70], b"file.txt");
+            assert_eq!(entry[70..], [0, 0]);
+            Ok(())
+        }
+
+        #[test]
+        fn entry_roundtrip() -> Result<(), ErrorType> {
+            // un nombre de 2 bytes ocupa justo 64 bytes y necesita 8 nulos de padding
+            let long_name = vec![b'a'; 5000];
+            for path in [&b"ab"[..], b"dir/archivo con espacios.txt", &long_name] {
+                let file_info = entry_for(path)?;
+                let entry = file_info.to_index_entry()?;
+                assert_eq!(entry.len() % 8, 0);
+                assert_eq!(entry.last(), Some(&0));
+
+                let mut stream = entry.as_slice();
+                let read = IndexFileInfo::read_entry(&mut stream)?;
+                assert!(stream.is_empty());
+                assert_eq!(read, file_info);
+            }
+            Ok(())
+        }
+    }
+
+    mod tests_has_changed {
+        use std::{fs, path::PathBuf};
+
+        use crate::{
+            files::index_file_info::{IndexFileInfo, StatCheck, StatData},
+            git_errors::errors::ErrorType,
+        };
+
+        // un index escrito despues de cualquier modificacion: ninguna entrada es racy
+        const LATER_INDEX: Option<(u32, u32)> = Some((u32::MAX, 0));
+
+        #[test]
+        fn hashes_only_when_stat_differs_or_racy() -> Result<(), ErrorType> {
+            let home = std::env::temp_dir().join("git_rustico_tests_has_changed");
+            let _ = fs::remove_dir_all(&home);
+            fs::create_dir_all(&home)?;
+            fs::write(home.join("file.txt"), "hola\n")?;
+            let mut file_info = IndexFileInfo::new(PathBuf::from("file.txt"), &home)?;
+
+            assert_eq!(
+                file_info.has_changed(&home, LATER_INDEX)?,
+                StatCheck::Unchanged
+            );
+            // sin la fecha del index siempre se lee el contenido
+            assert_eq!(file_info.has_changed(&home, None)?, StatCheck::Refreshed);
+
+            // se reescribe con el mismo contenido: cambia el stat pero no el hash
+            fs::write(home.join("file.txt"), "hola\n")?;
+            file_info.stat.ino = 0;
+            assert_eq!(
+                file_info.has_changed(&home, LATER_INDEX)?,
+                StatCheck::Refreshed
+            );
+            assert_eq!(file_info.stat, StatData::from_path(&home.join("file.txt"))?);
+
+            fs::write(home.join("file.txt"), "chau\n")?;
+            assert_eq!(
+                file_info.has_changed(&home, LATER_INDEX)?,
+                StatCheck::Changed
+            );
+
+            fs::remove_dir_all(&home)?;
+            Ok(())
+        }
+
+        #[test]
+        fn racy_entry_is_smudged() -> Result<(), ErrorType> {
+            let home = std::env::temp_dir().join("git_rustico_tests_racy_entry");
+            let _ = fs::remove_dir_all(&home);
+            fs::create_dir_all(&home)?;
+            fs::write(home.join("file.txt"), "hola\n")?;
+            let mut file_info = IndexFileInfo::new(PathBuf::from("file.txt"), &home)?;
+
+            // el archivo cambia en el mismo instante en que se escribio el index: mismo stat
+            fs::write(home.join("file.txt"), "chau\n")?;
+            file_info.stat = StatData::from_path(&home.join("file.txt"))?;
+            let index_timestamp = Some(file_info.stat.mtime);
+
+            assert_eq!(
+                file_info.has_changed(&home, index_timestamp)?,
+                StatCheck::Changed
+            );
+            // aunque despues el index sea mas nuevo que el archivo se sigue viendo el cambio
+            assert_eq!(
+                file_info.has_changed(&home, LATER_INDEX)?,
+                StatCheck::Changed
+            );
 
+            fs::remove_dir_all(&home)?;
             Ok(())
         }
     }
@@ -1,2 +1,3 @@
+pub mod file_mode;
 pub mod index_file_info;
 pub mod object_type;
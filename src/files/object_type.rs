@@ -3,12 +3,14 @@ use core::fmt;
 const HEADER_BLOB: &str = "blob";
 const HEADER_COMMIT: &str = "commit";
 const HEADER_TREE: &str = "tree";
+const HEADER_TAG: &str = "tag";
 
 #[derive(Clone, Debug, PartialEq, Copy)]
 pub enum ObjectType {
     Commit,
     Blob,
     Tree,
+    Tag,
 }
 impl ObjectType {
     pub fn add_header(content: &Vec<u8>, obj_type: &ObjectType) -> Vec<u8> {
@@ -17,6 +19,7 @@ impl ObjectType {
             ObjectType::Commit => HEADER_COMMIT,
             ObjectType::Blob => HEADER_BLOB,
             ObjectType::Tree => HEADER_TREE,
+            ObjectType::Tag => HEADER_TAG,
         };
         let mut header_content = format!("{} {}\0", header, &size).as_bytes().to_vec();
         header_content.extend(content);
@@ -30,6 +33,7 @@ impl fmt::Display for ObjectType {
             ObjectType::Commit => write!(f, "commit"),
             ObjectType::Blob => write!(f, "blob"),
             ObjectType::Tree => write!(f, "tree"),
+            ObjectType::Tag => write!(f, "tag"),
         }
     }
 }
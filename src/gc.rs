@@ -0,0 +1,305 @@
+use crate::branch::Branch;
+use crate::commit_graph::CommitGraph;
+use crate::config::RepoConfig;
+use crate::files::file_mode::FileMode;
+use crate::files::object_type::ObjectType;
+use crate::git_errors::command_error::CommandError::IncorrectAmount;
+use crate::git_errors::errors::ErrorType;
+use crate::git_object::GitObject;
+use crate::hash::GitHash;
+use crate::index::Index;
+use crate::protocol::pack_file::{build_packfile, save_packfile, tree_entry_names};
+use crate::protocol::pack_index;
+use crate::refs::BranchRef;
+use crate::repo_paths::RepoPaths;
+use crate::stash::Stash;
+use crate::tag::Tag;
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Junta en un unico packfile (con su .idx) todos los objects alcanzables desde las refs
+/// (branches, tags, stash, HEAD, HEAD_MERGE, HEAD_REMOTE y branches remotas) y desde el index.
+/// Borra los loose objects, los packfiles anteriores y los objects que no se pueden alcanzar.
+pub struct Gc {
+    repo_paths: RepoPaths,
+}
+
+impl Gc {
+    pub fn gc_command(repo_paths: &RepoPaths, args: Vec<String>) -> Result<String, ErrorType> {
+        if !args.is_empty() {
+            return Err(ErrorType::CommandError(IncorrectAmount(
+                "0".to_string(),
+                args.len(),
+            )));
+        }
+        let gc = Gc {
+            repo_paths: repo_paths.clone(),
+        };
+        let result = gc.run()?;
+        println!("{}", result);
+        Ok(result)
+    }
+
+    fn run(&self) -> Result<String, ErrorType> {
+        let path_objects = self.repo_paths.get_objects();
+        let size_before = Self::dir_size(&path_objects)?;
+
+        let loose_objects = Self::list_loose_objects(&path_objects)?;
+        let mut stored: HashSet<GitHash> = loose_objects.iter().map(|(h, _)| h.clone()).collect();
+        stored.extend(pack_index::list_objects(&path_objects)?);
+        let old_packs = Self::list_pack_files(&path_objects)?;
+
+        let reachable = self.reachable_objects(&path_objects)?;
+        let reachable_amount = reachable.len();
+        let commits: Vec<GitHash> = reachable
+            .iter()
+            .filter(|(object_type, _, _)| *object_type == ObjectType::Commit)
+            .map(|(_, hash, _)| hash.clone())
+            .collect();
+
+        let mut new_pack = None;
+        if !reachable.is_empty() {
+            let options = RepoConfig::open(self.repo_paths.get_config())?.get_pack_options();
+            let packfile = build_packfile(reachable, options)?;
+            let checksum = GitHash::from_hex(&packfile[packfile.len() - 20..])?;
+            save_packfile(&mut packfile.as_slice(), &path_objects)?;
+            new_pack = Some(format!("pack-{checksum}"));
+        }
+
+        // el nuevo packfile ya esta guardado, recien ahora se borra lo anterior
+        for (_, path) in &loose_objects {
+            fs::remove_file(path)?;
+            if let Some(dir) = path.parent() {
+                if fs::read_dir(dir)?.next().is_none() {
+                    fs::remove_dir(dir)?;
+                }
+            }
+        }
+        for path in old_packs {
+            let name = path.file_stem().map(|s| s.to_string_lossy().to_string());
+            if name != new_pack {
+                fs::remove_file(path)?;
+            }
+        }
+        pack_index::forget_indexes(&path_objects);
+
+        // los commits que se borraron tampoco pueden quedar en el commit-graph
+        CommitGraph::rebuild(&path_objects, &commits)?;
+
+        let size_after = Self::dir_size(&path_objects)?;
+        Ok(format!(
+            "Packed {} objects. Removed {} loose objects and {} unreachable objects. {} bytes saved ({} -> {}).",
+            reachable_amount,
+            loose_objects.len(),
+            stored.len().saturating_sub(reachable_amount),
+            size_before.saturating_sub(size_after),
+            size_before,
+            size_after
+        ))
+    }
+
+    fn roots(&self) -> Result<Vec<GitHash>, ErrorType> {
+        let repo_paths = &self.repo_paths;
+        let mut roots = Vec::new();
+
+        roots.extend(Branch::list_branches(&repo_paths.get_refs_heads())?.into_values());
+        roots.extend(Branch::list_branches(&repo_paths.get_refs_remote())?.into_values());
+        roots.extend(Tag::get_tags(repo_paths)?.into_iter().map(|(_, hash)| hash));
+        roots.extend(Stash::open(repo_paths)?.get_entries());
+
+        for path_ref in [repo_paths.get_head(), repo_paths.get_head_merge()] {
+            if !path_ref.exists() {
+                continue;
+            }
+            let head = BranchRef::open(path_ref, &repo_paths.get_refs_heads())?;
+            roots.extend(head.get_last_commit_hash());
+        }
+        if repo_paths.get_remote_head().exists() {
+            let hash = fs::read_to_string(repo_paths.get_remote_head())?;
+            roots.push(GitHash::new(hash.trim())?);
+        }
+
+        if repo_paths.get_index().exists() {
+            let index = Index::open(&repo_paths.get_index())?;
+            // los gitlinks apuntan a commits de un submodule, que no estan en este repo
+            roots.extend(
+                index
+                    .as_files_vector()
+                    .iter()
+                    .filter(|file| file.get_mode() != FileMode::Gitlink)
+                    .map(|file| file.get_hash()),
+            );
+        }
+        Ok(roots)
+    }
+
+    // recorre commits (tree y parents), trees (entradas) y tags (object) desde las refs.
+    // Si falta alguno el repo esta roto y gc no puede seguir, se borrarian objects
+    fn reachable_objects(
+        &self,
+        path_objects: &Path,
+    ) -> Result<Vec<(ObjectType, GitHash, Vec<u8>)>, ErrorType> {
+        let mut objects = Vec::new();
+        let mut visited = HashSet::new();
+        let mut pending = self.roots()?;
+
+        while let Some(hash) = pending.pop() {
+            if !visited.insert(hash.clone()) {
+                continue;
+            }
+            if !GitObject::contains(&hash, path_objects) {
+                return Err(ErrorType::RepositoryError(format!(
+                    "missing object {hash}, reachable from the refs. gc aborted, nothing was removed"
+                )));
+            }
+            let (object_type, content) = GitObject::read_object(&hash, path_objects)?;
+            match object_type {
+                ObjectType::Commit | ObjectType::Tag => {
+                    let text = String::from_utf8_lossy(&content);
+                    for line in text.lines().take_while(|line| !line.is_empty()) {
+                        if let Some((category, data)) = line.split_once(' ') {
+                            if ["tree", "parent", "object"].contains(&category) {
+                                pending.push(GitHash::new(data)?);
+                            }
+                        }
+                    }
+                }
+                ObjectType::Tree => {
+                    pending.extend(tree_entry_names(&content).into_iter().map(|(h, _)| h));
+                }
+                ObjectType::Blob => {}
+            }
+            objects.push((object_type, hash, content));
+        }
+        Ok(objects)
+    }
+
+    // objects/xx/yyyy...
+    fn list_loose_objects(path_objects: &Path) -> Result<Vec<(GitHash, PathBuf)>, ErrorType> {
+        let mut objects = Vec::new();
+        for dir in fs::read_dir(path_objects)? {
+            let dir = dir?;
+            let dir_name = dir.file_name().to_string_lossy().to_string();
+            if dir_name.len() != 2 || !dir.file_type()?.is_dir() {
+                continue;
+            }
+            for file in fs::read_dir(dir.path())? {
+                let file = file?;
+                let file_name = file.file_name().to_string_lossy().to_string();
+                if let Ok(hash) = GitHash::new(&format!("{dir_name}{file_name}")) {
+                    objects.push((hash, file.path()));
+                }
+            }
+        }
+        Ok(objects)
+    }
+
+    fn list_pack_files(path_objects: &Path) -> Result<Vec<PathBuf>, ErrorType> {
+        let path_pack_dir = pack_index::get_pack_dir(path_objects);
+        if !path_pack_dir.is_dir() {
+            return Ok(Vec::new());
+        }
+        let mut packs = Vec::new();
+        for entry in fs::read_dir(path_pack_dir)? {
+            packs.push(entry?.path());
+        }
+        Ok(packs)
+    }
+
+    fn dir_size(path: &Path) -> Result<u64, ErrorType> {
+        let mut size = 0;
+        for entry in fs::read_dir(path)? {
+            let entry = entry?;
+            let metadata = entry.metadata()?;
+            size += match metadata.is_dir() {
+                true => Self::dir_size(&entry.path())?,
+                false => metadata.len(),
+            };
+        }
+        Ok(size)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::init::git_init;
+    use crate::test_utils::init_repo;
+
+    fn save(
+        content: &[u8],
+        obj_type: ObjectType,
+        path_objects: &Path,
+    ) -> Result<GitHash, ErrorType> {
+        GitObject::save_object(content.to_vec(), obj_type, path_objects)?;
+        Ok(GitHash::hash_sha1(&ObjectType::add_header(
+            &content.to_vec(),
+            &obj_type,
+        )))
+    }
+
+    #[test]
+    fn packs_reachable_and_drops_unreachable() -> Result<(), ErrorType> {
+        let home = std::env::temp_dir().join("git_rustico_tests_gc");
+        let _ = fs::remove_dir_all(&home);
+        fs::create_dir_all(&home)?;
+        let repo_paths = RepoPaths::new(home.clone())?;
+        git_init(repo_paths.clone())?;
+        let path_objects = repo_paths.get_objects();
+
+        let blob = save(b"contenido\n", ObjectType::Blob, &path_objects)?;
+        let mut tree = b"100644 file\0".to_vec();
+        tree.extend(blob.to_hex()?);
+        let tree = save(&tree, ObjectType::Tree, &path_objects)?;
+        let commit = format!("tree {tree}\nauthor a <b> 0 +0000\ncommitter a <b> 0 +0000\n\nmsg\n");
+        let commit = save(commit.as_bytes(), ObjectType::Commit, &path_objects)?;
+        let lost = save(b"nadie me referencia\n", ObjectType::Blob, &path_objects)?;
+        fs::write(
+            repo_paths.get_refs_heads().join("master"),
+            commit.to_string(),
+        )?;
+
+        let result = Gc::gc_command(&repo_paths, Vec::new())?;
+
+        assert!(result.starts_with("Packed 3 objects. Removed 4 loose objects and 1 unreachable"));
+        assert!(Gc::list_loose_objects(&path_objects)?.is_empty());
+        for hash in [&blob, &tree, &commit] {
+            assert!(GitObject::contains(hash, &path_objects));
+        }
+        assert!(!GitObject::contains(&lost, &path_objects));
+        assert_eq!(
+            GitObject::read_object(&blob, &path_objects)?,
+            (ObjectType::Blob, b"contenido\n".to_vec())
+        );
+
+        fs::remove_dir_all(&home)?;
+        Ok(())
+    }
+
+    #[test]
+    fn missing_reachable_object_aborts_without_removing() -> Result<(), ErrorType> {
+        let repo_paths = init_repo("git_rustico_tests_gc_missing")?;
+        let path_objects = repo_paths.get_objects();
+
+        let missing = GitHash::hash_object(&b"100644 perdido\0".to_vec(), ObjectType::Tree);
+        let commit =
+            format!("tree {missing}\nauthor a <b> 0 +0000\ncommitter a <b> 0 +0000\n\nmsg\n");
+        let commit = save(commit.as_bytes(), ObjectType::Commit, &path_objects)?;
+        let lost = save(b"nadie me referencia\n", ObjectType::Blob, &path_objects)?;
+        fs::write(
+            repo_paths.get_refs_heads().join("master"),
+            commit.to_string(),
+        )?;
+
+        let result = Gc::gc_command(&repo_paths, Vec::new());
+        assert!(
+            matches!(result, Err(ErrorType::RepositoryError(error)) if error.contains(&missing.to_string()))
+        );
+        assert_eq!(Gc::list_loose_objects(&path_objects)?.len(), 2);
+        assert!(GitObject::contains(&lost, &path_objects));
+
+        fs::remove_dir_all(repo_paths.get_home())?;
+        Ok(())
+    }
+}
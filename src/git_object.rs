@@ -4,8 +4,10 @@ use crate::compressor::Compressor;
 use crate::files::object_type::ObjectType;
 use crate::git_errors::{command_error::CommandError::InvalidHash, errors::ErrorType};
 use crate::hash::GitHash;
+use crate::protocol::{pack_file, pack_index};
 use crate::tree::Tree;
-use std::{fs, fs::File, io::Write, path::Path};
+use std::io::{Cursor, Read, Write};
+use std::{fs, fs::File, path::Path};
 
 #[derive(Clone, Debug, PartialEq)]
 pub struct GitObject;
@@ -40,11 +42,6 @@ impl GitObject {
 
     pub(crate) fn read_blob(hash: &GitHash, path_objects: &Path) -> Result<Blob, ErrorType> {
         let (obj_type, _, content) = Self::parse_object(hash, path_objects)?;
-        let content: String = String::from_utf8(content)
-            .ok()
-            .ok_or(ErrorType::FormatError(format!(
-                "invalid_content in blob object {hash}"
-            )))?;
         match obj_type {
             ObjectType::Blob => Ok(Blob::new(content)),
             _ => Err(ErrorType::ObjectType(
@@ -54,13 +51,28 @@ impl GitObject {
         }
     }
 
+    /// Devuelve el tipo y el contenido (sin header) de cualquier object
+    pub(crate) fn read_object(
+        hash: &GitHash,
+        path_objects: &Path,
+    ) -> Result<(ObjectType, Vec<u8>), ErrorType> {
+        let (obj_type, _, content) = Self::parse_object(hash, path_objects)?;
+        Ok((obj_type, content))
+    }
+
+    pub(crate) fn contains(hash: &GitHash, path_objects: &Path) -> bool {
+        let (dir, file) = hash.split_at_2();
+        path_objects.join(dir).join(file).exists()
+            || matches!(pack_index::find_object(hash, path_objects), Ok(Some(_)))
+    }
+
     // separa el contenido del object en (type, size y content)
     fn parse_object(
         hash: &GitHash,
         path_objects: &Path,
     ) -> Result<(ObjectType, usize, Vec<u8>), ErrorType> {
-        let file = Self::open_object(hash, path_objects)?;
-        let file_content = Compressor::uncompress(file)?;
+        let mut file_content = Vec::new();
+        Self::open_object(hash, path_objects)?.read_to_end(&mut file_content)?;
 
         if !file_content.contains(&b' ') || !file_content.contains(&b'\0') {
             return Err(ErrorType::FormatError(format!(
@@ -96,9 +108,10 @@ impl GitObject {
             "tree" => ObjectType::Tree,
             "blob" => ObjectType::Blob,
             "commit" => ObjectType::Commit,
+            "tag" => ObjectType::Tag,
             _ => {
                 return Err(ErrorType::ObjectType(
-                    "tree, blob or commit".to_string(),
+                    "tree, blob, commit or tag".to_string(),
                     obj_type.to_string(),
                 ))
             }
@@ -134,15 +147,30 @@ impl GitObject {
         Ok((obj_type, size, content))
     }
 
-    pub fn open_object(hash_object: &GitHash, path_objects: &Path) -> Result<File, ErrorType> {
+    /// Abre el object, ya sea loose (objects/xx/yyyy) o guardado dentro de un packfile
+    /// (objects/pack), y devuelve un reader de su contenido descomprimido: <type> <size>\0<content>
+    pub fn open_object(
+        hash_object: &GitHash,
+        path_objects: &Path,
+    ) -> Result<Box<dyn Read>, ErrorType> {
         let (dir, file) = hash_object.split_at_2();
         let path = path_objects.join(dir).join(file);
-        if !Path::new(&path).exists() {
-            return Err(ErrorType::CommandError(InvalidHash(format!(
+        if Path::new(&path).exists() {
+            let file_content = Compressor::uncompress(File::open(path)?)?;
+            return Ok(Box::new(Cursor::new(file_content)));
+        }
+        match pack_index::find_object(hash_object, path_objects)? {
+            Some((path_pack, offset)) => {
+                let (obj_type, content) =
+                    pack_file::read_packed_object(&path_pack, offset, path_objects)?;
+                Ok(Box::new(Cursor::new(ObjectType::add_header(
+                    &content, &obj_type,
+                ))))
+            }
+            None => Err(ErrorType::CommandError(InvalidHash(format!(
                 "{hash_object} not in objects"
-            ))));
+            )))),
         }
-        Ok(File::open(path)?)
     }
 
     pub(crate) fn save_commit(content: Vec<u8>, path_objects: &Path) -> Result<(), ErrorType> {
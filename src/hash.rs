@@ -10,7 +10,7 @@ const FLAG_T: &str = "-t";
 const FLAG_W: &str = "-w";
 // const FLAG_STDIN: &str = "--stdin";
 
-#[derive(Debug, PartialEq, Clone, Eq, Hash)]
+#[derive(Debug, PartialEq, Clone, Eq, Hash, PartialOrd, Ord)]
 pub struct GitHash {
     hash: String,
 }
@@ -84,6 +84,9 @@ impl GitHash {
                 ObjectType::Commit => GitObject::save_commit(content, &repo_paths.get_objects())?,
                 ObjectType::Blob => GitObject::save_blob(content, &repo_paths.get_objects())?,
                 ObjectType::Tree => GitObject::save_tree(content, &repo_paths.get_objects())?,
+                ObjectType::Tag => {
+                    GitObject::save_object(content, ObjectType::Tag, &repo_paths.get_objects())?
+                }
             };
         }
 
@@ -108,6 +111,7 @@ impl GitHash {
                     "blob" => Some(ObjectType::Blob),
                     "commit" => Some(ObjectType::Commit),
                     "tree" => Some(ObjectType::Tree),
+                    "tag" => Some(ObjectType::Tag),
                     _ => {
                         return Err(ErrorType::CommandError(FormatError(format!(
                             "invalid object type '{arg}' in hash-object command"
@@ -1,39 +1,359 @@
 use std::{
-    collections::HashSet,
+    collections::HashMap,
     fs,
-    path::{Path, PathBuf},
+    path::{Component, Path, PathBuf},
 };
 
 use crate::{git_errors::errors::ErrorType, repo_paths::RepoPaths};
 
-pub struct Ignore;
+const IGNORE_FILE: &str = ".gitignore";
+const OPTION_VERBOSE: &str = "-v";
+const OPTION_VERBOSE_LONG: &str = "--verbose";
+
+// external docs:
+// https://git-scm.com/docs/gitignore#_pattern_format
+
+/// Una linea de un .gitignore ya parseada
+#[derive(Clone, Debug, PartialEq)]
+pub struct IgnorePattern {
+    glob: Vec<char>,
+    // empieza con '!': vuelve a incluir lo que excluyo un patron anterior
+    negated: bool,
+    // termina con '/': solo matchea directorios
+    dir_only: bool,
+    // tiene una '/' al principio o en el medio: se compara contra el path relativo al directorio
+    // del .gitignore. Si no, se compara contra el nombre del archivo en cualquier nivel
+    anchored: bool,
+    // directorio del .gitignore, relativo al home del repo
+    base: PathBuf,
+    line_number: usize,
+    line: String,
+}
+
+impl IgnorePattern {
+    fn parse(line: &str, base: &Path, line_number: usize) -> Option<Self> {
+        let original = line.trim_end_matches('\r');
+        // los espacios al final no cuentan, salvo que esten escapados con '\'
+        let mut text = original;
+        while text.ends_with(' ') && !text.ends_with("\\ ") {
+            text = &text[..text.len() - 1];
+        }
+        if text.is_empty() || text.starts_with('#') {
+            return None;
+        }
+
+        let negated = text.starts_with('!');
+        if negated {
+            text = &text[1..];
+        }
+        let dir_only = text.ends_with('/');
+        let text = text.trim_end_matches('/');
+        let anchored = text.contains('/');
+        let text = text.trim_start_matches('/');
+        if text.is_empty() {
+            return None;
+        }
+
+        Some(Self {
+            glob: text.chars().collect(),
+            negated,
+            dir_only,
+            anchored,
+            base: base.to_path_buf(),
+            line_number,
+            line: original.to_string(),
+        })
+    }
+
+    /// path relativo al home del repo
+    fn matches(&self, path: &Path, is_dir: bool) -> bool {
+        if self.dir_only && !is_dir {
+            return false;
+        }
+        let Ok(relative) = path.strip_prefix(&self.base) else {
+            return false;
+        };
+        let text = match self.anchored {
+            true => relative.to_string_lossy().to_string(),
+            false => match relative.file_name() {
+                Some(name) => name.to_string_lossy().to_string(),
+                None => return false,
+            },
+        };
+        wildmatch(&self.glob, &text.chars().collect::<Vec<char>>())
+    }
+
+    /// Ubicacion del patron con el formato de check-ignore -v: <.gitignore>:<linea>:<patron>
+    pub fn source(&self) -> String {
+        format!(
+            "{}:{}:{}",
+            self.base.join(IGNORE_FILE).display(),
+            self.line_number,
+            self.line
+        )
+    }
+}
+
+/// Decide que archivos del working directory estan ignorados segun el .gitignore del home
+/// y los .gitignore de los subdirectorios. Los patrones de un subdirectorio tienen prioridad
+/// sobre los de sus padres y, dentro de un mismo archivo, gana el ultimo que matchea.
+pub struct Ignore {
+    home: PathBuf,
+    // patrones de cada directorio (relativo al home), se leen a medida que se necesitan
+    patterns: HashMap<PathBuf, Vec<IgnorePattern>>,
+}
 
 impl Ignore {
-    pub fn get_ignored_files(path_ignore: &Path) -> Result<HashSet<PathBuf>, ErrorType> {
-        let mut ignored = HashSet::new();
+    pub fn open(repo_paths: &RepoPaths) -> Self {
+        Self {
+            home: repo_paths.get_home(),
+            patterns: HashMap::new(),
+        }
+    }
+
+    /// Indica si el path (relativo al home del repo) esta ignorado
+    pub fn is_ignored(&mut self, path: &Path, is_dir: bool) -> Result<bool, ErrorType> {
+        Ok(self
+            .find_match(path, is_dir)?
+            .is_some_and(|pattern| !pattern.negated))
+    }
 
-        if !path_ignore.exists() {
-            return Ok(ignored);
+    /// Devuelve el patron que decide si el path esta ignorado o no, si es que alguno matchea.
+    /// Si un directorio padre esta ignorado todo lo que tiene adentro tambien lo esta
+    /// (no se puede volver a incluir con '!'), y se devuelve el patron del padre.
+    pub fn find_match(
+        &mut self,
+        path: &Path,
+        is_dir: bool,
+    ) -> Result<Option<IgnorePattern>, ErrorType> {
+        let components: Vec<Component> = path
+            .components()
+            .filter(|component| matches!(component, Component::Normal(_)))
+            .collect();
+        let mut current = PathBuf::new();
+        for (i, component) in components.iter().enumerate() {
+            current.push(component);
+            if i == components.len() - 1 {
+                return self.last_match(&current, is_dir);
+            }
+            if let Some(pattern) = self.last_match(&current, true)? {
+                if !pattern.negated {
+                    return Ok(Some(pattern));
+                }
+            }
         }
+        Ok(None)
+    }
 
-        let ignore_file = fs::read_to_string(path_ignore)?;
+    fn last_match(
+        &mut self,
+        path: &Path,
+        is_dir: bool,
+    ) -> Result<Option<IgnorePattern>, ErrorType> {
+        // primero el .gitignore del home y despues los de cada subdirectorio hasta el del path
+        let mut dirs = vec![PathBuf::new()];
+        for component in path.parent().iter().flat_map(|parent| parent.components()) {
+            let dir = dirs[dirs.len() - 1].join(component);
+            dirs.push(dir);
+        }
 
-        for file in ignore_file.lines() {
-            ignored.insert(PathBuf::from(file));
+        let mut found = None;
+        for dir in dirs {
+            for pattern in self.patterns_of(&dir)? {
+                if pattern.matches(path, is_dir) {
+                    found = Some(pattern.clone());
+                }
+            }
         }
-        Ok(ignored)
+        Ok(found)
+    }
+
+    fn patterns_of(&mut self, dir: &Path) -> Result<&Vec<IgnorePattern>, ErrorType> {
+        if !self.patterns.contains_key(dir) {
+            let path_ignore = self.home.join(dir).join(IGNORE_FILE);
+            let mut patterns = Vec::new();
+            if path_ignore.is_file() {
+                let content = fs::read(path_ignore)?;
+                for (i, line) in String::from_utf8_lossy(&content).lines().enumerate() {
+                    patterns.extend(IgnorePattern::parse(line, dir, i + 1));
+                }
+            }
+            self.patterns.insert(dir.to_path_buf(), patterns);
+        }
+        Ok(&self.patterns[dir])
     }
 
     pub fn check_ignore_command(
         args: Vec<String>,
         repo_paths: &RepoPaths,
-    ) -> Result<(), ErrorType> {
-        let ignored = Self::get_ignored_files(&repo_paths.get_ignore())?;
-        for path in args {
-            if ignored.contains(&PathBuf::from(&path)) {
-                println!("{}", path);
+    ) -> Result<String, ErrorType> {
+        let verbose = args
+            .iter()
+            .any(|arg| arg == OPTION_VERBOSE || arg == OPTION_VERBOSE_LONG);
+        let mut ignore = Self::open(repo_paths);
+        let mut lines = Vec::new();
+        for path in args.iter().filter(|arg| !arg.starts_with('-')) {
+            let is_dir = path.ends_with('/') || repo_paths.get_home().join(path).is_dir();
+            match ignore.find_match(Path::new(path), is_dir)? {
+                // con -v tambien se muestran los patrones negados que matchean
+                Some(pattern) if verbose => lines.push(format!("{}\t{}", pattern.source(), path)),
+                Some(pattern) if !pattern.negated => lines.push(path.to_string()),
+                _ => {}
             }
         }
+        for line in &lines {
+            println!("{}", line);
+        }
+        Ok(lines.join("\n"))
+    }
+}
+
+/// Compara un patron de gitignore con un texto:
+/// - `*` cualquier secuencia sin '/', `?` cualquier caracter salvo '/'
+/// - `[a-z]`, `[!abc]` clases de caracteres
+/// - `**/` cero o mas directorios, `**` al final cualquier cosa
+/// - `\` escapa el caracter siguiente
+fn wildmatch(glob: &[char], text: &[char]) -> bool {
+    match glob {
+        [] => text.is_empty(),
+        ['*', '*', '/', rest @ ..] => {
+            wildmatch(rest, text)
+                || text
+                    .iter()
+                    .enumerate()
+                    .any(|(i, c)| *c == '/' && wildmatch(rest, &text[i + 1..]))
+        }
+        ['*', '*', rest @ ..] => (0..=text.len()).any(|i| wildmatch(rest, &text[i..])),
+        ['*', rest @ ..] => {
+            let limit = text.iter().position(|c| *c == '/').unwrap_or(text.len());
+            (0..=limit).any(|i| wildmatch(rest, &text[i..]))
+        }
+        ['?', rest @ ..] => match text {
+            [c, text_rest @ ..] if *c != '/' => wildmatch(rest, text_rest),
+            _ => false,
+        },
+        ['[', class @ ..] => match (text, match_class(class, text.first())) {
+            ([_, text_rest @ ..], Some((true, length))) => wildmatch(&class[length..], text_rest),
+            (_, Some((false, _))) => false,
+            // sin ']' que cierre la clase el '[' es un caracter comun
+            (_, None) => text.first() == Some(&'[') && wildmatch(class, &text[1..]),
+            _ => false,
+        },
+        ['\\', escaped, rest @ ..] => text.first() == Some(escaped) && wildmatch(rest, &text[1..]),
+        [c, rest @ ..] => text.first() == Some(c) && wildmatch(rest, &text[1..]),
+    }
+}
+
+// recibe lo que sigue al '[' y devuelve si el caracter esta en la clase y cuanto ocupa la clase
+// (incluyendo el ']'), o None si la clase no esta cerrada
+fn match_class(class: &[char], c: Option<&char>) -> Option<(bool, usize)> {
+    let negated = matches!(class.first(), Some('!') | Some('^'));
+    let mut i = usize::from(negated);
+    let mut found = false;
+    let mut first = true;
+    while i < class.len() {
+        match class[i] {
+            // un ']' justo despues de '[' (o de '[!') es parte de la clase
+            ']' if !first => {
+                let matched = c.is_some_and(|c| *c != '/' && found != negated);
+                return Some((matched, i + 1));
+            }
+            start
+                if class.get(i + 1) == Some(&'-')
+                    && class.get(i + 2).is_some_and(|e| *e != ']') =>
+            {
+                found |= c.is_some_and(|c| (start..=class[i + 2]).contains(c));
+                i += 2;
+            }
+            member => found |= c == Some(&member),
+        }
+        first = false;
+        i += 1;
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matches(pattern: &str, path: &str, is_dir: bool) -> bool {
+        IgnorePattern::parse(pattern, Path::new(""), 1)
+            .is_some_and(|pattern| pattern.matches(Path::new(path), is_dir))
+    }
+
+    #[test]
+    fn globs() {
+        assert!(matches("*.log", "debug.log", false));
+        assert!(matches("*.log", "logs/debug.log", false));
+        assert!(!matches("*.log", "debug.log.txt", false));
+        assert!(matches("debug?.log", "debug1.log", false));
+        assert!(matches("debug[0-9].log", "debug7.log", false));
+        assert!(!matches("debug[!0-9].log", "debug7.log", false));
+        assert!(matches("debug[!0-9].log", "debugx.log", false));
+        assert!(matches("\\#file", "#file", false));
+        assert!(matches("trailing\\ ", "trailing ", false));
+        assert!(matches("trailing   ", "trailing", false));
+    }
+
+    #[test]
+    fn anchored_and_directories() {
+        assert!(matches("/debug.log", "debug.log", false));
+        assert!(!matches("/debug.log", "logs/debug.log", false));
+        assert!(matches("logs/debug.log", "logs/debug.log", false));
+        assert!(!matches("logs/debug.log", "build/logs/debug.log", false));
+        assert!(matches("target/", "target", true));
+        assert!(!matches("target/", "target", false));
+        assert!(!matches("logs/*.log", "logs/a/debug.log", false));
+    }
+
+    #[test]
+    fn double_asterisk() {
+        assert!(matches("**/build", "build", true));
+        assert!(matches("**/build", "a/b/build", true));
+        assert!(matches("logs/**", "logs/a/b.txt", false));
+        assert!(!matches("logs/**", "logs", true));
+        assert!(matches("a/**/b", "a/b", false));
+        assert!(matches("a/**/b", "a/x/y/b", false));
+    }
+
+    #[test]
+    fn comments_and_blank_lines() {
+        assert!(IgnorePattern::parse("# comentario", Path::new(""), 1).is_none());
+        assert!(IgnorePattern::parse("   ", Path::new(""), 1).is_none());
+        assert!(IgnorePattern::parse("/", Path::new(""), 1).is_none());
+    }
+
+    #[test]
+    fn negation_and_nested_files() -> Result<(), ErrorType> {
+        let home = std::env::temp_dir().join("git_rustico_tests_ignore");
+        let _ = fs::remove_dir_all(&home);
+        fs::create_dir_all(home.join("sub"))?;
+        fs::write(home.join(IGNORE_FILE), "# logs\n*.log\n!keep.log\nbuild/\n")?;
+        fs::write(home.join("sub").join(IGNORE_FILE), "!sub.log\n*.tmp\n")?;
+        let mut ignore = Ignore::open(&RepoPaths::new(home.clone())?);
+
+        assert!(ignore.is_ignored(Path::new("debug.log"), false)?);
+        assert!(!ignore.is_ignored(Path::new("keep.log"), false)?);
+        assert!(!ignore.is_ignored(Path::new("sub/sub.log"), false)?);
+        assert!(ignore.is_ignored(Path::new("sub/other.log"), false)?);
+        assert!(ignore.is_ignored(Path::new("sub/a.tmp"), false)?);
+        assert!(!ignore.is_ignored(Path::new("a.tmp"), false)?);
+        // lo que esta adentro de un directorio ignorado no se puede volver a incluir
+        assert!(ignore.is_ignored(Path::new("build/keep.log"), false)?);
+
+        let pattern = ignore.find_match(Path::new("sub/sub.log"), false)?;
+        assert_eq!(
+            pattern.map(|p| p.source()),
+            Some("sub/.gitignore:1:!sub.log".to_string())
+        );
+        let pattern = ignore.find_match(Path::new("build/x.txt"), false)?;
+        assert_eq!(
+            pattern.map(|p| p.source()),
+            Some(".gitignore:4:build/".to_string())
+        );
+
+        fs::remove_dir_all(&home)?;
         Ok(())
     }
 }
@@ -1,17 +1,90 @@
 use std::{
     collections::HashMap,
+    fmt,
     fs::{self, File},
-    io::{BufReader, Read, Write},
-    path::{Path, PathBuf},
+    io::{Read, Write},
+    path::{Component, Path, PathBuf},
 };
 
 use crate::{
-    files::index_file_info::IndexFileInfo, git_errors::errors::ErrorType, hash::GitHash,
-    ignore::Ignore, refs::BranchRef, repo_paths::RepoPaths,
+    blob::Blob,
+    branch::Branch,
+    files::{
+        file_mode::FileMode,
+        index_file_info::{IndexFileInfo, StatCheck, StatData},
+    },
+    git_errors::{
+        command_error::CommandError::{IncorrectAmount, UnknownOption},
+        errors::ErrorType,
+    },
+    hash::GitHash,
+    ignore::Ignore,
+    protocol::pack_index::read_array,
+    refs::BranchRef,
+    remote::Remote,
+    repo_paths::RepoPaths,
+    utils,
 };
-use std::io::BufRead;
 
-// formato de la linea [path modification_date current_blob_hash previous_blob_hash]
+// external docs:
+// https://git-scm.com/docs/index-format
+
+// header       -> DIRC + version (2) + cantidad de entradas
+// entradas     -> ordenadas por path, ver IndexFileInfo::to_index_entry
+// extensiones  -> firma (4 bytes) + largo (4 bytes) + contenido
+// checksum     -> sha1 de todo lo anterior
+const INDEX_SIGNATURE: &[u8; 4] = b"DIRC";
+const INDEX_VERSION: u32 = 2;
+const CHECKSUM_SIZE: usize = 20;
+// extension propia con el previous_blob_hash de cada archivo: [path \0 hash].
+// Empieza con mayuscula, asi que git la considera opcional y la ignora
+const PREVIOUS_HASH_EXTENSION: &[u8; 4] = b"PREV";
+// stage de la version de HEAD de un archivo en conflicto
+const OURS_STAGE: u8 = 2;
+
+/// Tipo de cambio de un archivo en el status
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum FileChange {
+    New,
+    Modified,
+    Deleted,
+}
+
+impl fmt::Display for FileChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let label = match self {
+            FileChange::New => "new file:",
+            FileChange::Modified => "modified:",
+            FileChange::Deleted => "deleted:",
+        };
+        // se pasa por pad para que respete el ancho del format
+        f.pad(label)
+    }
+}
+
+/// Result of `Index::status`.
+///
+/// A file can be in `staged` and also in `not_staged` when it was modified again after `add`.
+#[derive(Debug, Default, PartialEq)]
+pub struct Status {
+    /// Files in the working directory that are not in the index.
+    pub untracked: Vec<PathBuf>,
+    /// Differences between the index and the last commit.
+    pub staged: Vec<(FileChange, PathBuf)>,
+    /// Differences between the working directory and the index.
+    pub not_staged: Vec<(FileChange, PathBuf)>,
+    /// Conflicted files of an open merge that weren't added yet.
+    pub unmerged: Vec<PathBuf>,
+}
+
+impl Status {
+    pub fn is_empty(&self) -> bool {
+        self.untracked.is_empty()
+            && self.staged.is_empty()
+            && self.not_staged.is_empty()
+            && self.unmerged.is_empty()
+    }
+}
 
 /// Represents the index of a git-rustico repository.
 ///
@@ -20,9 +93,17 @@ use std::io::BufRead;
 ///
 /// The `Index` struct provides methods for opening, creating, and manipulating the index, such as adding and removing files,
 /// saving the index to a file, and checking the status of files in the index.
+#[derive(Default)]
 pub struct Index {
     hash_map: HashMap<PathBuf, IndexFileInfo>,
+    // entradas de los archivos en conflicto de un merge abierto (stages 1 a 3). Un path en
+    // conflicto no tiene entrada en hash_map
+    unmerged: HashMap<PathBuf, Vec<IndexFileInfo>>,
     // path_index : PathBuf,
+    // mtime del archivo index al abrirlo, para detectar las entradas racy
+    timestamp: Option<(u32, u32)>,
+    // si status actualizo el stat de alguna entrada
+    refreshed: bool,
 }
 
 impl Index {
@@ -37,30 +118,118 @@ impl Index {
     /// A `Result` containing the `Index` instance if successful, or an `ErrorType` if an error
     /// occurred.
     pub fn open(path_index: &PathBuf) -> Result<Self, ErrorType> {
-        let file = File::open(path_index)?;
-        Self::new(file)
+        let content = fs::read(path_index)?;
+        let mut index = Self::new(content.as_slice())?;
+        // los index de texto de versiones anteriores se migran al formato binario
+        if !content.starts_with(INDEX_SIGNATURE) {
+            index.save(&mut File::create(path_index)?)?;
+        }
+        index.timestamp = Some(StatData::from_path(path_index)?.get_mtime());
+        Ok(index)
     }
 
-    /// Gets the Git hash associated with the specified file path in the index.
+    /// Reads an index in the DIRC version 2 format. The text index of previous versions
+    /// (one `path modification_date current_blob_hash previous_blob_hash mode` line per file)
+    /// is also accepted.
     ///
     /// # Arguments
     ///
-    /// * `branch_file_path` - The path of the file in the branch.
+    /// * `index_file` - The content of the index file.
     ///
     /// # Returns
     ///
-    /// An `Option` containing the Git hash if the file is found in the index, or `None` if the
-    /// file is not in the index.
-    pub fn new<R: Read>(index_file: R) -> Result<Self, ErrorType> {
-        let mut hash_map = HashMap::new();
-        let reader = BufReader::new(index_file);
+    /// A `Result` containing the `Index` instance if successful, or an `ErrorType` if the
+    /// format is not valid.
+    pub fn new<R: Read>(mut index_file: R) -> Result<Self, ErrorType> {
+        let mut content = Vec::new();
+        index_file.read_to_end(&mut content)?;
+        match content.starts_with(INDEX_SIGNATURE) {
+            true => Self::from_dirc(&content),
+            false => Self::from_text(&content),
+        }
+    }
+
+    fn from_dirc(content: &[u8]) -> Result<Self, ErrorType> {
+        let format_error = |message: &str| ErrorType::FormatError(format!("index: {message}"));
+        let data_size = content
+            .len()
+            .checked_sub(CHECKSUM_SIZE)
+            .ok_or(format_error("file too short"))?;
+        let (data, checksum) = content.split_at(data_size);
+        if GitHash::hash_sha1(&data.to_vec()).to_hex()? != checksum {
+            return Err(format_error("checksum mismatch"));
+        }
+
+        let mut stream = data;
+        let header: [u8; 12] = read_array(&mut stream)?;
+        let version = u32::from_be_bytes([header[4], header[5], header[6], header[7]]);
+        if version != INDEX_VERSION {
+            return Err(format_error(&format!(
+                "version {version} is not supported, only version {INDEX_VERSION}"
+            )));
+        }
+        let amount = u32::from_be_bytes([header[8], header[9], header[10], header[11]]);
+
+        let mut index = Self::default();
+        for _ in 0..amount {
+            let info = IndexFileInfo::read_entry(&mut stream)?;
+            match info.get_stage() {
+                0 => {
+                    index.hash_map.insert(info.get_path(), info);
+                }
+                _ => index
+                    .unmerged
+                    .entry(info.get_path())
+                    .or_default()
+                    .push(info),
+            }
+        }
+
+        while !stream.is_empty() {
+            let signature: [u8; 4] = read_array(&mut stream)?;
+            let size = u32::from_be_bytes(read_array(&mut stream)?) as usize;
+            if stream.len() < size {
+                return Err(format_error("truncated extension"));
+            }
+            let (extension, rest) = stream.split_at(size);
+            stream = rest;
+            if &signature == PREVIOUS_HASH_EXTENSION {
+                index.read_previous_hashes(extension)?;
+            } else if !signature[0].is_ascii_uppercase() {
+                // las extensiones que no empiezan con mayuscula no se pueden ignorar
+                return Err(format_error(&format!(
+                    "unsupported extension '{}'",
+                    String::from_utf8_lossy(&signature)
+                )));
+            }
+        }
+        Ok(index)
+    }
 
-        for line in reader.lines() {
-            let current_line: &str = &line?;
-            let info = IndexFileInfo::try_from(current_line)?;
-            hash_map.insert(info.get_path(), info);
+    fn read_previous_hashes(&mut self, mut extension: &[u8]) -> Result<(), ErrorType> {
+        while let Some(end) = extension.iter().position(|&byte| byte == 0) {
+            let path = utils::bytes_to_path(&extension[..end]);
+            extension = &extension[end + 1..];
+            let hash: [u8; CHECKSUM_SIZE] = read_array(&mut extension)?;
+            if let Some(file_info) = self.hash_map.get_mut(&path) {
+                file_info.set_previous_blob_hash(GitHash::from_hex(&hash)?);
+            }
         }
-        Ok(Self { hash_map })
+        Ok(())
+    }
+
+    // formato anterior: una linea [path modification_date current_blob_hash previous_blob_hash mode]
+    fn from_text(content: &[u8]) -> Result<Self, ErrorType> {
+        let mut index = Self::default();
+        // los paths pueden no ser utf-8, se leen las lineas como bytes
+        for line in content.split(|&byte| byte == b'\n') {
+            if line.is_empty() {
+                continue;
+            }
+            let info = IndexFileInfo::try_from(line)?;
+            index.hash_map.insert(info.get_path(), info);
+        }
+        Ok(index)
     }
 
     /// Gets the Git hash associated with the specified file path in the index.
@@ -105,15 +274,46 @@ impl Index {
     /// A `Result` indicating whether the operation was successful or an `ErrorType` if an error
     /// occurred.
     pub fn save<W: Write>(&self, index_file: &mut W) -> Result<(), ErrorType> {
-        let mut text: String = String::new();
-        for file in self.hash_map.values() {
-            text += &file.to_index_line();
+        let mut files: Vec<&IndexFileInfo> = self
+            .hash_map
+            .values()
+            .chain(self.unmerged.values().flatten())
+            .collect();
+        files.sort_by_key(|file| (utils::path_to_bytes(&file.get_path()), file.get_stage()));
+
+        let mut content: Vec<u8> = INDEX_SIGNATURE.to_vec();
+        content.extend(INDEX_VERSION.to_be_bytes());
+        content.extend((files.len() as u32).to_be_bytes());
+        let mut previous_hashes = Vec::new();
+        for file in files {
+            content.extend(file.to_index_entry()?);
+            if let Some(hash) = file.get_previous_blob_hash() {
+                previous_hashes.extend(utils::path_to_bytes(&file.get_path()));
+                previous_hashes.push(0);
+                previous_hashes.extend(hash.to_hex()?);
+            }
+        }
+        if !previous_hashes.is_empty() {
+            content.extend(PREVIOUS_HASH_EXTENSION);
+            content.extend((previous_hashes.len() as u32).to_be_bytes());
+            content.extend(previous_hashes);
         }
-        index_file.write_all(text.as_bytes())?;
+        let checksum = GitHash::hash_sha1(&content);
+        content.extend(checksum.to_hex()?);
+        index_file.write_all(&content)?;
 
         Ok(())
     }
 
+    /// Saves the index to the specified path only if `status` refreshed the stat data of
+    /// any entry, so the next `status` doesn't need to hash those files again.
+    pub fn save_refreshed(&self, path_index: &Path) -> Result<(), ErrorType> {
+        if self.refreshed {
+            self.save(&mut File::create(path_index)?)?;
+        }
+        Ok(())
+    }
+
     /// Adds a file to the index.
     ///
     /// # Arguments
@@ -132,9 +332,15 @@ impl Index {
         home_path: &Path,
         path_objects: &Path,
     ) -> Result<(), ErrorType> {
+        // agregar un archivo en conflicto marca la resolucion: se parte de la version de HEAD
+        if let Some(stages) = self.unmerged.remove(&file_path) {
+            if let Some(ours) = stages.into_iter().find(|e| e.get_stage() == OURS_STAGE) {
+                self.hash_map.insert(file_path.clone(), ours.with_stage(0));
+            }
+        }
         if let Some(file_info) = self.hash_map.get_mut(&file_path) {
             // the file was already tracked in index
-            file_info.verify_change(path_objects, home_path)?;
+            file_info.verify_change(path_objects, home_path, self.timestamp)?;
         } else {
             // this is a new file, it wasn't previously in index
             let file_info = IndexFileInfo::new(file_path, home_path)?;
@@ -156,7 +362,8 @@ impl Index {
     /// A `Result` indicating whether the operation was successful or an `ErrorType` if an error
     /// occurred.
     pub fn remove(&mut self, path: PathBuf) -> Result<(), ErrorType> {
-        if self.hash_map.remove(&path).is_none() {
+        let unmerged = self.unmerged.remove(&path).is_some();
+        if self.hash_map.remove(&path).is_none() && !unmerged {
             return Err(ErrorType::FileNotInIndex(format!(
                 "ERROR {} not in the index.",
                 path.display()
@@ -177,7 +384,24 @@ impl Index {
         }
 
         // todo preguntar si esta es la forma correcta
-        let mut index = Self::new(File::open(&path_index)?)?;
+        let mut index = Self::open(&path_index)?;
+
+        // igual que git, los archivos ignorados solo se agregan si ya estaban en el index
+        let mut ignore = Ignore::open(repo_paths);
+        let mut ignored = Vec::new();
+        for path in file_paths.iter() {
+            let path = PathBuf::from(path);
+            let is_dir = path_home.join(&path).is_dir();
+            if !index.hash_map.contains_key(&path) && ignore.is_ignored(&path, is_dir)? {
+                ignored.push(path.display().to_string());
+            }
+        }
+        if !ignored.is_empty() {
+            return Err(ErrorType::RepositoryError(format!(
+                "The following paths are ignored by one of your .gitignore files:\n{}",
+                ignored.join("\n")
+            )));
+        }
 
         for path in file_paths {
             index.add(PathBuf::from(path), &path_home, &repo_paths.get_objects())?;
@@ -190,7 +414,7 @@ impl Index {
     }
 
     pub fn rm_command(file_paths: Vec<String>, index_path: PathBuf) -> Result<(), ErrorType> {
-        let mut index = Self::new(File::open(index_path.clone())?)?;
+        let mut index = Self::open(&index_path)?;
 
         for file_path in file_paths {
             index.remove(PathBuf::from(file_path))?;
@@ -202,62 +426,332 @@ impl Index {
         Ok(())
     }
 
-    /// Returns the status of files in the index.
+    /// restore [--staged] [--worktree] <paths>
+    ///
+    /// Con --staged vuelve las entradas del index al ultimo commit (las que no estan en el commit
+    /// se sacan del index). Con --worktree, la opcion por defecto, descarta los cambios del working
+    /// directory escribiendo el contenido del index. Con las dos opciones se restauran ambos desde
+    /// el ultimo commit.
+    ///
+    /// Los paths pueden ser directorios, en ese caso se restauran todos los archivos que contienen.
+    pub fn restore_command(args: Vec<String>, repo_paths: &RepoPaths) -> Result<String, ErrorType> {
+        let mut staged = false;
+        let mut worktree = false;
+        let mut pathspecs = Vec::new();
+        for arg in args {
+            match arg.as_str() {
+                "--staged" | "-S" => staged = true,
+                "--worktree" | "-W" => worktree = true,
+                option if option.starts_with('-') => {
+                    return Err(ErrorType::CommandError(UnknownOption(
+                        "--staged, --worktree".to_string(),
+                        arg,
+                    )))
+                }
+                _ => pathspecs.push(PathBuf::from(arg)),
+            }
+        }
+        if pathspecs.is_empty() {
+            return Err(ErrorType::CommandError(IncorrectAmount(
+                "at least 1".to_string(),
+                0,
+            )));
+        }
+        let worktree = worktree || !staged;
+
+        let path_index = repo_paths.get_index();
+        let mut index = Self::open(&path_index)?;
+        let mut restored = Vec::new();
+        if staged {
+            restored.extend(index.restore_staged(&pathspecs, &Self::head_files(repo_paths)?)?);
+        }
+        if worktree {
+            restored.extend(index.restore_worktree(
+                &pathspecs,
+                &repo_paths.get_home(),
+                &repo_paths.get_objects(),
+            )?);
+        }
+        index.save(&mut File::create(path_index)?)?;
+
+        restored.sort();
+        restored.dedup();
+        let restored: Vec<String> = restored.iter().map(|p| p.display().to_string()).collect();
+        Ok(format!("Files restored: {}.", restored.join(", ")))
+    }
+
+    // vuelve las entradas al modo y hash del ultimo commit. Se usa el commit y no el
+    // previous_blob_hash porque despues de varios add este ya no es el hash commiteado
+    fn restore_staged(
+        &mut self,
+        pathspecs: &[PathBuf],
+        head_files: &HashMap<PathBuf, (FileMode, GitHash)>,
+    ) -> Result<Vec<PathBuf>, ErrorType> {
+        let mut candidates: Vec<&PathBuf> = self.hash_map.keys().collect();
+        candidates.extend(
+            head_files
+                .keys()
+                .filter(|p| !self.hash_map.contains_key(*p)),
+        );
+        let paths = Self::match_pathspecs(candidates, pathspecs)?;
+
+        for path in paths.iter() {
+            match (head_files.get(path), self.hash_map.get_mut(path)) {
+                (Some((mode, hash)), Some(file_info)) => {
+                    if (file_info.get_mode(), file_info.get_hash()) != (*mode, hash.clone()) {
+                        file_info.restore_blob(*mode, hash.clone());
+                    }
+                }
+                (Some((mode, hash)), None) => {
+                    let file_info =
+                        IndexFileInfo::from_tree_entry(path.clone(), *mode, hash.clone());
+                    self.hash_map.insert(path.clone(), file_info);
+                }
+                (None, _) => {
+                    self.hash_map.remove(path);
+                }
+            }
+        }
+        Ok(paths)
+    }
+
+    // escribe en el working directory el contenido del index de cada archivo
+    fn restore_worktree(
+        &mut self,
+        pathspecs: &[PathBuf],
+        home_path: &Path,
+        path_objects: &Path,
+    ) -> Result<Vec<PathBuf>, ErrorType> {
+        let paths = Self::match_pathspecs(self.hash_map.keys().collect(), pathspecs)?;
+
+        for path in paths.iter() {
+            let Some(file_info) = self.hash_map.get_mut(path) else {
+                continue;
+            };
+            Blob::checkout(
+                &file_info.get_hash(),
+                file_info.get_mode(),
+                &home_path.join(path),
+                path_objects,
+            )?;
+            file_info.refresh_stat(home_path)?;
+        }
+        Ok(paths)
+    }
+
+    // paths que coinciden con alguno de los pathspecs: el mismo archivo o uno dentro del directorio
+    fn match_pathspecs(
+        candidates: Vec<&PathBuf>,
+        pathspecs: &[PathBuf],
+    ) -> Result<Vec<PathBuf>, ErrorType> {
+        let mut paths = Vec::new();
+        for pathspec in pathspecs {
+            // "." y "./dir" se comparan sin el directorio actual
+            let pathspec: PathBuf = pathspec
+                .components()
+                .filter(|c| *c != Component::CurDir)
+                .collect();
+            let matching: Vec<PathBuf> = candidates
+                .iter()
+                .filter(|path| path.starts_with(&pathspec))
+                .map(|path| (*path).clone())
+                .collect();
+            if matching.is_empty() {
+                return Err(ErrorType::RepositoryError(format!(
+                    "pathspec '{}' did not match any file(s) known to git-rustico",
+                    pathspec.display()
+                )));
+            }
+            paths.extend(matching);
+        }
+        paths.sort();
+        paths.dedup();
+        Ok(paths)
+    }
+
+    /// Vuelve el index al tree de un commit (reset --mixed). Las entradas que no cambian conservan
+    /// su stat; el resto queda con el stat vacio para que status compare el contenido
+    pub(crate) fn reset_to(&mut self, files: HashMap<PathBuf, (FileMode, GitHash)>) {
+        let mut hash_map = HashMap::new();
+        for (path, (mode, hash)) in files {
+            let file_info = match self.hash_map.remove(&path) {
+                Some(mut file_info)
+                    if (file_info.get_mode(), file_info.get_hash()) == (mode, hash.clone()) =>
+                {
+                    file_info.reset_previous_blob_hash();
+                    file_info
+                }
+                _ => IndexFileInfo::from_tree_entry(path.clone(), mode, hash),
+            };
+            hash_map.insert(path, file_info);
+        }
+        self.hash_map = hash_map;
+        self.unmerged.clear();
+    }
+
+    /// Modo y hash del archivo en el index
+    pub(crate) fn get_entry(&self, path: &Path) -> Option<(FileMode, GitHash)> {
+        self.hash_map
+            .get(path)
+            .map(|file_info| (file_info.get_mode(), file_info.get_hash()))
+    }
+
+    /// Si el archivo del working directory cambio (o se borro) respecto del index
+    pub(crate) fn has_local_changes(
+        &mut self,
+        path: &Path,
+        home_path: &Path,
+    ) -> Result<bool, ErrorType> {
+        let Some(file_info) = self.hash_map.get_mut(path) else {
+            return Ok(false);
+        };
+        let global_path = home_path.join(path);
+        if !global_path.exists() && !global_path.is_symlink() {
+            return Ok(true);
+        }
+        Ok(file_info.has_changed(home_path, self.timestamp)? == StatCheck::Changed)
+    }
+
+    /// Pone en el index el archivo que se acaba de escribir en el working directory
+    pub(crate) fn checkout_entry(
+        &mut self,
+        path: PathBuf,
+        mode: FileMode,
+        hash: GitHash,
+        home_path: &Path,
+    ) -> Result<(), ErrorType> {
+        let mut file_info = IndexFileInfo::from_tree_entry(path.clone(), mode, hash);
+        file_info.refresh_stat(home_path)?;
+        self.unmerged.remove(&path);
+        self.hash_map.insert(path, file_info);
+        Ok(())
+    }
+
+    /// Marca el archivo como en conflicto con sus versiones de la base, de HEAD y de la branch
+    /// que se mergea (stages 1, 2 y 3). Las versiones que no existen no tienen entrada
+    pub(crate) fn set_conflict(&mut self, path: PathBuf, stages: [Option<(FileMode, GitHash)>; 3]) {
+        self.hash_map.remove(&path);
+        let entries = stages
+            .into_iter()
+            .zip(1..)
+            .filter_map(|(entry, stage)| {
+                entry.map(|(mode, hash)| {
+                    IndexFileInfo::from_tree_entry(path.clone(), mode, hash).with_stage(stage)
+                })
+            })
+            .collect();
+        self.unmerged.insert(path, entries);
+    }
+
+    /// Paths en conflicto que todavia no se resolvieron con add
+    pub(crate) fn unmerged_paths(&self) -> Vec<PathBuf> {
+        let mut paths: Vec<PathBuf> = self.unmerged.keys().cloned().collect();
+        paths.sort();
+        paths
+    }
+
+    /// Descarta los conflictos de un merge abierto, sin tocar las entradas resueltas
+    pub(crate) fn clear_unmerged(&mut self) {
+        self.unmerged.clear();
+    }
+
+    /// Tracked paths of the index
+    pub(crate) fn get_paths(&self) -> Vec<PathBuf> {
+        self.hash_map.keys().cloned().collect()
+    }
+
+    // modo y hash de los archivos del commit al que apunta HEAD, vacio si todavia no hay commits
+    fn head_files(
+        repo_paths: &RepoPaths,
+    ) -> Result<HashMap<PathBuf, (FileMode, GitHash)>, ErrorType> {
+        let head = BranchRef::open(repo_paths.get_head(), &repo_paths.get_refs_heads())?;
+        Ok(match head.get_last_commit(&repo_paths.get_objects())? {
+            Some(commit) => commit.get_files_map()?,
+            None => HashMap::new(),
+        })
+    }
+
+    /// Returns the status of the files in the working directory and in the index.
     ///
     /// # Arguments
     ///
-    /// * `home_path` - The path of the repository's home directory.
-    /// * `path_ignore` - The path of the ignore file.
+    /// * `repo_paths` - The paths of the repository.
     ///
     /// # Returns
     ///
-    /// A `Result` containing a vector of vectors of `PathBuf` representing the untracked files,
-    /// not staged files, and staged files, respectively, if successful, or an `ErrorType` if an
-    /// error occurred.
-    pub fn status(
-        &self,
-        home_path: &Path,
-        path_ignore: &Path,
-    ) -> Result<Vec<Vec<PathBuf>>, ErrorType> {
-        let file_paths = Self::list_dir_file_paths(home_path.to_path_buf())?;
+    /// A `Result` containing the `Status` with the untracked files, the changes not staged
+    /// (working directory against index), the changes to be commited (index against the last
+    /// commit) and the unmerged paths of an open merge, or an `ErrorType` if an error occurred.
+    pub fn status(&mut self, repo_paths: &RepoPaths) -> Result<Status, ErrorType> {
+        let home_path = repo_paths.get_home();
+        let mut ignore = Ignore::open(repo_paths);
+        let file_paths = Self::list_dir_file_paths(home_path.clone(), &mut ignore)?;
 
-        let ignored = Ignore::get_ignored_files(path_ignore)?;
-
-        let mut untracked: Vec<PathBuf> = Vec::new();
-        let mut not_staged: Vec<PathBuf> = Vec::new();
-        let mut staged: Vec<PathBuf> = Vec::new();
+        let head_files = Self::head_files(repo_paths)?;
 
+        let mut status = Status {
+            unmerged: self.unmerged_paths(),
+            ..Default::default()
+        };
         for file_path in file_paths.iter() {
-            if ignored.contains(file_path) {
+            if !self.hash_map.contains_key(file_path) && !self.unmerged.contains_key(file_path) {
+                status.untracked.push(file_path.clone());
+            }
+        }
+
+        let mut tracked: Vec<PathBuf> = self.hash_map.keys().cloned().collect();
+        tracked.sort();
+        for file_path in tracked {
+            let Some(file_info) = self.hash_map.get_mut(&file_path) else {
                 continue;
+            };
+            let in_head = head_files.get(&file_path);
+            if in_head != Some(&(file_info.get_mode(), file_info.get_hash())) {
+                let change = match in_head {
+                    Some(_) => FileChange::Modified,
+                    None => FileChange::New,
+                };
+                status.staged.push((change, file_path.clone()));
             }
-            match self.hash_map.get(file_path) {
-                Some(file_info) => {
-                    if file_info.has_changed(home_path)? {
-                        not_staged.push(file_path.clone());
-                    } else {
-                        if !file_info.added_since_commit() {
-                            continue;
-                        }
-                        staged.push(file_path.clone());
-                    }
-                }
-                None => {
-                    untracked.push(file_path.clone());
-                }
+
+            if !home_path.join(&file_path).exists() && !home_path.join(&file_path).is_symlink() {
+                status.not_staged.push((FileChange::Deleted, file_path));
+                continue;
+            }
+            let check = file_info.has_changed(&home_path, self.timestamp)?;
+            // el stat nuevo (o invalidado) se tiene que volver a escribir en el index
+            self.refreshed |= check != StatCheck::Unchanged;
+            if check == StatCheck::Changed {
+                status.not_staged.push((FileChange::Modified, file_path));
             }
         }
 
-        Ok(vec![untracked, not_staged, staged])
+        // los que estan en el ultimo commit pero se sacaron del index
+        let mut removed: Vec<&PathBuf> = head_files
+            .keys()
+            .filter(|path| !self.hash_map.contains_key(*path) && !self.unmerged.contains_key(*path))
+            .collect();
+        removed.sort();
+        for path in removed {
+            status.staged.push((FileChange::Deleted, path.clone()));
+        }
+
+        Ok(status)
     }
 
-    fn list_dir_file_paths(repo_home_path: PathBuf) -> Result<Vec<PathBuf>, ErrorType> {
-        Self::list_dir_file_paths_rec(repo_home_path.clone(), repo_home_path)
+    // lista los archivos del working directory que no estan ignorados. Los directorios
+    // ignorados no se recorren
+    fn list_dir_file_paths(
+        repo_home_path: PathBuf,
+        ignore: &mut Ignore,
+    ) -> Result<Vec<PathBuf>, ErrorType> {
+        Self::list_dir_file_paths_rec(repo_home_path.clone(), &repo_home_path, ignore)
     }
 
     fn list_dir_file_paths_rec(
         path_dir: PathBuf,
-        repo_home_path: PathBuf,
+        repo_home_path: &Path,
+        ignore: &mut Ignore,
     ) -> Result<Vec<PathBuf>, ErrorType> {
         let mut files: Vec<PathBuf> = Vec::new();
 
@@ -266,146 +760,173 @@ impl Index {
         for entry in entries {
             let entry = entry?;
             let path_buf = entry.path();
+            // los symlinks no se siguen, se trackean como archivos
+            let file_type = entry.file_type()?;
+            let path = match path_buf.strip_prefix(repo_home_path) {
+                Ok(p) => PathBuf::from(p),
+                Err(_) => path_buf.clone(),
+            };
 
-            if path_buf.is_file() {
-                if path_buf.ends_with(".gitignore") {
+            if file_type.is_file() || file_type.is_symlink() {
+                if path_buf.ends_with(".gitignore") || ignore.is_ignored(&path, false)? {
                     continue;
                 }
-                let path = match path_buf.strip_prefix(&format!("{}/", repo_home_path.display())) {
-                    Ok(p) => PathBuf::from(p),
-                    Err(_) => path_buf,
-                };
                 files.push(path);
-            } else if path_buf.is_dir() {
-                if path_buf.ends_with(".git-rustico") {
+            } else if file_type.is_dir() {
+                if path_buf.ends_with(".git-rustico") || ignore.is_ignored(&path, true)? {
                     continue;
                 }
                 let paths_subdirectory =
-                    Self::list_dir_file_paths_rec(path_buf, repo_home_path.clone())?;
+                    Self::list_dir_file_paths_rec(path_buf, repo_home_path, ignore)?;
                 files.extend(paths_subdirectory);
             }
         }
         Ok(files)
     }
 
-    // todo agregar la branch actual
-    // todo que te diga la branch y si estas en un merge abierto
-    // todo trakear los borrados tambien
-    // todo puede estar en changes to be commited (add) pero tambien en changes not staged for commmit (cambio despues del add) (nice to have)
     pub fn status_command(repo_paths: &RepoPaths) -> Result<String, ErrorType> {
         // archivo puede estar:
         // en repo pero no en index -> untracked
-        // en repo y en index pero modificado -> not staged for commit
-        // en repo e index pero previous_blob_hash != None -> changes to be commited
-        // en repo e index pero igual al ultimo commit (previous_blob_hash = None) -> no se imprime
+        // en index y en el working directory distinto (o borrado) -> not staged for commit
+        // en index distinto al ultimo commit (o borrado del index) -> changes to be commited
+        // en conflicto en un merge abierto (stages 1 a 3 en el index) y sin add -> unmerged
+        // puede estar en changes to be commited y tambien en not staged (cambio despues del add)
+
+        let mut index = Index::open(&repo_paths.get_index())?;
+        let status = index.status(repo_paths)?;
+        index.save_refreshed(&repo_paths.get_index())?;
+        Self::print_branch_state(repo_paths, &status)?;
+        Ok(Self::print_status(status))
+    }
 
-        let index = Index::new(File::open(repo_paths.get_index())?)?;
+    // branch actual, diferencia con el upstream y si hay un merge abierto
+    fn print_branch_state(repo_paths: &RepoPaths, status: &Status) -> Result<(), ErrorType> {
         let head = BranchRef::open(repo_paths.get_head(), &repo_paths.get_refs_heads())?;
-        let results = index.status(&repo_paths.get_home(), &repo_paths.get_ignore())?;
-        Ok(Self::print_status(
-            head,
-            results[0].clone(),
-            results[1].clone(),
-            results[2].clone(),
-        ))
-    }
-
-    pub fn print_status(
-        head: BranchRef,
-        untracked: Vec<PathBuf>,
-        not_staged: Vec<PathBuf>,
-        staged: Vec<PathBuf>,
-    ) -> String {
-        if let Some(branch_name) = head.get_branch_name() {
-            println!("On branch {}", branch_name);
-        } else {
-            println!("Not currently on any branch.");
+        if let (true, Some(hash)) = (head.is_detached(), head.get_last_commit_hash()) {
+            println!("HEAD detached at {}\n", &hash.as_str()[..7]);
+            return Ok(());
+        }
+        let Some(branch) = head.get_branch() else {
+            // HEAD apunta a una branch que todavia no tiene commits
+            let branch_name = fs::read_to_string(repo_paths.get_head())?;
+            println!(
+                "On branch {}
+
+No commits yet
+",
+                branch_name.trim()
+            );
+            return Ok(());
+        };
+        println!("On branch {}", branch.get_name());
+
+        if let Some((_, upstream_name)) =
+            Remote::get_upstream(repo_paths.get_remote(), branch.get_name())?
+        {
+            let path_objects = repo_paths.get_objects();
+            match Branch::open(&repo_paths.get_refs_remote(), &upstream_name) {
+                Ok(upstream) => {
+                    let local = branch.get_last_commit(&path_objects)?;
+                    let remote = upstream.get_last_commit(&path_objects)?;
+                    let (ahead, behind) = local.ahead_behind(&remote, &path_objects)?;
+                    println!("{}", Self::tracking_message(&upstream_name, ahead, behind));
+                }
+                Err(_) => println!(
+                    "Your branch is based on '{upstream_name}', but the upstream is gone.\n  (use 'git-rustico branch --set-upstream-to' to fixup)"
+                ),
+            }
+        }
+
+        if repo_paths.get_head_merge().exists() {
+            match status.unmerged.is_empty() {
+                true => println!("All conflicts fixed but you are still merging.\n  (use 'git-rustico merge --continue' to conclude merge)"),
+                false => println!("You have unmerged paths.\n  (fix conflicts and run 'git-rustico merge --continue')\n  (use 'git-rustico merge --abort' to abort the merge)"),
+            }
         }
-        if untracked.is_empty() && not_staged.is_empty() && staged.is_empty() {
+        println!();
+        Ok(())
+    }
+
+    fn tracking_message(upstream_name: &str, ahead: usize, behind: usize) -> String {
+        let commits = |amount: usize| match amount {
+            1 => "1 commit".to_string(),
+            _ => format!("{amount} commits"),
+        };
+        match (ahead, behind) {
+            (0, 0) => format!("Your branch is up to date with '{upstream_name}'."),
+            (_, 0) => format!(
+                "Your branch is ahead of '{upstream_name}' by {}.\n  (use 'git-rustico push' to publish your local commits)",
+                commits(ahead)
+            ),
+            (0, _) => format!(
+                "Your branch is behind '{upstream_name}' by {}, and can be fast-forwarded.\n  (use 'git-rustico pull' to update your local branch)",
+                commits(behind)
+            ),
+            _ => format!(
+                "Your branch and '{upstream_name}' have diverged,\nand have {ahead} and {behind} different commits each, respectively.\n  (use 'git-rustico pull' to merge the remote branch into yours)"
+            ),
+        }
+    }
+
+    pub fn print_status(status: Status) -> String {
+        if status.is_empty() {
             println!("Up to date. Nothing to commit.");
             return "Up to date. Nothing to commit.".to_string();
         }
         let mut changes = vec!["Exist files:".to_string()];
-        if !staged.is_empty() {
+        if !status.staged.is_empty() {
             println!("Changes to be commited:");
-            for file in &staged {
-                println!("\t{}", file.display());
+            for (change, file) in &status.staged {
+                println!("\t{:<12}{}", change, file.display());
             }
             println!();
             changes.push("To be commited.".to_string())
         }
-        if !not_staged.is_empty() {
+        if !status.unmerged.is_empty() {
+            println!("Unmerged paths:\n    (Use 'git-rustico add <file>...' to mark resolution)");
+            for file in &status.unmerged {
+                println!("\tboth modified:   {}", file.display());
+            }
+            println!();
+            changes.push("Unmerged.".to_string())
+        }
+        if !status.not_staged.is_empty() {
             println!("Changes not staged for commit:\n    (Use 'git-rustico add <file>...' to update what will be commited)");
-            for file in not_staged {
-                println!("\t{}", file.display());
+            for (change, file) in &status.not_staged {
+                println!("\t{:<12}{}", change, file.display());
             }
             println!();
             changes.push("Not staged for commit.".to_string())
         }
-        if !untracked.is_empty() {
+        if !status.untracked.is_empty() {
             println!(
                 "Untracked files:\n    (Use 'git-rustico add <file>' to include in what will be commited)"
             );
-            for file in untracked {
+            for file in &status.untracked {
                 println!("\t{}", file.display());
             }
             println!();
             changes.push("Untracked.".to_string())
         }
-        if staged.is_empty() {
+        if status.staged.is_empty() {
             println!("nothing added to commit (use 'git-rustico add <file>')");
         }
         changes.join(" ")
     }
 
-    /// Updates the index to reflect the working directory.
-    ///
-    /// # Arguments
-    ///
-    /// * `working_dir_files` - A vector of tuples containing the local file paths and their Git
-    ///                         hashes in the working directory.
-    /// * `home_path` - The path of the repository's home directory.
-    ///
-    /// # Returns
-    ///
-    /// A `Result` indicating whether the operation was successful or an `ErrorType` if an error
-    /// occurred.
-    pub(crate) fn update_to_working_dir(
-        &mut self,
-        working_dir_files: Vec<(PathBuf, GitHash)>,
-        home_path: &Path,
-    ) -> Result<(), ErrorType> {
-        let files = self.as_files_vector();
-        for file in files {
-            if !home_path.join(file.get_path()).exists() {
-                self.remove(file.get_path())?;
-            }
-        }
-
-        for (local_path, _) in working_dir_files {
-            let file_info = IndexFileInfo::new(local_path.clone(), home_path)?;
-            self.hash_map.insert(local_path, file_info);
-        }
-        Ok(())
-    }
-
     /// Checks if there are any uncommitted changes in the index.
     ///
     /// # Arguments
     ///
-    /// * `path_home` - The path of the repository's home directory.
-    /// * `path_ignore` - The path of the ignore file.
+    /// * `repo_paths` - The paths of the repository.
     ///
     /// # Returns
     ///
     /// A `Result` indicating whether there are any uncommitted changes or an `ErrorType` if an
     /// error occurred.
-    pub fn check_for_changes(&self, path_home: &Path, path_ignore: &Path) -> Result<(), ErrorType> {
-        let results = self.status(path_home, path_ignore)?;
-        for result in results {
-            if !result.is_empty() {
-                return Err(ErrorType::RepositoryError("There's uncommited changes either tracked in index or not.\nAdd and commit or delete them before continuing".to_string()));
-            }
+    pub fn check_for_changes(&mut self, repo_paths: &RepoPaths) -> Result<(), ErrorType> {
+        if !self.status(repo_paths)?.is_empty() {
+            return Err(ErrorType::RepositoryError("There's uncommited changes either tracked in index or not.\nAdd and commit or delete them before continuing".to_string()));
         }
         Ok(())
     }
@@ -522,3 +1043,245 @@ mod tests_status {
     //     }
     // }
 }
+
+#[cfg(test)]
+mod tests_dirc {
+    use super::*;
+    use crate::files::file_mode::FileMode;
+
+    // index escrito por git con `git add` de a.txt, run.sh (ejecutable) y "dir/con espacio.txt"
+    const GIT_INDEX: &[u8] = include_bytes!("../tests/data/index/git_index");
+
+    #[test]
+    fn reads_and_writes_index_from_git() -> Result<(), ErrorType> {
+        let index = Index::new(GIT_INDEX)?;
+
+        let mut files = index.as_files_vector();
+        files.sort_by_key(|file| file.get_path());
+        let files: Vec<(PathBuf, GitHash, FileMode)> = files
+            .iter()
+            .map(|file| (file.get_path(), file.get_hash(), file.get_mode()))
+            .collect();
+        assert_eq!(
+            files,
+            vec![
+                (
+                    PathBuf::from("a.txt"),
+                    GitHash::new("5c1b14949828006ed75a3e8858957f86a2f7e2eb")?,
+                    FileMode::Regular
+                ),
+                (
+                    PathBuf::from("dir/con espacio.txt"),
+                    GitHash::new("c1b0730e0133447badcfd47fd144e254807b06e1")?,
+                    FileMode::Regular
+                ),
+                (
+                    PathBuf::from("run.sh"),
+                    GitHash::new("1a2485251c33a70432394c93fb89330ef214bfc9")?,
+                    FileMode::Executable
+                ),
+            ]
+        );
+
+        // sin previous_blob_hash no se escribe ninguna extension: queda igual al de git
+        let mut saved = Vec::new();
+        index.save(&mut saved)?;
+        assert_eq!(saved, GIT_INDEX);
+        Ok(())
+    }
+
+    #[test]
+    fn keeps_previous_blob_hash_in_extension() -> Result<(), ErrorType> {
+        let mut index = Index::new(GIT_INDEX)?;
+        let hash = GitHash::new("e69de29bb2d1d6434b8b29ae775ad8c2e48c5391")?;
+        if let Some(file) = index.hash_map.get_mut(&PathBuf::from("run.sh")) {
+            file.set_previous_blob_hash(hash.clone());
+        }
+
+        let mut saved = Vec::new();
+        index.save(&mut saved)?;
+        let index = Index::new(saved.as_slice())?;
+
+        let previous = |path: &str| index.hash_map[&PathBuf::from(path)].get_previous_blob_hash();
+        assert_eq!(previous("run.sh"), Some(hash));
+        assert_eq!(previous("a.txt"), None);
+        Ok(())
+    }
+
+    #[test]
+    fn rejects_corrupted_index() {
+        let mut corrupted = GIT_INDEX.to_vec();
+        corrupted[20] ^= 1;
+        assert!(Index::new(corrupted.as_slice()).is_err());
+    }
+
+    #[test]
+    fn migrates_text_index_on_open() -> Result<(), ErrorType> {
+        let dir = std::env::temp_dir().join("git_rustico_tests_index_migration");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir)?;
+        let path_index = dir.join("index");
+        let hash = "e69de29bb2d1d6434b8b29ae775ad8c2e48c5391";
+        fs::write(
+            &path_index,
+            format!("mi archivo.txt 2023/10/17-18:15:00 {hash} {hash} 100644\nold.txt 2023/10/17-18:15:00 {hash} None\n"),
+        )?;
+
+        let index = Index::open(&path_index)?;
+        let content = fs::read(&path_index)?;
+        assert!(content.starts_with(INDEX_SIGNATURE));
+
+        let migrated = Index::new(content.as_slice())?;
+        let mut expected = index.as_files_vector();
+        let mut files = migrated.as_files_vector();
+        expected.sort_by_key(|file| file.get_path());
+        files.sort_by_key(|file| file.get_path());
+        assert_eq!(files, expected);
+        assert_eq!(files.len(), 2);
+        assert_eq!(files[0].get_previous_blob_hash(), Some(GitHash::new(hash)?));
+
+        fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests_full_status {
+    use super::*;
+    use crate::{commit::Commit, test_utils::init_repo};
+
+    fn write(home: &Path, path: &str, content: &str) -> Result<(), ErrorType> {
+        Ok(fs::write(home.join(path), content)?)
+    }
+
+    fn paths(changes: &[(FileChange, PathBuf)], change: FileChange) -> Vec<PathBuf> {
+        changes
+            .iter()
+            .filter(|(c, _)| *c == change)
+            .map(|(_, path)| path.clone())
+            .collect()
+    }
+
+    #[test]
+    fn reports_deleted_and_staged_then_modified() -> Result<(), ErrorType> {
+        let repo_paths = init_repo("git_rustico_tests_full_status")?;
+        let home = repo_paths.get_home();
+
+        for file in ["a.txt", "b.txt", "c.txt"] {
+            write(&home, file, file)?;
+        }
+        let files = vec![
+            "a.txt".to_string(),
+            "b.txt".to_string(),
+            "c.txt".to_string(),
+        ];
+        Index::add_command(files, &repo_paths)?;
+        Commit::commit_command(&repo_paths, vec!["first".to_string()])?;
+        let mut index = Index::open(&repo_paths.get_index())?;
+        assert!(index.status(&repo_paths)?.is_empty());
+
+        // a: add y modificado despues, b: borrado, c: sacado del index, d: nuevo, e: untracked
+        write(&home, "a.txt", "a2")?;
+        write(&home, "d.txt", "d")?;
+        Index::add_command(vec!["a.txt".to_string(), "d.txt".to_string()], &repo_paths)?;
+        write(&home, "a.txt", "a3")?;
+        fs::remove_file(home.join("b.txt"))?;
+        Index::rm_command(vec!["c.txt".to_string()], repo_paths.get_index())?;
+        write(&home, "e.txt", "e")?;
+
+        let mut index = Index::open(&repo_paths.get_index())?;
+        let status = index.status(&repo_paths)?;
+
+        assert_eq!(
+            paths(&status.staged, FileChange::Modified),
+            vec![PathBuf::from("a.txt")]
+        );
+        assert_eq!(
+            paths(&status.staged, FileChange::New),
+            vec![PathBuf::from("d.txt")]
+        );
+        assert_eq!(
+            paths(&status.staged, FileChange::Deleted),
+            vec![PathBuf::from("c.txt")]
+        );
+        assert_eq!(
+            paths(&status.not_staged, FileChange::Modified),
+            vec![PathBuf::from("a.txt")]
+        );
+        assert_eq!(
+            paths(&status.not_staged, FileChange::Deleted),
+            vec![PathBuf::from("b.txt")]
+        );
+        let mut untracked = status.untracked.clone();
+        untracked.sort();
+        assert_eq!(
+            untracked,
+            vec![PathBuf::from("c.txt"), PathBuf::from("e.txt")]
+        );
+        assert!(index.check_for_changes(&repo_paths).is_err());
+
+        fs::remove_dir_all(&home)?;
+        Ok(())
+    }
+
+    #[test]
+    fn restore_unstages_and_discards_changes() -> Result<(), ErrorType> {
+        let repo_paths = init_repo("git_rustico_tests_restore")?;
+        let home = repo_paths.get_home();
+        fs::create_dir_all(home.join("dir"))?;
+        write(&home, "a.txt", "a")?;
+        write(&home, "dir/b.txt", "b")?;
+        Index::add_command(
+            vec!["a.txt".to_string(), "dir/b.txt".to_string()],
+            &repo_paths,
+        )?;
+        Commit::commit_command(&repo_paths, vec!["first".to_string()])?;
+
+        write(&home, "a.txt", "a2")?;
+        write(&home, "new.txt", "new")?;
+        Index::add_command(
+            vec!["a.txt".to_string(), "new.txt".to_string()],
+            &repo_paths,
+        )?;
+        write(&home, "a.txt", "a3")?;
+        fs::remove_file(home.join("dir/b.txt"))?;
+
+        // --staged solo cambia el index: el contenido del working directory se conserva
+        let args = vec!["--staged".to_string(), ".".to_string()];
+        Index::restore_command(args, &repo_paths)?;
+        let mut index = Index::open(&repo_paths.get_index())?;
+        let status = index.status(&repo_paths)?;
+        assert!(status.staged.is_empty());
+        assert_eq!(
+            paths(&status.not_staged, FileChange::Modified),
+            vec![PathBuf::from("a.txt")]
+        );
+        assert_eq!(status.untracked, vec![PathBuf::from("new.txt")]);
+        assert_eq!(fs::read_to_string(home.join("a.txt"))?, "a3");
+
+        Index::restore_command(vec!["a.txt".to_string(), "dir".to_string()], &repo_paths)?;
+        assert_eq!(fs::read_to_string(home.join("a.txt"))?, "a");
+        assert_eq!(fs::read_to_string(home.join("dir/b.txt"))?, "b");
+        let mut index = Index::open(&repo_paths.get_index())?;
+        let status = index.status(&repo_paths)?;
+        assert!(status.not_staged.is_empty());
+
+        assert!(Index::restore_command(vec!["other.txt".to_string()], &repo_paths).is_err());
+
+        fs::remove_dir_all(&home)?;
+        Ok(())
+    }
+
+    #[test]
+    fn tracking_message_counts() {
+        assert_eq!(
+            Index::tracking_message("master", 0, 0),
+            "Your branch is up to date with 'master'."
+        );
+        assert!(Index::tracking_message("master", 1, 0)
+            .starts_with("Your branch is ahead of 'master' by 1 commit."));
+        assert!(Index::tracking_message("master", 0, 3)
+            .starts_with("Your branch is behind 'master' by 3 commits"));
+        assert!(Index::tracking_message("master", 2, 3).contains("have 2 and 3 different commits"));
+    }
+}
@@ -1,6 +1,6 @@
 use std::fs::{self, create_dir_all, File};
 
-use crate::{git_errors::errors::ErrorType, repo_paths::RepoPaths};
+use crate::{git_errors::errors::ErrorType, index::Index, repo_paths::RepoPaths};
 
 pub fn git_init(paths: RepoPaths) -> Result<String, ErrorType> {
     // Verifica si el directorio ya es un repositorio Git
@@ -12,12 +12,13 @@ pub fn git_init(paths: RepoPaths) -> Result<String, ErrorType> {
     // Crea el directorio .git-rustico dentro de la carpeta especificada
     create_dir_all(paths.get_objects())?;
     create_dir_all(paths.get_refs_heads())?;
+    create_dir_all(paths.get_refs_tags())?;
 
     // Crea el archivo HEAD
     fs::write(paths.get_head(), "master")?;
 
-    // Crea el archivo index
-    File::create(paths.get_index())?;
+    // Crea el archivo index (vacio, en formato DIRC)
+    Index::default().save(&mut File::create(paths.get_index())?)?;
 
     File::create(paths.get_config())?;
 
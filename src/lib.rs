@@ -2,10 +2,12 @@ pub mod blob;
 pub mod branch;
 pub mod cat_file;
 pub mod commit;
+pub mod commit_graph;
 pub mod compressor;
 pub mod config;
 pub mod diff;
 pub mod files;
+pub mod gc;
 pub mod git_errors;
 pub mod git_object;
 pub mod gui;
@@ -26,9 +28,14 @@ pub mod refs;
 pub mod remote;
 pub mod repo_paths;
 pub mod repository_access_permission;
+pub mod rev_parse;
+pub mod rev_walk;
 pub mod server_paths;
 pub mod show_ref;
+pub mod stash;
 pub mod tag;
+#[cfg(test)]
+mod test_utils;
 pub mod tree;
 pub mod user;
 pub mod utils;
@@ -1,5 +1,5 @@
 use crate::git_errors::errors::ErrorType;
-use crate::index::Index;
+use crate::index::{FileChange, Index};
 use crate::ls_tree::LsTree;
 use crate::repo_paths::RepoPaths;
 use std::fs;
@@ -25,11 +25,18 @@ impl LsFile {
 
     fn output_info(&self, repo_paths: &RepoPaths) -> Result<String, ErrorType> {
         // let (_untracked, not_staged, staged) = self.get_status_files(repo_paths)?;
-        let index = Index::open(&repo_paths.get_index())?;
-        // status = vec [untracked, not_staged, staged]
-        let status = index.status(&repo_paths.get_home(), &repo_paths.get_ignore())?;
-        let not_staged = self.convert_vec_pathbuf_to_vec_string(status[1].clone());
-        let staged = self.convert_vec_pathbuf_to_vec_string(status[2].clone());
+        let mut index = Index::open(&repo_paths.get_index())?;
+        let status = index.status(repo_paths)?;
+        index.save_refreshed(&repo_paths.get_index())?;
+        let not_staged = status.not_staged.into_iter().map(|(_, path)| path);
+        let not_staged = self.convert_vec_pathbuf_to_vec_string(not_staged.collect());
+        // los borrados del index ya no se listan
+        let staged = status
+            .staged
+            .into_iter()
+            .filter(|(change, _)| *change != FileChange::Deleted)
+            .map(|(_, path)| path);
+        let staged = self.convert_vec_pathbuf_to_vec_string(staged.collect());
         match self.others {
             true => self.get_untrackers(
                 not_staged,
@@ -92,10 +99,7 @@ impl LsFile {
     fn convert_vec_pathbuf_to_vec_string(&self, vec_pathbuf: Vec<PathBuf>) -> Vec<String> {
         let mut result = Vec::new();
         for path_buf in vec_pathbuf {
-            match path_buf.to_str() {
-                None => continue,
-                Some(a) => result.push(a.to_string()),
-            }
+            result.push(path_buf.to_string_lossy().to_string());
         }
         result
     }
@@ -1,11 +1,9 @@
-use crate::branch::Branch;
 use crate::cat_file::cat_file;
+use crate::files::file_mode::FileMode;
 use crate::git_errors::errors::ErrorType;
 use crate::git_object::GitObject;
-use crate::hash::GitHash;
-use crate::refs::BranchRef;
 use crate::repo_paths::RepoPaths;
-use crate::tag::Tag;
+use crate::rev_parse::RevParse;
 use crate::tree::Tree;
 
 const OPTION_FORMAT: &str = "--format=";
@@ -15,18 +13,6 @@ const FORMAT_TYPE: &str = "%(objecttype)";
 const FORMAT_HASH: &str = "%(objectname)";
 const FORMAT_SIZE: &str = "%(objectsize)";
 const OPTION_SIZE: &str = "-l";
-const REF_HEAD: &str = "HEAD";
-// Posibles "modos":
-// - 040000: Indica un directorio (tree). Este número representa el modo y los permisos de un directorio en Git.
-// - 100644: Indica un archivo normal (blob) con permisos de lectura y escritura para el propietario y solo lectura para otros.
-// - 100755: Indica un archivo ejecutable (blob) con permisos de ejecución para el propietario y lectura y ejecución para otros.
-// - 120000: Indica un enlace simbólico (blob) en Git.
-// - 160000: Indica un enlace a un commit de submodule en Git.
-const MODE_TREE: &str = "040000";
-const MODE_BLOB: &str = "100644";
-// const MODE_BLOB_EXEC: &str = "100755";
-// const MODE_BLOB_SYMBOL: &str = "120000";
-const MODE_LINK_COMMIT: &str = "160000";
 
 pub struct LsTree {
     tree: Tree,
@@ -84,60 +70,27 @@ impl LsTree {
                 "ERROR, ls-tree requier one argument (Branch/Hash commit/HEAD)".to_string(),
             ));
         }
-        let reference = if ref_commit_or_branch == *REF_HEAD {
-            let branch = BranchRef::open(repo_paths.get_head(), &repo_paths.get_refs_heads())?;
-            let last_commit = match branch.get_last_commit(&repo_paths.get_objects())? {
-                None => {
-                    return Err(ErrorType::RepositoryError(
-                        "Error in get commit HEAD".to_string(),
-                    ))
-                }
-                Some(commit) => commit,
-            };
-            last_commit.get_tree().clone()
-        } else {
-            match GitHash::new(&ref_commit_or_branch) {
-                Ok(hash) => {
-                    let commit = GitObject::read_commit(&hash, &repo_paths.get_objects())?;
-                    commit.get_tree().clone()
-                }
-                Err(_) => {
-                    match Branch::open(&repo_paths.get_refs_heads(), &ref_commit_or_branch) {
-                        Ok(branch) => {
-                            let last_commit = branch.get_last_commit(&repo_paths.get_objects())?;
-                            last_commit.get_tree().clone()
-                        }
-                        Err(_) => {
-                            // caso de TAG
-                            let hash_tag =
-                                Tag::get_hash_of_tag(repo_paths, ref_commit_or_branch.clone())?;
-                            let hash = GitHash::new(&hash_tag)?;
-                            let commit = GitObject::read_commit(&hash, &repo_paths.get_objects())?;
-                            commit.get_tree().clone()
-                        }
-                    }
-                }
-            }
-        };
-        Ok(reference)
+        // acepta cualquier revision que apunte a un commit o a un tree
+        let hash = RevParse::resolve(repo_paths, &ref_commit_or_branch)?;
+        let hash = RevParse::peel(repo_paths, &hash, "tree")?;
+        GitObject::read_tree(&hash, &repo_paths.get_objects())
     }
 
     fn output_info(&self) -> Result<String, ErrorType> {
         let mut list_output = Vec::new();
-        for (path, hash) in self.tree.get_files_vec() {
-            let path_file = match path.to_str() {
-                None => "",
-                Some(a) => a,
-            };
+        for (path, mode, hash) in self.tree.get_files_with_mode_vec() {
+            // los nombres que no son utf-8 se muestran con el caracter de reemplazo
+            let path_file = path.to_string_lossy();
             if !self.ref_path.is_empty() && !path_file.contains(&self.ref_path) {
                 continue;
             }
-            let (file_type, mode) = self.get_type_mode(&hash)?;
-            let name = hash.to_string();
-            let file_size = match mode == MODE_BLOB {
-                true => cat_file("-s", &hash.to_string())?,
-                false => "-".to_string(),
+            let (file_type, file_size) = match mode {
+                // los submodules apuntan a un commit de otro repo, no hay object para leer
+                FileMode::Gitlink => ("commit".to_string(), "-".to_string()),
+                _ => ("blob".to_string(), cat_file("-s", &hash.to_string())?),
             };
+            let mode = mode.to_string();
+            let name = hash.to_string();
             match self.format.is_empty() {
                 true => self.line_default(
                     mode,
@@ -199,20 +152,4 @@ impl LsTree {
         list_output.push(line_whit_path);
         Ok(())
     }
-
-    fn get_type_mode(&self, hash: &GitHash) -> Result<(String, String), ErrorType> {
-        let file_type = cat_file("-t", &hash.to_string())?;
-        let mode = match &*file_type {
-            "blob" => MODE_BLOB,
-            "tree" => MODE_TREE,
-            "commit" => MODE_LINK_COMMIT,
-            _ => {
-                return Err(ErrorType::ObjectType(
-                    "blob/tree/commit".to_string(),
-                    file_type.clone(),
-                ))
-            }
-        };
-        Ok((file_type, mode.to_string()))
-    }
 }
@@ -1,3 +1,5 @@
+use git_rustico::blob::Blob;
+use git_rustico::gc::Gc;
 use git_rustico::hash::GitHash;
 use git_rustico::ignore::Ignore;
 use git_rustico::index::Index;
@@ -8,7 +10,9 @@ use git_rustico::network_commands::{self, clone_command, pull_command, push_comm
 use git_rustico::rebase::Rebase;
 use git_rustico::remote::Remote;
 use git_rustico::repo_paths::RepoPaths;
+use git_rustico::rev_parse::RevParse;
 use git_rustico::show_ref::ShowRef;
+use git_rustico::stash::Stash;
 // use git_rustico::gui2;
 use git_rustico::tag::Tag;
 use git_rustico::{
@@ -23,6 +27,7 @@ use git_rustico::{
     refs::BranchRef,
 };
 use std::fs;
+use std::io::Write;
 use std::path::Path;
 
 fn main() {
@@ -110,22 +115,25 @@ fn match_command(
                     options.len(),
                 )));
             }
-            let string = git_rustico::cat_file::cat_file(&options[0], &args[0])?;
-            println!("{}", string);
+            let hash = RevParse::resolve(&repo_paths, &args[0])?;
+            let content = git_rustico::cat_file::cat_file_bytes(&options[0], &hash.to_string())?;
+            let mut stdout = std::io::stdout();
+            stdout.write_all(&content)?;
+            if !Blob::is_binary_content(&content) {
+                writeln!(stdout)?;
+            }
             Ok(format!("option: {}, file: {}", options[0], args[0]))
         }
         "status" => {
             check_arguments_quantity(0, &args)?;
             Index::status_command(&repo_paths)
         }
-        "check-ignore" => {
-            Ignore::check_ignore_command(args, &repo_paths)?;
-            Ok("".to_string()) // todo fix
-        }
+        "check-ignore" => Ignore::check_ignore_command(args, &repo_paths),
         "add" => {
             Index::add_command(args.clone(), &repo_paths)?;
             Ok(format!("Files added: {}.", args.join(", ")))
         }
+        "restore" => Index::restore_command(args, &repo_paths),
         "rm" => {
             Index::rm_command(args.clone(), repo_paths.get_index())?;
             Ok(format!("Files removed: {}.", args.join(", ")))
@@ -164,7 +172,7 @@ fn match_command(
         }
 
         "log" => {
-            let n = BranchRef::log_command(repo_paths)?;
+            let n = BranchRef::log_command(repo_paths, args)?;
             Ok(format!("Number of commits: {}.", n))
         }
 
@@ -174,6 +182,10 @@ fn match_command(
 
         "switch" => BranchRef::switch_command(repo_paths, args),
 
+        "reset" => BranchRef::reset_command(repo_paths, args),
+
+        "stash" => Stash::stash_command(args, &repo_paths),
+
         "merge" => {
             Merge::merge_command(repo_paths, args.clone())?;
             Ok(format!("Whit branch {}.", args[0].clone()))
@@ -187,6 +199,12 @@ fn match_command(
 
         "rebase" => Rebase::rebase(repo_paths, args),
 
+        "rev-parse" => RevParse::rev_parse_command(args, &repo_paths),
+
+        "merge-base" => Merge::merge_base_command(&repo_paths, args),
+
+        "gc" => Gc::gc_command(&repo_paths, args),
+
         "ls-tree" => {
             let text = LsTree::show_tree(args.clone(), &repo_paths)?;
             println!("{}", text);
@@ -10,19 +10,43 @@ use crate::{
     branch::Branch,
     commit::Commit,
     config::RepoConfig,
-    diff::{Diff, ModificationType},
-    git_errors::{command_error::CommandError::IncorrectAmount, errors::ErrorType},
+    diff::{Diff, MergeChunk},
+    files::file_mode::FileMode,
+    git_errors::{
+        command_error::CommandError::{IncorrectAmount, UnknownOption},
+        errors::ErrorType,
+    },
     git_object::GitObject,
     hash::GitHash,
     index::Index,
     refs::BranchRef,
     repo_paths::RepoPaths,
+    rev_parse::RevParse,
+    rev_walk::RevWalk,
     user::User,
 };
 type TreeFileVector = Vec<(PathBuf, Blob)>;
+
+const CONFLICT_START: &str = "<<<<<<<";
+const CONFLICT_BASE: &str = "|||||||";
+const CONFLICT_SEPARATOR: &str = "=======";
+const CONFLICT_END: &str = ">>>>>>>";
+const HEAD_LABEL: &str = "HEAD";
+const BASE_LABEL: &str = "base";
+
+// autor y mensaje del commit que junta los ancestros comunes en un criss-cross merge. El
+// mensaje tambien es la etiqueta de las marcas de conflicto dentro de esa base
+const VIRTUAL_MERGE_USER: &str = "git-rustico";
+const VIRTUAL_MERGE_MESSAGE: &str = "merged common ancestors";
+
 pub struct Merge;
 
 impl Merge {
+    /// merge <revision> | --continue | --abort
+    ///
+    /// Si hay conflictos el merge queda abierto: los archivos en conflicto quedan en el index con
+    /// sus versiones de la base, de HEAD y de la branch hasta que se agregan con add.
+    /// --continue crea el commit del merge y --abort vuelve el working directory y el index a HEAD.
     pub fn merge_command(repo_paths: RepoPaths, args: Vec<String>) -> Result<(), ErrorType> {
         if args.len() != 1 {
             return Err(ErrorType::CommandError(IncorrectAmount(
@@ -30,14 +54,35 @@ impl Merge {
                 args.len(),
             )));
         }
+        match args[0].as_str() {
+            "--continue" => return Self::continue_merge(&repo_paths),
+            "--abort" => return Self::abort_merge(&repo_paths),
+            option if option.starts_with('-') => {
+                return Err(ErrorType::CommandError(UnknownOption(
+                    "--continue, --abort".to_string(),
+                    args[0].clone(),
+                )))
+            }
+            _ => {}
+        }
+        if repo_paths.get_head_merge().exists() {
+            return Err(ErrorType::RepositoryError(
+                "You have not concluded your merge (HEAD_MERGE exists).\nUse 'git-rustico merge --continue' or 'git-rustico merge --abort' before you merge again.".to_string(),
+            ));
+        }
         let path_branches = repo_paths.get_refs_heads();
         let path_index = repo_paths.get_index();
         let path_home = repo_paths.get_home();
 
-        let index = Index::open(&path_index)?;
-        index.check_for_changes(&path_home, &repo_paths.get_ignore())?;
+        let mut index = Index::open(&path_index)?;
+        index.check_for_changes(&repo_paths)?;
 
         let mut head = BranchRef::open(repo_paths.get_head(), &path_branches)?;
+        if head.is_detached() {
+            return Err(ErrorType::RepositoryError(
+                "can't merge on a detached HEAD. Create a branch first".to_string(),
+            ));
+        }
 
         let mut head_branch =
             match head.get_branch() {
@@ -48,12 +93,33 @@ impl Merge {
                 )),
             };
 
-        let branch_name = &args[0];
-        let branch = Branch::open(&path_branches, branch_name)?;
+        // con una branch HEAD_MERGE guarda su nombre, con cualquier otra revision el hash
+        let revision = &args[0];
+        let (branch, merge_head) = match path_branches.join(revision).is_file() {
+            true => {
+                let branch = Branch::open(&path_branches, revision)?;
+                let name = branch.get_name();
+                (branch, name)
+            }
+            false => {
+                let hash = RevParse::resolve_commit(&repo_paths, revision)?.get_hash();
+                let merge_head = hash.to_string();
+                (
+                    Branch::from_revision(revision, &path_branches, hash),
+                    merge_head,
+                )
+            }
+        };
 
-        let (modified_files, conflict_files) = Self::merge(
+        // la base se calcula una vez, los conflictos la usan para el stage 1 del index
+        let path_objects = repo_paths.get_objects();
+        let head_commit = head_branch.get_last_commit(&path_objects)?;
+        let branch_commit = branch.get_last_commit(&path_objects)?;
+        let base = Self::merge_base(&head_commit, &branch_commit, &repo_paths)?;
+        let (modified_files, conflict_files) = Self::merge_from_base(
             &mut head_branch,
             branch.clone(),
+            base.clone(),
             repo_paths.clone(),
             None,
             None,
@@ -65,6 +131,7 @@ impl Merge {
                 &path_home,
                 &repo_paths.get_objects(),
                 &path_index,
+                false,
             )?;
             head.save()?;
             if modified_files.is_empty() {
@@ -74,22 +141,123 @@ impl Merge {
             }
         } else {
             println!("There were conflicts while merging. Both branches modified:\n");
-            for (file_path, _) in &conflict_files {
-                println!("\t{}\n", file_path.display());
-            }
-            let all_files: Vec<(PathBuf, Blob)> =
-                modified_files.into_iter().chain(conflict_files).collect();
-            for (file_path, blob) in all_files {
-                let mut file = File::create(&file_path)?;
-                file.write_all(blob.get_content().as_bytes())?;
+            for (file_path, blob) in &conflict_files {
+                match blob.is_binary() {
+                    true => println!(
+                        "\t{} (binary file, kept HEAD version)\n",
+                        file_path.display()
+                    ),
+                    false => println!("\t{}\n", file_path.display()),
+                }
             }
-            println!("Solve them manually and then close the merge with add and commit.");
-            fs::write(repo_paths.get_head_merge(), branch.get_name().as_bytes())?;
+            Self::write_conflicts(
+                &repo_paths,
+                index,
+                (base.as_ref(), &head_commit, &branch_commit),
+                modified_files,
+                conflict_files,
+            )?;
+            println!("Solve them manually, add them and then run 'git-rustico merge --continue'.");
+            fs::write(repo_paths.get_head_merge(), merge_head.as_bytes())?;
         }
 
         Ok(())
     }
 
+    // escribe el resultado del merge en el working directory. Los archivos que se mergearon
+    // quedan en el index y los que tienen conflictos con sus tres versiones (stages 1, 2 y 3)
+    fn write_conflicts(
+        repo_paths: &RepoPaths,
+        mut index: Index,
+        (base, head_commit, branch_commit): (Option<&Commit>, &Commit, &Commit),
+        modified_files: TreeFileVector,
+        conflict_files: TreeFileVector,
+    ) -> Result<(), ErrorType> {
+        let path_home = repo_paths.get_home();
+        let path_objects = repo_paths.get_objects();
+        let head_files = head_commit.get_files_map()?;
+        let branch_files = branch_commit.get_files_map()?;
+        let base_files = match base {
+            Some(base) => base.get_files_map()?,
+            None => HashMap::new(),
+        };
+
+        for (file_path, blob) in modified_files {
+            let mode = match branch_files.get(&file_path) {
+                Some((mode, _)) => *mode,
+                None => FileMode::Regular,
+            };
+            Blob::checkout(
+                &blob.get_hash(),
+                mode,
+                &path_home.join(&file_path),
+                &path_objects,
+            )?;
+            index.checkout_entry(file_path, mode, blob.get_hash(), &path_home)?;
+        }
+        for (file_path, blob) in conflict_files {
+            let mut file = File::create(path_home.join(&file_path))?;
+            file.write_all(&blob.get_content())?;
+            let stages = [&base_files, &head_files, &branch_files]
+                .map(|files| files.get(&file_path).cloned());
+            index.set_conflict(file_path, stages);
+        }
+        index.save(&mut File::create(repo_paths.get_index())?)
+    }
+
+    // cierra el merge abierto con el commit de los dos padres, commit toma el segundo de HEAD_MERGE
+    fn continue_merge(repo_paths: &RepoPaths) -> Result<(), ErrorType> {
+        let path_head_merge = repo_paths.get_head_merge();
+        if !path_head_merge.exists() {
+            return Err(ErrorType::RepositoryError(
+                "There is no merge in progress (HEAD_MERGE missing).".to_string(),
+            ));
+        }
+        let merge_head = fs::read_to_string(path_head_merge)?;
+        let head = BranchRef::open(repo_paths.get_head(), &repo_paths.get_refs_heads())?;
+        let head_name = match head.get_branch() {
+            Some(branch) => branch.get_name(),
+            None => fs::read_to_string(repo_paths.get_head())?,
+        };
+        let message = Self::merge_message(merge_head.trim(), head_name.trim());
+        Commit::commit_command(repo_paths, vec![message])
+    }
+
+    // vuelve el working directory y el index a HEAD. Es el estado de antes del merge porque
+    // merge no arranca con cambios sin commitear
+    fn abort_merge(repo_paths: &RepoPaths) -> Result<(), ErrorType> {
+        if !repo_paths.get_head_merge().exists() {
+            return Err(ErrorType::RepositoryError(
+                "There is no merge to abort (HEAD_MERGE missing).".to_string(),
+            ));
+        }
+        let path_objects = repo_paths.get_objects();
+        let path_index = repo_paths.get_index();
+        let head = BranchRef::open(repo_paths.get_head(), &repo_paths.get_refs_heads())?;
+        let Some(head_commit) = head.get_last_commit(&path_objects)? else {
+            return Err(ErrorType::RepositoryError(
+                "HEAD doesn't have commits to go back to".to_string(),
+            ));
+        };
+
+        let mut index = Index::open(&path_index)?;
+        BranchRef::reset_working_dir(
+            &mut index,
+            &head_commit,
+            &head_commit,
+            &repo_paths.get_home(),
+            &path_objects,
+        )?;
+        index.save(&mut File::create(path_index)?)?;
+        fs::remove_file(repo_paths.get_head_merge())?;
+        println!("Merge aborted");
+        Ok(())
+    }
+
+    fn merge_message(branch_name: &str, head_name: &str) -> String {
+        format!("Merge branch <{}> into <{}>", branch_name, head_name)
+    }
+
     pub fn merge(
         head_branch: &mut Branch,
         branch: Branch,
@@ -98,15 +266,31 @@ impl Merge {
         user: Option<User>,
     ) -> Result<(TreeFileVector, TreeFileVector), ErrorType> {
         let path_objects = repo_paths.get_objects();
+        let base = Self::merge_base(
+            &head_branch.get_last_commit(&path_objects)?,
+            &branch.get_last_commit(&path_objects)?,
+            &repo_paths,
+        )?;
+        Self::merge_from_base(head_branch, branch, base, repo_paths, message, user)
+    }
+
+    // merge con la base ya calculada: fast-forward si la base es HEAD, sino three-way
+    fn merge_from_base(
+        head_branch: &mut Branch,
+        branch: Branch,
+        base: Option<Commit>,
+        repo_paths: RepoPaths,
+        message: Option<String>,
+        user: Option<User>,
+    ) -> Result<(TreeFileVector, TreeFileVector), ErrorType> {
+        let path_objects = repo_paths.get_objects();
 
         let head_commit = head_branch.get_last_commit(&path_objects)?;
         let branch_commit = branch.get_last_commit(&path_objects)?;
 
-        let last_common_ancestor: Commit = branch_commit
-            .last_common_ancestor(&head_commit, &path_objects)?
-            .ok_or(ErrorType::RepositoryError(
-                "No common commit ancestor between both branch tips".to_string(),
-            ))?;
+        let last_common_ancestor = base.ok_or(ErrorType::RepositoryError(
+            "No common commit ancestor between both branch tips".to_string(),
+        ))?;
 
         if last_common_ancestor == head_commit {
             head_branch.set_last_commit_hash(branch_commit.get_hash());
@@ -125,6 +309,118 @@ impl Merge {
         }
     }
 
+    /// Base para mergear los dos commits. Si tienen mas de un mejor ancestro comun
+    /// (criss-cross) se mergean entre ellos y la base es ese commit virtual, que queda solo
+    /// en memoria.
+    pub(crate) fn merge_base(
+        head_commit: &Commit,
+        branch_commit: &Commit,
+        repo_paths: &RepoPaths,
+    ) -> Result<Option<Commit>, ErrorType> {
+        let mut walk = RevWalk::new(&repo_paths.get_objects());
+        let bases = walk.merge_bases(&head_commit.get_hash(), &branch_commit.get_hash())?;
+        Self::virtual_base(&mut walk, &bases, repo_paths)
+    }
+
+    // junta los ancestros comunes de a uno. La base de cada merge se calcula con los commits
+    // reales ya juntados, porque los virtuales no estan en objects
+    fn virtual_base(
+        walk: &mut RevWalk,
+        bases: &[GitHash],
+        repo_paths: &RepoPaths,
+    ) -> Result<Option<Commit>, ErrorType> {
+        let Some(first) = bases.first() else {
+            return Ok(None);
+        };
+        let mut base = walk.read(first)?.clone();
+        for (merged, other) in bases.iter().enumerate().skip(1) {
+            let inner_bases =
+                walk.common_ancestors(&bases[..merged], std::slice::from_ref(other))?;
+            let inner_base = Self::virtual_base(walk, &inner_bases, repo_paths)?;
+            let other = walk.read(other)?.clone();
+            base = Self::virtual_merge(base, other, inner_base, repo_paths)?;
+        }
+        Ok(Some(base))
+    }
+
+    // merge de dos ancestros comunes, los conflictos quedan con las marcas en el contenido.
+    // Solo se guardan los blobs, el commit y su tree no se escriben en objects
+    fn virtual_merge(
+        one: Commit,
+        other: Commit,
+        base: Option<Commit>,
+        repo_paths: &RepoPaths,
+    ) -> Result<Commit, ErrorType> {
+        let path_objects = repo_paths.get_objects();
+        let base_files = match base {
+            Some(base) => base.get_files_vec()?,
+            None => Vec::new(),
+        };
+        let (modified_files, conflict_files) = Self::compare_files(
+            one.get_files_vec()?,
+            other.get_files_vec()?,
+            base_files,
+            VIRTUAL_MERGE_MESSAGE,
+            repo_paths,
+        )?;
+
+        let mut tree = one.get_tree()?.clone();
+        for (file, blob) in modified_files.iter().chain(&conflict_files) {
+            GitObject::save_blob(blob.get_content(), &path_objects)?;
+            tree.add(file, blob.get_hash());
+        }
+        Commit::new(
+            tree,
+            Some(one.get_hash()),
+            Some(other.get_hash()),
+            VIRTUAL_MERGE_MESSAGE,
+            User::new(VIRTUAL_MERGE_USER, VIRTUAL_MERGE_USER),
+        )
+    }
+
+    /// merge-base [--all] <commit> <commit>
+    ///
+    /// Imprime el mejor ancestro comun de los dos commits, o todos con --all.
+    pub fn merge_base_command(
+        repo_paths: &RepoPaths,
+        args: Vec<String>,
+    ) -> Result<String, ErrorType> {
+        let mut all = false;
+        let mut revisions = Vec::new();
+        for arg in args {
+            match arg.as_str() {
+                "--all" | "-a" => all = true,
+                option if option.starts_with('-') => {
+                    return Err(ErrorType::CommandError(UnknownOption(
+                        "--all".to_string(),
+                        arg,
+                    )))
+                }
+                _ => revisions.push(arg),
+            }
+        }
+        if revisions.len() != 2 {
+            return Err(ErrorType::CommandError(IncorrectAmount(
+                "2".to_string(),
+                revisions.len(),
+            )));
+        }
+        let one = RevParse::resolve_commit(repo_paths, &revisions[0])?;
+        let other = RevParse::resolve_commit(repo_paths, &revisions[1])?;
+
+        let mut bases = one.merge_bases(&other, &repo_paths.get_objects())?;
+        if !all {
+            bases.truncate(1);
+        }
+        let result = bases
+            .iter()
+            .map(|base| base.get_hash().to_string())
+            .collect::<Vec<_>>()
+            .join("\n");
+        println!("{}", result);
+        Ok(result)
+    }
+
     fn real_merge(
         head_branch: &mut Branch,
         branch: Branch,
@@ -137,17 +433,22 @@ impl Merge {
         let head_commit = head_branch.get_last_commit(objects)?;
         let branch_commit = branch.get_last_commit(objects)?;
 
-        let head_files = head_commit.get_files_vec();
-        let branch_files = branch_commit.get_files_vec();
-        let lca_files = last_common_ancestor.get_files_vec();
+        let head_files = head_commit.get_files_vec()?;
+        let branch_files = branch_commit.get_files_vec()?;
+        let lca_files = last_common_ancestor.get_files_vec()?;
 
-        let (modified_files, conflict_files) =
-            Self::compare_files(head_files, branch_files, lca_files, &repo_paths)?;
+        let (modified_files, conflict_files) = Self::compare_files(
+            head_files,
+            branch_files,
+            lca_files,
+            &branch.get_name(),
+            &repo_paths,
+        )?;
 
-        let mut tree = head_commit.get_tree().clone();
+        let mut tree = head_commit.get_tree()?.clone();
         for (file, blob) in &modified_files {
-            GitObject::save_blob(blob.get_content().as_bytes().to_vec(), objects)?;
-            tree.add(&format!("{}", file.display()), blob.get_hash());
+            GitObject::save_blob(blob.get_content(), objects)?;
+            tree.add(file, blob.get_hash());
         }
 
         if !conflict_files.is_empty() {
@@ -156,11 +457,7 @@ impl Merge {
 
         let message = match message {
             Some(m) => m,
-            None => format!(
-                "Merge branch <{}> into <{}>",
-                branch.get_name(),
-                head_branch.get_name()
-            ),
+            None => Self::merge_message(&branch.get_name(), &head_branch.get_name()),
         };
 
         let user = match user{
@@ -183,13 +480,17 @@ impl Merge {
         Ok((modified_files, conflict_files))
     }
 
+    /// Archivos que la branch cambia respecto de head (lca es la base de las dos): los que se
+    /// pudieron mergear y los que quedaron con conflictos, con las marcas en el contenido
     pub fn compare_files(
         head_files: Vec<(PathBuf, GitHash)>,
         branch_files: Vec<(PathBuf, GitHash)>,
         lca_files: Vec<(PathBuf, GitHash)>,
+        branch_name: &str,
         repo_paths: &RepoPaths,
     ) -> Result<(TreeFileVector, TreeFileVector), ErrorType> {
         let path_objects = repo_paths.get_objects();
+        let style = RepoConfig::open(repo_paths.get_config())?.get_conflict_style();
         let mut conflict_files = Vec::new();
         let mut modified_files = Vec::new();
         // maybe sacar afuera
@@ -204,50 +505,56 @@ impl Merge {
             .collect::<HashMap<_, _>>();
 
         for (branch_file_path, branch_blob_hash) in branch_files {
-            if let Some(head_blob_hash) = head_files.get(&branch_file_path) {
-                //both have it
-                if head_blob_hash == &branch_blob_hash
-                // both have it but with the same changes
-                {
-                    continue;
-                };
-                let lca_blob_hash = match lca_files.get(&branch_file_path) {
-                    Some(a) => a,
-                    None => {
-                        return Err(ErrorType::RepositoryError(
-                            "No common ancestor between branches commits".to_string(),
-                        ))
-                    }
-                };
-                if lca_blob_hash == &branch_blob_hash {
-                    // only head has changes
-                    continue;
-                }
-                // both have changes
-
-                let head_content =
-                    GitObject::read_blob(head_blob_hash, &path_objects)?.get_content();
-                let branch_content =
-                    GitObject::read_blob(&branch_blob_hash, &path_objects)?.get_content();
-
-                let lca_content = GitObject::read_blob(lca_blob_hash, &path_objects)?.get_content();
-
-                let mut merged_content: String = String::new();
-
-                if Self::three_way_merge(
-                    &lca_content,
-                    &head_content,
-                    &branch_content,
-                    &mut merged_content,
-                )? {
-                    conflict_files.push((branch_file_path.clone(), Blob::new(merged_content)));
-                } else {
-                    modified_files.push((branch_file_path, Blob::new(merged_content)));
-                }
-            } else {
+            let Some(head_blob_hash) = head_files.get(&branch_file_path) else {
                 // only branch has it
                 let blob = GitObject::read_blob(&branch_blob_hash, &path_objects)?;
                 modified_files.push((branch_file_path, blob));
+                continue;
+            };
+            let lca_blob_hash = lca_files.get(&branch_file_path);
+            if head_blob_hash == &branch_blob_hash || lca_blob_hash == Some(&branch_blob_hash) {
+                // both have the same changes, or only head has changes
+                continue;
+            }
+            if lca_blob_hash == Some(head_blob_hash) {
+                // only branch has changes
+                let blob = GitObject::read_blob(&branch_blob_hash, &path_objects)?;
+                modified_files.push((branch_file_path, blob));
+                continue;
+            }
+            // both have changes. Si los dos lo agregaron se mergea contra un archivo vacio
+            let head_blob = GitObject::read_blob(head_blob_hash, &path_objects)?;
+            let branch_blob = GitObject::read_blob(&branch_blob_hash, &path_objects)?;
+            let lca_blob = match lca_blob_hash {
+                Some(hash) => GitObject::read_blob(hash, &path_objects)?,
+                None => Blob::new(Vec::new()),
+            };
+
+            let (lca_content, head_content, branch_content) = match (
+                lca_blob.get_text(),
+                head_blob.get_text(),
+                branch_blob.get_text(),
+            ) {
+                (Some(lca), Some(head), Some(branch)) => (lca, head, branch),
+                // los binarios no se mergean por lineas: queda la version de HEAD
+                // y se marca el conflicto para que se resuelva a mano
+                _ => {
+                    conflict_files.push((branch_file_path, head_blob));
+                    continue;
+                }
+            };
+
+            let mut merged_content: String = String::new();
+            let conflict = Self::three_way_merge(
+                (lca_content, head_content, branch_content),
+                branch_name,
+                style,
+                &mut merged_content,
+            );
+            let blob = Blob::new(merged_content.into_bytes());
+            match conflict {
+                true => conflict_files.push((branch_file_path, blob)),
+                false => modified_files.push((branch_file_path, blob)),
             }
         }
         Ok((modified_files, conflict_files))
@@ -255,124 +562,233 @@ impl Merge {
 
     /// Given an original common base text and two different modified versions of the base it merges them.
     /// It keeps the parts that are the same in the three of them and applies changes where just one text
-    /// made a change or marks a conflict where both texts modified the same part.
+    /// made a change. Where both texts modified the same part it writes both versions between conflict
+    /// markers (and the base too with the diff3 style). Returns whether there were conflicts.
     fn three_way_merge(
-        lca_content: &str,
-        head_content: &str,
-        branch_content: &str,
+        (lca_content, head_content, branch_content): (&str, &str, &str),
+        branch_name: &str,
+        style: ConflictStyle,
         buffer: &mut String,
-    ) -> Result<bool, ErrorType> {
-        let mut conflict = false;
-
-        let diff_head = Diff::diff(lca_content, head_content);
-        let diff_branch = Diff::diff(lca_content, branch_content);
-
-        let mut i = 0;
-        let mut j = 0;
-
-        let mut lines = Vec::new();
+    ) -> bool {
+        // las marcas usan el mismo fin de linea que el archivo
+        let eol = match head_content.find('\n') {
+            Some(end) if head_content[..end].ends_with('\r') => "\r\n",
+            _ => "\n",
+        };
+        // el salto de linea final se mergea aparte, queda como lo dejo la version que lo cambio
+        // respecto de la base. Para las lineas, todas las versiones terminan en salto de linea
+        let head_newline = head_content.ends_with('\n');
+        let final_newline = match head_newline != lca_content.ends_with('\n') {
+            true => head_newline,
+            false => branch_content.ends_with('\n'),
+        };
+        let with_eol = |content: &str| match content.is_empty() || content.ends_with('\n') {
+            true => content.to_string(),
+            false => format!("{content}{eol}"),
+        };
 
-        while i < diff_head.len() || j < diff_branch.len() {
-            if i >= diff_head.len() {
-                match &diff_branch[j] {
-                    ModificationType::Remove(_) => continue,
-                    ModificationType::Same(l) => lines.push(l.clone()),
-                    ModificationType::Add(l) => lines.push(l.clone()),
-                }
-                j += 1;
-            } else if j >= diff_branch.len() {
-                match &diff_head[i] {
-                    ModificationType::Remove(_) => continue,
-                    ModificationType::Same(l) => lines.push(l.clone()),
-                    ModificationType::Add(l) => lines.push(l.clone()),
-                }
-                i += 1;
-            } else {
-                match (&diff_head[i], &diff_branch[j]) {
-                    (ModificationType::Same(l), ModificationType::Same(_)) => {
-                        lines.push(l.clone());
-                        i += 1;
-                        j += 1;
-                    }
-                    (ModificationType::Same(_), ModificationType::Add(l)) => {
-                        lines.push(l.clone());
-                        j += 1;
-                    }
-                    (ModificationType::Add(l), ModificationType::Same(_)) => {
-                        lines.push(l.clone());
-                        i += 1;
-                    }
-                    (ModificationType::Same(_), ModificationType::Remove(_))
-                    | (ModificationType::Remove(_), ModificationType::Same(_))
-                    | (ModificationType::Remove(_), ModificationType::Remove(_)) => {
-                        i += 1;
-                        j += 1;
-                    }
-                    (ModificationType::Add(l1), ModificationType::Add(l2)) => {
-                        if l1 == l2 {
-                            lines.push(l1.clone());
-                            i += 1;
-                            j += 1;
-                        } else {
-                            //conflicto
-                            conflict = true;
-                            let conflict_block =
-                                Self::conflict(&diff_head, &mut i, &diff_branch, &mut j);
-                            lines.push(conflict_block);
-                        }
-                    }
-                    // conflicto
-                    _ => {
-                        conflict = true;
-                        let conflict_block =
-                            Self::conflict(&diff_head, &mut i, &diff_branch, &mut j);
-                        lines.push(conflict_block);
+        let mut conflict = false;
+        let mut ends_in_conflict = false;
+        buffer.clear();
+        let chunks = Diff::diff3(
+            &with_eol(lca_content),
+            &with_eol(head_content),
+            &with_eol(branch_content),
+        );
+        for chunk in chunks {
+            ends_in_conflict = matches!(chunk, MergeChunk::Conflict { .. });
+            match chunk {
+                MergeChunk::Resolved(resolved) => buffer.extend(resolved),
+                MergeChunk::Conflict { base, head, branch } => {
+                    conflict = true;
+                    Self::push_marker(buffer, &format!("{CONFLICT_START} {HEAD_LABEL}"), eol);
+                    buffer.extend(head);
+                    if style == ConflictStyle::Diff3 {
+                        Self::push_marker(buffer, &format!("{CONFLICT_BASE} {BASE_LABEL}"), eol);
+                        buffer.extend(base);
                     }
+                    Self::push_marker(buffer, CONFLICT_SEPARATOR, eol);
+                    buffer.extend(branch);
+                    Self::push_marker(buffer, &format!("{CONFLICT_END} {branch_name}"), eol);
                 }
             }
         }
+        // la marca de cierre siempre termina en salto de linea
+        if !final_newline && !ends_in_conflict {
+            if let Some(content) = buffer.strip_suffix(eol) {
+                buffer.truncate(content.len());
+            }
+        }
+        conflict
+    }
 
-        *buffer = lines.join("\n");
-        Ok(conflict)
+    // las lineas del merge siempre terminan en salto de linea, la marca va en la siguiente
+    fn push_marker(buffer: &mut String, marker: &str, eol: &str) {
+        buffer.push_str(marker);
+        buffer.push_str(eol);
     }
+}
 
-    /// Given two vectors of different modifications to the lines of a text and the position of a conflict it creates
-    /// a divided block of lines containing the conflicting changes made in each modified text separatedly.
-    fn conflict(
-        diff_head: &[ModificationType],
-        head_conflict_line: &mut usize,
-        diff_branch: &[ModificationType],
-        branch_conflict_line: &mut usize,
-    ) -> String {
-        let mut result = Vec::new();
-        result.push("<<<<<<< HEAD");
-        while *head_conflict_line < diff_head.len() {
-            match &diff_head[*head_conflict_line] {
-                ModificationType::Same(_) => break,
-                ModificationType::Add(l) => result.push(l),
-                ModificationType::Remove(_) => (),
-            }
-            *head_conflict_line += 1;
-        }
-        result.push("=======");
+/// Como se escriben los conflictos de un merge (merge_conflict_style en la config)
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum ConflictStyle {
+    /// Las versiones de HEAD y de la branch
+    #[default]
+    Merge,
+    /// Tambien la version de la base, entre las dos
+    Diff3,
+}
 
-        while *branch_conflict_line < diff_branch.len() {
-            match &diff_branch[*branch_conflict_line] {
-                ModificationType::Same(_) => break,
-                ModificationType::Add(l) => result.push(l),
-                ModificationType::Remove(_) => (),
-            }
-            *branch_conflict_line += 1;
+impl ConflictStyle {
+    pub fn parse(style: &str) -> Result<Self, ErrorType> {
+        match style.trim() {
+            "merge" => Ok(Self::Merge),
+            "diff3" => Ok(Self::Diff3),
+            other => Err(ErrorType::FormatError(format!(
+                "Invalid conflict style '{other}', expected merge or diff3"
+            ))),
         }
-        result.push(">>>>>>> Merge Branch");
+    }
 
-        result.join("\n")
+    pub fn as_str(&self) -> &str {
+        match self {
+            Self::Merge => "merge",
+            Self::Diff3 => "diff3",
+        }
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::{git_errors::errors::ErrorType, merge::Merge};
+    use crate::{
+        branch::Branch,
+        commit::Commit,
+        git_errors::errors::ErrorType,
+        git_object::GitObject,
+        hash::GitHash,
+        index::Index,
+        merge::{ConflictStyle, Merge},
+        refs::BranchRef,
+        test_utils::{commit_file, commit_with_parents, init_repo},
+    };
+    use std::{fs, path::PathBuf};
+
+    #[test]
+    fn criss_cross_merges_common_ancestors() -> Result<(), ErrorType> {
+        let repo_paths = init_repo("git_rustico_tests_merge_criss_cross")?;
+        let path_objects = repo_paths.get_objects();
+
+        //   base - left  - left_merge
+        //       \       X
+        //        right - right_merge
+        let base = commit_with_parents(&path_objects, &[], "1\n2\n3\n")?;
+        let left = commit_with_parents(&path_objects, &[&base.get_hash()], "one\n2\n3\n")?;
+        let right = commit_with_parents(&path_objects, &[&base.get_hash()], "1\n2\nthree\n")?;
+        let (left_hash, right_hash) = (left.get_hash(), right.get_hash());
+        let left_merge =
+            commit_with_parents(&path_objects, &[&left_hash, &right_hash], "one\n2\nthree\n")?;
+        let right_merge =
+            commit_with_parents(&path_objects, &[&right_hash, &left_hash], "one\n2\nthree\n")?;
+
+        let bases = left_merge.merge_bases(&right_merge, &repo_paths.get_objects())?;
+        let mut bases: Vec<GitHash> = bases.iter().map(|commit| commit.get_hash()).collect();
+        bases.sort();
+        let mut expected = vec![left_hash, right_hash];
+        expected.sort();
+        assert_eq!(bases, expected);
+        assert_eq!(
+            left.merge_bases(&right_merge, &repo_paths.get_objects())?,
+            vec![left.clone()]
+        );
+
+        // la base virtual tiene los cambios de los dos ancestros
+        let virtual_base = Merge::merge_base(&left_merge, &right_merge, &repo_paths)?
+            .ok_or(ErrorType::RepositoryError("no merge base".to_string()))?;
+        assert_eq!(virtual_base.get_parents().len(), 2);
+        assert!(!GitObject::contains(
+            &virtual_base.get_hash(),
+            &path_objects
+        ));
+        let (_, hash) = virtual_base.get_files_vec()?.remove(0);
+        let blob = GitObject::read_blob(&hash, &repo_paths.get_objects())?;
+        assert_eq!(blob.get_text(), Some("one\n2\nthree\n"));
+
+        fs::remove_dir_all(repo_paths.get_home())?;
+        Ok(())
+    }
+
+    #[test]
+    fn criss_cross_with_three_common_ancestors() -> Result<(), ErrorType> {
+        let repo_paths = init_repo("git_rustico_tests_merge_three_bases")?;
+        let path_objects = repo_paths.get_objects();
+
+        // cada ancestro cambia una linea distinta y los dos merges los juntan en otro orden
+        let base = commit_with_parents(&path_objects, &[], "1\n2\n3\n4\n5\n")?;
+        let ancestors = [
+            "one\n2\n3\n4\n5\n",
+            "1\n2\nthree\n4\n5\n",
+            "1\n2\n3\n4\nfive\n",
+        ]
+        .into_iter()
+        .map(|content| commit_with_parents(&path_objects, &[&base.get_hash()], content))
+        .map(|commit| commit.map(|commit| commit.get_hash()))
+        .collect::<Result<Vec<GitHash>, ErrorType>>()?;
+        let [a, b, c] = [&ancestors[0], &ancestors[1], &ancestors[2]];
+        let left = commit_with_parents(&path_objects, &[a, b], "one\n2\nthree\n4\n5\n")?;
+        let left = commit_with_parents(&path_objects, &[&left.get_hash(), c], "merged\n")?;
+        let right = commit_with_parents(&path_objects, &[b, c], "1\n2\nthree\n4\nfive\n")?;
+        let right = commit_with_parents(&path_objects, &[&right.get_hash(), a], "merged\n")?;
+
+        let virtual_base = Merge::merge_base(&left, &right, &repo_paths)?
+            .ok_or(ErrorType::RepositoryError("no merge base".to_string()))?;
+        let (_, hash) = virtual_base.get_files_vec()?.remove(0);
+        let blob = GitObject::read_blob(&hash, &path_objects)?;
+        assert_eq!(blob.get_text(), Some("one\n2\nthree\n4\nfive\n"));
+
+        fs::remove_dir_all(repo_paths.get_home())?;
+        Ok(())
+    }
+
+    #[test]
+    fn conflicts_stay_in_index_until_continue_or_abort() -> Result<(), ErrorType> {
+        let repo_paths = init_repo("git_rustico_tests_merge_continue_abort")?;
+        let home = repo_paths.get_home();
+
+        commit_file(&repo_paths, "f.txt", "1\n2\n3\n")?;
+        Branch::branch_command(&repo_paths, vec!["dev".to_string()])?;
+        commit_file(&repo_paths, "f.txt", "1\nmaster\n3\n")?;
+        BranchRef::checkout_command(repo_paths.clone(), vec!["dev".to_string()])?;
+        commit_file(&repo_paths, "f.txt", "1\ndev\n3\n")?;
+        BranchRef::checkout_command(repo_paths.clone(), vec!["master".to_string()])?;
+        let merge = |arg: &str| Merge::merge_command(repo_paths.clone(), vec![arg.to_string()]);
+        let unmerged =
+            || Ok::<_, ErrorType>(Index::open(&repo_paths.get_index())?.unmerged_paths());
+
+        merge("dev")?;
+        assert_eq!(unmerged()?, vec![PathBuf::from("f.txt")]);
+        assert!(Commit::commit_command(&repo_paths, vec!["wip".to_string()]).is_err());
+        assert!(merge("--continue").is_err());
+
+        merge("--abort")?;
+        assert!(unmerged()?.is_empty());
+        assert!(!repo_paths.get_head_merge().exists());
+        assert_eq!(fs::read_to_string(home.join("f.txt"))?, "1\nmaster\n3\n");
+
+        merge("dev")?;
+        fs::write(home.join("f.txt"), "1\nboth\n3\n")?;
+        Index::add_command(vec!["f.txt".to_string()], &repo_paths)?;
+        assert!(unmerged()?.is_empty());
+        merge("--continue")?;
+        let head = BranchRef::open(repo_paths.get_head(), &repo_paths.get_refs_heads())?;
+        let merge_commit = head
+            .get_last_commit(&repo_paths.get_objects())?
+            .ok_or(ErrorType::RepositoryError("no merge commit".to_string()))?;
+        assert_eq!(merge_commit.get_parents().len(), 2);
+        assert!(!repo_paths.get_head_merge().exists());
+
+        fs::remove_dir_all(&home)?;
+        Ok(())
+    }
 
     #[test]
     fn conflict_both_modified_same_line() -> Result<(), ErrorType> {
@@ -380,24 +796,124 @@ mod tests {
         let head_content = "line 1\nline 4\nline 3\n";
         let branch_content = "line 1\nline 5\nline 3\n";
         let mut writer = String::new();
-        let result =
-            Merge::three_way_merge(lca_content, head_content, branch_content, &mut writer)?;
+        let result = Merge::three_way_merge(
+            (lca_content, head_content, branch_content),
+            "branch",
+            ConflictStyle::Merge,
+            &mut writer,
+        );
         assert!(result);
         assert_eq!(
             writer,
-            "line 1\n<<<<<<< HEAD\nline 4\n=======\nline 5\n>>>>>>> Merge Branch\nline 3"
+            "line 1\n<<<<<<< HEAD\nline 4\n=======\nline 5\n>>>>>>> branch\nline 3\n"
         );
         Ok(())
     }
 
+    #[test]
+    fn final_newline_comes_from_the_side_that_changed_it() {
+        let lca_content = "a\nb\nc\nd\ne\n";
+        let head_content = "A\nb\nc\nd\ne\n";
+        let branch_content = "a\nb\nc\nd\ne";
+        let mut writer = String::new();
+        let result = Merge::three_way_merge(
+            (lca_content, head_content, branch_content),
+            "branch",
+            ConflictStyle::Merge,
+            &mut writer,
+        );
+        assert!(!result);
+        assert_eq!(writer, "A\nb\nc\nd\ne");
+
+        let result = Merge::three_way_merge(
+            (branch_content, branch_content, head_content),
+            "branch",
+            ConflictStyle::Merge,
+            &mut writer,
+        );
+        assert!(!result);
+        assert_eq!(writer, "A\nb\nc\nd\ne\n");
+    }
+
+    #[test]
+    fn crlf_line_endings_are_kept() {
+        let lca_content = "a\r\nb\r\nc\r\n";
+        let head_content = "A\r\nb\r\nc\r\n";
+        let branch_content = "a\r\nb\r\nC\r\n";
+        let mut writer = String::new();
+        let result = Merge::three_way_merge(
+            (lca_content, head_content, branch_content),
+            "branch",
+            ConflictStyle::Merge,
+            &mut writer,
+        );
+        assert!(!result);
+        assert_eq!(writer, "A\r\nb\r\nC\r\n");
+
+        let branch_content = "X\r\nb\r\nc\r\n";
+        let result = Merge::three_way_merge(
+            (lca_content, head_content, branch_content),
+            "branch",
+            ConflictStyle::Merge,
+            &mut writer,
+        );
+        assert!(result);
+        assert_eq!(
+            writer,
+            "<<<<<<< HEAD\r\nA\r\n=======\r\nX\r\n>>>>>>> branch\r\nb\r\nc\r\n"
+        );
+
+        let result = Merge::three_way_merge(
+            ("a\r\nb\r\nc", "A\r\nb\r\nc", "a\r\nb\r\nC"),
+            "branch",
+            ConflictStyle::Merge,
+            &mut writer,
+        );
+        assert!(!result);
+        assert_eq!(writer, "A\r\nb\r\nC");
+    }
+
+    #[test]
+    fn separate_changes_merge_and_diff3_shows_base() {
+        let lca_content = "a\nb\nc\nd\ne\n";
+        let head_content = "A\nb\nc\nd\ne\n";
+        let branch_content = "a\nb\nc\nd\nE\n";
+        let mut writer = String::new();
+        let result = Merge::three_way_merge(
+            (lca_content, head_content, branch_content),
+            "branch",
+            ConflictStyle::Diff3,
+            &mut writer,
+        );
+        assert!(!result);
+        assert_eq!(writer, "A\nb\nc\nd\nE\n");
+
+        let branch_content = "a2\nb\nc\nd\nE\n";
+        let result = Merge::three_way_merge(
+            (lca_content, head_content, branch_content),
+            "topic",
+            ConflictStyle::Diff3,
+            &mut writer,
+        );
+        assert!(result);
+        assert_eq!(
+            writer,
+            "<<<<<<< HEAD\nA\n||||||| base\na\n=======\na2\n>>>>>>> topic\nb\nc\nd\nE\n"
+        );
+    }
+
     #[test]
     fn no_conflict_nothing_changed() -> Result<(), ErrorType> {
         let lca_content = "line 1\nline 2\nline 3";
         let head_content = "line 1\nline 2\nline 3";
         let branch_content = "line 1\nline 2\nline 3";
         let mut writer = String::new();
-        let result =
-            Merge::three_way_merge(lca_content, head_content, branch_content, &mut writer)?;
+        let result = Merge::three_way_merge(
+            (lca_content, head_content, branch_content),
+            "branch",
+            ConflictStyle::Merge,
+            &mut writer,
+        );
         assert!(!result);
         assert_eq!(writer, "line 1\nline 2\nline 3");
         Ok(())
@@ -409,10 +925,14 @@ mod tests {
         let head_content = "line 1\nline 2\nline 3\nline 4\n";
         let branch_content = "line 1\nline 2\nline 3\nline 4\n";
         let mut writer = String::new();
-        let result =
-            Merge::three_way_merge(lca_content, head_content, branch_content, &mut writer)?;
+        let result = Merge::three_way_merge(
+            (lca_content, head_content, branch_content),
+            "branch",
+            ConflictStyle::Merge,
+            &mut writer,
+        );
         assert!(!result);
-        assert_eq!(writer, "line 1\nline 2\nline 3\nline 4");
+        assert_eq!(writer, "line 1\nline 2\nline 3\nline 4\n");
         Ok(())
     }
 
@@ -422,10 +942,14 @@ mod tests {
         let head_content = "line 1\nline 3\n";
         let branch_content = "line 1\nline 3\n";
         let mut writer = String::new();
-        let result =
-            Merge::three_way_merge(lca_content, head_content, branch_content, &mut writer)?;
+        let result = Merge::three_way_merge(
+            (lca_content, head_content, branch_content),
+            "branch",
+            ConflictStyle::Merge,
+            &mut writer,
+        );
         assert!(!result);
-        assert_eq!(writer, "line 1\nline 3");
+        assert_eq!(writer, "line 1\nline 3\n");
         Ok(())
     }
 
@@ -435,12 +959,16 @@ mod tests {
         let head_content = "line 0\nline 1\nline 2\nline 3\nline 4\n";
         let branch_content = "line 5\nline 1\nline 2\nline 2.5\nline 3\nline 6\n";
         let mut writer = String::new();
-        let result =
-            Merge::three_way_merge(lca_content, head_content, branch_content, &mut writer)?;
+        let result = Merge::three_way_merge(
+            (lca_content, head_content, branch_content),
+            "branch",
+            ConflictStyle::Merge,
+            &mut writer,
+        );
         assert!(result);
         assert_eq!(
             writer,
-            "<<<<<<< HEAD\nline 0\n=======\nline 5\n>>>>>>> Merge Branch\nline 1\nline 2\nline 2.5\nline 3\n<<<<<<< HEAD\nline 4\n=======\nline 6\n>>>>>>> Merge Branch"
+            "<<<<<<< HEAD\nline 0\n=======\nline 5\n>>>>>>> branch\nline 1\nline 2\nline 2.5\nline 3\n<<<<<<< HEAD\nline 4\n=======\nline 6\n>>>>>>> branch\n"
         );
         Ok(())
     }
@@ -451,10 +979,14 @@ mod tests {
         let head_content = "line 1\nline a\nline b\nline c\nline 3\n";
         let branch_content = "line 1\nline d\nline e\nline f\nline 3\n";
         let mut writer = String::new();
-        let result =
-            Merge::three_way_merge(lca_content, head_content, branch_content, &mut writer)?;
+        let result = Merge::three_way_merge(
+            (lca_content, head_content, branch_content),
+            "branch",
+            ConflictStyle::Merge,
+            &mut writer,
+        );
         assert!(result);
-        assert_eq!(writer, "line 1\n<<<<<<< HEAD\nline a\nline b\nline c\n=======\nline d\nline e\nline f\n>>>>>>> Merge Branch\nline 3");
+        assert_eq!(writer, "line 1\n<<<<<<< HEAD\nline a\nline b\nline c\n=======\nline d\nline e\nline f\n>>>>>>> branch\nline 3\n");
         Ok(())
     }
 
@@ -464,8 +996,12 @@ mod tests {
         let head_content = "line 0\nline 1";
         let branch_content = "line 1\nline 2";
         let mut writer = String::new();
-        let result =
-            Merge::three_way_merge(lca_content, head_content, branch_content, &mut writer)?;
+        let result = Merge::three_way_merge(
+            (lca_content, head_content, branch_content),
+            "branch",
+            ConflictStyle::Merge,
+            &mut writer,
+        );
         assert!(!result);
         assert_eq!(writer, "line 0\nline 1\nline 2");
         Ok(())
@@ -6,12 +6,13 @@ use std::{
 };
 
 use crate::git_errors::command_error::CommandError::{self, IncorrectAmount, InvalidBranch};
-use crate::protocol::pack_file::{read_packfile, send_packfile};
+use crate::protocol::pack_file::{save_packfile, send_packfile, tree_entry_names};
 use crate::protocol::pkt_line::read_pkt_line;
 use crate::{
-    branch::Branch, files::object_type::ObjectType, git_errors::errors::ErrorType,
-    git_object::GitObject, hash::GitHash, index::Index, merge::Merge,
-    protocol::pkt_line::create_pkt_line, refs::BranchRef, remote::Remote, repo_paths::RepoPaths,
+    branch::Branch, commit_graph::CommitGraph, config::RepoConfig, files::object_type::ObjectType,
+    git_errors::errors::ErrorType, git_object::GitObject, hash::GitHash, index::Index,
+    merge::Merge, protocol::pkt_line::create_pkt_line, refs::BranchRef, remote::Remote,
+    repo_paths::RepoPaths, rev_walk::RevWalk,
 };
 
 const HASH_ZERO: [u8; 20] = [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0];
@@ -85,6 +86,7 @@ pub fn clone_command(repo_paths: RepoPaths, args: Vec<String>) -> Result<(), Err
         &repo_paths.get_home(),
         &repo_paths.get_objects(),
         &repo_paths.get_index(),
+        false,
     )?;
     head.save()?;
 
@@ -122,7 +124,7 @@ pub fn fetch_command(repo_paths: RepoPaths) -> Result<usize, ErrorType> {
         return Ok(0);
     }
 
-    let mut want_hashes = Vec::new();
+    let mut refs_to_update = Vec::new();
 
     for (branch_name, remote_hash) in remote_refs {
         if let Some(local_hash) = local_remote_refs.get(&branch_name) {
@@ -130,12 +132,11 @@ pub fn fetch_command(repo_paths: RepoPaths) -> Result<usize, ErrorType> {
                 continue;
             }
         }
-        want_hashes.push(remote_hash.clone());
-        Branch::new(&branch_name, &path_remote_branches, remote_hash)?;
+        refs_to_update.push((branch_name, remote_hash));
     }
 
     // packfile negotiation
-    for hash in want_hashes {
+    for (_, hash) in &refs_to_update {
         let want_pkt_line = create_pkt_line(&format!("want {}", hash))?;
         stream.write_all(want_pkt_line.as_bytes())?;
     }
@@ -153,14 +154,20 @@ pub fn fetch_command(repo_paths: RepoPaths) -> Result<usize, ErrorType> {
     // read packfile
 
     let mut reader = BufReader::new(stream);
-    let packfile_objects = read_packfile(&mut reader)?;
-    println!("{} objects fetched from remote", packfile_objects.len());
-
-    for (object_type, content) in packfile_objects.iter().cloned() {
-        GitObject::save_object(content, object_type, &repo_paths.get_objects())?;
+    let object_amount = save_packfile(&mut reader, &repo_paths.get_objects())?;
+    println!("{} objects fetched from remote", object_amount);
+
+    // remote refs are only updated once all their objects were received
+    let fetched: Vec<GitHash> = refs_to_update
+        .iter()
+        .map(|(_, hash)| hash.clone())
+        .collect();
+    for (branch_name, remote_hash) in refs_to_update {
+        Branch::new(&branch_name, &path_remote_branches, remote_hash)?;
     }
+    CommitGraph::update(&repo_paths.get_objects(), &fetched)?;
 
-    Ok(packfile_objects.len())
+    Ok(object_amount)
 }
 
 ////////////////////////////////////////////////////////////////////////////////////////////////////////
@@ -170,8 +177,8 @@ pub fn fetch_command(repo_paths: RepoPaths) -> Result<usize, ErrorType> {
 // "pull" -> pull from upstream
 // "pull [remote-branch]"
 pub fn pull_command(repo_paths: RepoPaths, args: Vec<String>) -> Result<(), ErrorType> {
-    let index = Index::open(&repo_paths.get_index())?;
-    index.check_for_changes(&repo_paths.get_home(), &repo_paths.get_ignore())?;
+    let mut index = Index::open(&repo_paths.get_index())?;
+    index.check_for_changes(&repo_paths)?;
     let object_ammount = fetch_command(repo_paths.clone())?;
     if object_ammount == 0 {
         println!("Already up to date");
@@ -179,6 +186,11 @@ pub fn pull_command(repo_paths: RepoPaths, args: Vec<String>) -> Result<(), Erro
     }
 
     let mut head = BranchRef::open(repo_paths.get_head(), &repo_paths.get_refs_heads())?;
+    if head.is_detached() {
+        return Err(ErrorType::RepositoryError(
+            "can't pull on a detached HEAD. Checkout a branch first".to_string(),
+        ));
+    }
     let mut head_branch = match head.get_branch() {
         Some(b) => b,
         None => {
@@ -200,6 +212,7 @@ pub fn pull_command(repo_paths: RepoPaths, args: Vec<String>) -> Result<(), Erro
                 &repo_paths.get_home(),
                 &repo_paths.get_objects(),
                 &repo_paths.get_index(),
+                false,
             )?;
             head.save()?;
             println!(
@@ -239,6 +252,7 @@ pub fn pull_command(repo_paths: RepoPaths, args: Vec<String>) -> Result<(), Erro
         &repo_paths.get_home(),
         &repo_paths.get_objects(),
         &repo_paths.get_index(),
+        false,
     )?;
 
     let method = if modified_files.is_empty() {
@@ -308,8 +322,8 @@ fn parse_pull_branch_name(
 //  doesnt support tags. if remote sends one it will produce unwanted behaviour
 //  it discards remote ref HEAD if it was sent
 pub fn push_command(repo_paths: RepoPaths, args: Vec<String>) -> Result<(), ErrorType> {
-    let index = Index::open(&repo_paths.get_index())?;
-    index.check_for_changes(&repo_paths.get_home(), &repo_paths.get_ignore())?;
+    let mut index = Index::open(&repo_paths.get_index())?;
+    index.check_for_changes(&repo_paths)?;
 
     //todo refactor remote
     let mut stream = Remote::connect_receive_pack(repo_paths.get_remote(), "origin".to_string())?;
@@ -360,7 +374,8 @@ pub fn push_command(repo_paths: RepoPaths, args: Vec<String>) -> Result<(), Erro
         &repo_paths.get_objects(),
     )?;
 
-    send_packfile(&mut stream, packfile_objects)?;
+    let options = RepoConfig::open(repo_paths.get_config())?.get_pack_options();
+    send_packfile(&mut stream, packfile_objects, options)?;
 
     let mut report = Vec::new();
 
@@ -481,38 +496,55 @@ pub fn get_packfile_objects(
 
     let mut packfile_objects: Vec<(ObjectType, GitHash, Vec<u8>)> = Vec::new();
 
-    // todo convertir en commit.get_reachable_objects()
+    // commits the remote already has (and so all of their ancestors) are not walked
+    let mut walk = RevWalk::new(path_objects);
+    for hash in &repeated_hashes_remote {
+        if let Ok((ObjectType::Commit, _)) = GitObject::read_object(hash, path_objects) {
+            walk.hide(hash.clone());
+        }
+    }
     for commit_hash in commits_to_update {
-        let commit = GitObject::read_commit(&commit_hash, path_objects)?;
-        let commit_history = commit.get_commits_history_rec(path_objects)?;
-        for commit in commit_history {
-            if !repeated_hashes_remote.insert(commit.get_hash()) {
-                // break; // from here on remote should allready have all the objects
-            }
-            packfile_objects.push((ObjectType::Commit, commit.get_hash(), commit.get_content()?));
-            let tree = commit.get_tree();
-            let tree_hash = tree.get_hash()?;
-            if !repeated_hashes_remote.insert(tree_hash.clone()) {
-                continue;
-            }
-            packfile_objects.push((ObjectType::Tree, tree_hash, tree.generate_content()?));
-            let tree_files = tree.get_files_vec();
-            for (_, blob_hash) in tree_files {
-                if !repeated_hashes_remote.insert(blob_hash.clone()) {
-                    continue;
-                }
-                let blob = GitObject::read_blob(&blob_hash, path_objects)?;
-                packfile_objects.push((
-                    ObjectType::Blob,
-                    blob_hash,
-                    blob.get_content().as_bytes().to_vec(),
-                ));
-            }
+        walk.push(commit_hash);
+    }
+    for commit in walk {
+        let commit = commit?;
+        if !repeated_hashes_remote.insert(commit.get_hash()) {
+            continue; // remote (or this packfile) already has it
         }
+        // objects are sent exactly as stored, so their hashes match on the other side
+        let (_, content) = GitObject::read_object(&commit.get_hash(), path_objects)?;
+        packfile_objects.push((ObjectType::Commit, commit.get_hash(), content));
+        push_tree_objects(
+            commit.get_tree_hash(),
+            &mut repeated_hashes_remote,
+            path_objects,
+            &mut packfile_objects,
+        )?;
     }
     Ok(packfile_objects)
 }
 
+// adds the tree and everything under it (sub-trees and blobs) that isn't already in repeated_hashes
+fn push_tree_objects(
+    tree_hash: GitHash,
+    repeated_hashes: &mut HashSet<GitHash>,
+    path_objects: &std::path::Path,
+    packfile_objects: &mut Vec<(ObjectType, GitHash, Vec<u8>)>,
+) -> Result<(), ErrorType> {
+    let mut pending = vec![tree_hash];
+    while let Some(hash) = pending.pop() {
+        if !repeated_hashes.insert(hash.clone()) {
+            continue;
+        }
+        let (object_type, content) = GitObject::read_object(&hash, path_objects)?;
+        if object_type == ObjectType::Tree {
+            pending.extend(tree_entry_names(&content).into_iter().map(|(h, _)| h));
+        }
+        packfile_objects.push((object_type, hash, content));
+    }
+    Ok(())
+}
+
 /////////////   UTILS   /////////////
 
 type ReferencesHashMap = HashMap<String, GitHash>;
@@ -564,20 +596,21 @@ pub fn parse_ref_line(ref_line: String) -> Result<(String, String, GitHash), Err
         let ref_type;
 
         if ref_parts.len() == 1 && ref_parts[0] == "HEAD" {
-            ref_name = "HEAD";
+            ref_name = "HEAD".to_string();
             ref_type = "heads";
-        } else if ref_parts.len() != 3 || ref_parts.first() != Some(&"refs") {
+        } else if ref_parts.len() < 3 || ref_parts.first() != Some(&"refs") {
             return Err(ErrorType::ProtocolError(format!(
                 "invalid ref format '{}'",
                 ref_parts.join("/")
             )));
         } else {
+            // refs/heads/feature/login -> feature/login
             ref_type = ref_parts[1];
-            ref_name = ref_parts[2];
+            ref_name = ref_parts[2..].join("/");
         }
 
         let hash = GitHash::new(hash)?;
-        return Ok((ref_type.to_string(), ref_name.to_string(), hash));
+        return Ok((ref_type.to_string(), ref_name, hash));
     }
     Err(ErrorType::ProtocolError(format!(
         "invalid ref line '{}'",
@@ -0,0 +1,335 @@
+use crate::git_errors::errors::ErrorType;
+use std::collections::HashMap;
+
+////////////////////////////////////////////////////////////////////////////////////////
+// DELTA
+////////////////////////////////////////////////////////////////////////////////////////
+
+// external docs:
+// https://git-scm.com/docs/pack-format#_deltified_representation
+
+// delta data
+//     varint -> size of the base object
+//     varint -> size of the resulting object
+//     instructions:
+//         1xxxxxxx -> copy from base. the x bits tell which offset (4) and size (3) bytes follow
+//         0xxxxxxx -> insert the next x bytes of the delta (x can't be 0)
+
+const COPY_INSTRUCTION: u8 = 0b10000000;
+const DEFAULT_COPY_SIZE: usize = 0x10000;
+const MAX_COPY_SIZE: usize = 0xffffff;
+const MAX_INSERT_SIZE: usize = 0b01111111;
+const BLOCK_SIZE: usize = 16;
+// the sizes in the header come from the network, so at most this much is reserved up front
+const MAX_PREALLOCATED_SIZE: usize = 1 << 20;
+
+/// Given the content of a base object and the delta data of a deltified packfile entry
+/// it applies the copy/insert instructions and returns the content of the resulting object
+pub fn apply_delta(base: &[u8], delta: &[u8]) -> Result<Vec<u8>, ErrorType> {
+    let mut pos = 0;
+    let base_size = read_delta_size(delta, &mut pos)?;
+    if base_size != base.len() {
+        return Err(corrupt_delta(format!(
+            "base object size ({}) doesn't match the size expected by the delta ({base_size})",
+            base.len()
+        )));
+    }
+    let result_size = read_delta_size(delta, &mut pos)?;
+    let mut result = Vec::with_capacity(result_size.min(MAX_PREALLOCATED_SIZE));
+
+    while pos < delta.len() {
+        let instruction = delta[pos];
+        pos += 1;
+
+        if instruction & COPY_INSTRUCTION != 0 {
+            let mut offset: usize = 0;
+            for i in 0..4 {
+                if instruction & (1 << i) != 0 {
+                    offset |= (next_byte(delta, &mut pos)? as usize) << (8 * i);
+                }
+            }
+            let mut size: usize = 0;
+            for i in 0..3 {
+                if instruction & (1 << (4 + i)) != 0 {
+                    size |= (next_byte(delta, &mut pos)? as usize) << (8 * i);
+                }
+            }
+            if size == 0 {
+                size = DEFAULT_COPY_SIZE;
+            }
+            let chunk = offset
+                .checked_add(size)
+                .and_then(|end| base.get(offset..end))
+                .ok_or(corrupt_delta(format!(
+                    "copy instruction out of bounds (offset {offset}, size {size})"
+                )))?;
+            result.extend_from_slice(chunk);
+        } else if instruction != 0 {
+            let size = instruction as usize;
+            let chunk = delta.get(pos..pos + size).ok_or(corrupt_delta(
+                "insert instruction out of bounds".to_string(),
+            ))?;
+            result.extend_from_slice(chunk);
+            pos += size;
+        } else {
+            return Err(corrupt_delta("reserved instruction 0".to_string()));
+        }
+    }
+
+    if result.len() != result_size {
+        return Err(corrupt_delta(format!(
+            "resulting object size ({}) doesn't match the expected size ({result_size})",
+            result.len()
+        )));
+    }
+    Ok(result)
+}
+
+/// Given a base object and a target object it returns the delta data that rebuilds the target
+/// from the base, or None if the delta wouldn't be at least half the size of the target.
+/// The base is indexed in blocks of 16 bytes and every match found in the target is extended
+/// in both directions as much as possible.
+pub fn create_delta(base: &[u8], target: &[u8]) -> Option<Vec<u8>> {
+    if base.len() < BLOCK_SIZE || target.len() < BLOCK_SIZE {
+        return None;
+    }
+    let max_size = target.len() / 2;
+
+    let mut blocks: HashMap<&[u8], usize> = HashMap::new();
+    for (i, block) in base.chunks_exact(BLOCK_SIZE).enumerate() {
+        blocks.entry(block).or_insert(i * BLOCK_SIZE);
+    }
+
+    let mut delta = Vec::new();
+    write_delta_size(&mut delta, base.len());
+    write_delta_size(&mut delta, target.len());
+
+    let mut insert: Vec<u8> = Vec::new();
+    let mut i = 0;
+    while i < target.len() {
+        let base_offset = match target
+            .get(i..i + BLOCK_SIZE)
+            .and_then(|block| blocks.get(block))
+        {
+            Some(offset) => *offset,
+            None => {
+                insert.push(target[i]);
+                i += 1;
+                continue;
+            }
+        };
+
+        let mut start = base_offset;
+        while start > 0 && insert.last() == Some(&base[start - 1]) {
+            insert.pop();
+            start -= 1;
+        }
+        let backwards = base_offset - start;
+
+        let mut length = BLOCK_SIZE + backwards;
+        while start + length < base.len()
+            && i + length - backwards < target.len()
+            && base[start + length] == target[i + length - backwards]
+        {
+            length += 1;
+        }
+
+        write_insert(&mut delta, &insert);
+        insert.clear();
+        write_copy(&mut delta, start, length);
+        i += length - backwards;
+
+        if delta.len() > max_size {
+            return None;
+        }
+    }
+    write_insert(&mut delta, &insert);
+
+    match delta.len() > max_size {
+        true => None,
+        false => Some(delta),
+    }
+}
+
+fn write_insert(delta: &mut Vec<u8>, data: &[u8]) {
+    for chunk in data.chunks(MAX_INSERT_SIZE) {
+        delta.push(chunk.len() as u8);
+        delta.extend_from_slice(chunk);
+    }
+}
+
+fn write_copy(delta: &mut Vec<u8>, mut offset: usize, mut length: usize) {
+    while length > 0 {
+        let size = length.min(MAX_COPY_SIZE);
+        let mut instruction = COPY_INSTRUCTION;
+        let mut bytes = Vec::new();
+        for i in 0..4 {
+            let byte = (offset >> (8 * i)) as u8;
+            if byte != 0 {
+                instruction |= 1 << i;
+                bytes.push(byte);
+            }
+        }
+        for i in 0..3 {
+            let byte = (size >> (8 * i)) as u8;
+            if byte != 0 {
+                instruction |= 1 << (4 + i);
+                bytes.push(byte);
+            }
+        }
+        delta.push(instruction);
+        delta.extend(bytes);
+        offset += size;
+        length -= size;
+    }
+}
+
+fn write_delta_size(delta: &mut Vec<u8>, mut size: usize) {
+    loop {
+        let byte = (size & 0b01111111) as u8;
+        size >>= 7;
+        if size == 0 {
+            delta.push(byte);
+            return;
+        }
+        delta.push(byte | 0b10000000);
+    }
+}
+
+// sizes in the delta header are little endian base 128 varints
+fn read_delta_size(delta: &[u8], pos: &mut usize) -> Result<usize, ErrorType> {
+    let mut size: usize = 0;
+    let mut shift = 0;
+    loop {
+        if shift >= usize::BITS {
+            return Err(corrupt_delta(
+                "object size in the header is too big".to_string(),
+            ));
+        }
+        let byte = next_byte(delta, pos)?;
+        size |= ((byte & 0b01111111) as usize) << shift;
+        shift += 7;
+        if byte & 0b10000000 == 0 {
+            return Ok(size);
+        }
+    }
+}
+
+fn next_byte(delta: &[u8], pos: &mut usize) -> Result<u8, ErrorType> {
+    let byte = delta
+        .get(*pos)
+        .ok_or(corrupt_delta("unexpected end of delta data".to_string()))?;
+    *pos += 1;
+    Ok(*byte)
+}
+
+fn corrupt_delta(reason: String) -> ErrorType {
+    ErrorType::ProtocolError(format!("corrupt packfile delta: {reason}"))
+}
+
+#[cfg(test)]
+mod tests_apply_delta {
+    use super::*;
+
+    #[test]
+    fn insert_only() -> Result<(), ErrorType> {
+        let base = b"abc";
+        let delta = [3, 5, 5, b'h', b'e', b'l', b'l', b'o'];
+        assert_eq!(apply_delta(base, &delta)?, b"hello");
+        Ok(())
+    }
+
+    #[test]
+    fn copy_and_insert() -> Result<(), ErrorType> {
+        let base = b"hello world";
+        // copy offset 0 size 6 ("hello "), insert "rust"
+        let delta = [11, 10, 0b10010000, 6, 4, b'r', b'u', b's', b't'];
+        assert_eq!(apply_delta(base, &delta)?, b"hello rust");
+        Ok(())
+    }
+
+    #[test]
+    fn copy_with_offset() -> Result<(), ErrorType> {
+        let base = b"hello world";
+        // copy offset 6 size 5 ("world")
+        let delta = [11, 5, 0b10010001, 6, 5];
+        assert_eq!(apply_delta(base, &delta)?, b"world");
+        Ok(())
+    }
+
+    #[test]
+    fn wrong_base_size() {
+        let delta = [4, 1, 1, b'a'];
+        assert!(apply_delta(b"abc", &delta).is_err());
+    }
+
+    #[test]
+    fn copy_out_of_bounds() {
+        let delta = [3, 5, 0b10010000, 5];
+        assert!(apply_delta(b"abc", &delta).is_err());
+    }
+
+    #[test]
+    fn reserved_instruction() {
+        let delta = [3, 0, 0];
+        assert!(apply_delta(b"abc", &delta).is_err());
+    }
+}
+
+#[cfg(test)]
+mod tests_create_delta {
+    use super::*;
+
+    #[test]
+    fn small_change_roundtrip() -> Result<(), ErrorType> {
+        let base = "line one\nline two\nline three\nline four\nline five\n".repeat(10);
+        let target = base.replace("line three", "line 3");
+
+        let delta = create_delta(base.as_bytes(), target.as_bytes()).ok_or(
+            ErrorType::ProtocolError("delta should have been created".to_string()),
+        )?;
+
+        assert!(delta.len() < target.len() / 2);
+        assert_eq!(apply_delta(base.as_bytes(), &delta)?, target.as_bytes());
+        Ok(())
+    }
+
+    #[test]
+    fn appended_content_roundtrip() -> Result<(), ErrorType> {
+        let base: Vec<u8> = (0..=255).cycle().take(4000).collect();
+        let mut target = b"header".to_vec();
+        target.extend(&base);
+        target.extend(b"footer");
+
+        let delta = create_delta(&base, &target).ok_or(ErrorType::ProtocolError(
+            "delta should have been created".to_string(),
+        ))?;
+
+        assert_eq!(apply_delta(&base, &delta)?, target);
+        Ok(())
+    }
+
+    #[test]
+    fn unrelated_content_is_not_deltified() {
+        let base = "a".repeat(100);
+        let target = "b".repeat(100);
+        assert!(create_delta(base.as_bytes(), target.as_bytes()).is_none());
+    }
+
+    #[test]
+    fn delta_size_encoding() -> Result<(), ErrorType> {
+        let mut bytes = Vec::new();
+        write_delta_size(&mut bytes, 300);
+        let mut pos = 0;
+        assert_eq!(read_delta_size(&bytes, &mut pos)?, 300);
+        assert_eq!(pos, bytes.len());
+        Ok(())
+    }
+
+    #[test]
+    fn oversized_delta_size_is_corrupt() {
+        let delta = [0xff; 11];
+        assert!(read_delta_size(&delta, &mut 0).is_err());
+        assert!(apply_delta(b"", &delta).is_err());
+    }
+}
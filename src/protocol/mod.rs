@@ -1,2 +1,4 @@
+pub mod delta;
 pub mod pack_file;
+pub mod pack_index;
 pub mod pkt_line;
@@ -1,12 +1,22 @@
+use super::delta;
+use super::pack_index;
 use crate::compressor::Compressor;
 use crate::files::object_type::ObjectType;
 use crate::git_errors::errors::ErrorType;
+use crate::git_object::GitObject;
 use crate::hash::GitHash;
 use flate2::bufread::ZlibDecoder;
-use std::io::{self, BufRead, Cursor, Read, Write};
+use sha1::{Digest, Sha1};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Cursor, Read, Seek, SeekFrom, Write};
+use std::path::Path;
 
 const PACKFILE_HEADER: [u8; 4] = [80, 65, 67, 75]; //PACK
 const PACKFILE_VERSION: [u8; 4] = [0, 0, 0, 2]; // 2
+const OBJ_OFS_DELTA: u8 = 6;
+const OBJ_REF_DELTA: u8 = 7;
+const GITLINK_MODE: &[u8] = b"160000";
 
 ////////////////////////////////////////////////////////////////////////////////////////
 // PACK FILE
@@ -36,34 +46,52 @@ pub fn read_bytes<R: Read, const N: usize>(stream: &mut R) -> io::Result<[u8; N]
 
 //////////////// CREATE ////////////////
 
-pub fn send_packfile(
-    stream: &mut impl Write,
-    packfile_objects: Vec<(ObjectType, GitHash, Vec<u8>)>,
-) -> Result<(), ErrorType> {
-    let mut packfile = Vec::new();
+const DEFAULT_WINDOW: usize = 10;
+const DEFAULT_DEPTH: usize = 50;
+
+/// How hard build_packfile tries to delta-compress objects.
+/// window: how many of the previous similar objects are tried as delta base for each object.
+/// depth: maximum length of a delta chain (a delta whose base is also a delta, and so on).
+/// A window of 0 disables deltas.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PackOptions {
+    window: usize,
+    depth: usize,
+}
 
-    let header: Vec<u8> = [PACKFILE_HEADER, PACKFILE_VERSION].concat(); //header
-    stream.write_all(&header)?;
-    packfile.extend_from_slice(&header);
+impl PackOptions {
+    pub fn new(window: usize, depth: usize) -> Self {
+        Self { window, depth }
+    }
 
-    let object_ammount: [u8; 4] = usize_to_bytes(packfile_objects.len())?;
-    stream.write_all(&object_ammount)?;
-    packfile.extend_from_slice(&object_ammount);
+    pub fn get_window(&self) -> usize {
+        self.window
+    }
 
-    for object in packfile_objects {
-        let object_entry = build_packfile_object_entry(object)?;
-        stream.write_all(&object_entry)?;
-        packfile.extend_from_slice(&object_entry);
+    pub fn get_depth(&self) -> usize {
+        self.depth
     }
+}
 
-    let checksum = GitHash::hash_sha1(&packfile);
-    stream.write_all(&checksum.to_hex()?)?;
+impl Default for PackOptions {
+    fn default() -> Self {
+        Self::new(DEFAULT_WINDOW, DEFAULT_DEPTH)
+    }
+}
 
+pub fn send_packfile(
+    stream: &mut impl Write,
+    packfile_objects: Vec<(ObjectType, GitHash, Vec<u8>)>,
+    options: PackOptions,
+) -> Result<(), ErrorType> {
+    let packfile = build_packfile(packfile_objects, options)?;
+    stream.write_all(&packfile)?;
     Ok(())
 }
 
 pub fn build_packfile(
     packfile_objects: Vec<(ObjectType, GitHash, Vec<u8>)>,
+    options: PackOptions,
 ) -> Result<Vec<u8>, ErrorType> {
     let mut packfile: Vec<u8> = [PACKFILE_HEADER, PACKFILE_VERSION].concat(); //header
 
@@ -71,8 +99,20 @@ pub fn build_packfile(
 
     packfile.write_all(&object_ammount)?;
 
-    for object in packfile_objects {
-        let object_entry = build_packfile_object_entry(object)?;
+    let packfile_objects = sort_for_deltas(packfile_objects);
+    let deltas = find_deltas(&packfile_objects, options);
+    let mut offsets = Vec::new();
+
+    for (object, delta) in packfile_objects.into_iter().zip(deltas) {
+        let offset = packfile.len();
+        let object_entry = match delta {
+            Some((base_index, delta)) => {
+                let base_offset: usize = offsets[base_index];
+                build_packfile_delta_entry(offset - base_offset, delta)?
+            }
+            None => build_packfile_object_entry(object)?,
+        };
+        offsets.push(offset);
         packfile.write_all(&object_entry)?;
     }
 
@@ -82,6 +122,87 @@ pub fn build_packfile(
     Ok(packfile)
 }
 
+// Objects that are likely to be similar must end up close to each other so they fall in the same
+// window: they are grouped by type, then by file name (so earlier versions of the same path are
+// together) and then by size, bigger first, because deleting is cheaper than inserting in a delta.
+// File names are taken from the trees included in the same packfile.
+fn sort_for_deltas(
+    mut packfile_objects: Vec<(ObjectType, GitHash, Vec<u8>)>,
+) -> Vec<(ObjectType, GitHash, Vec<u8>)> {
+    let mut names: HashMap<GitHash, String> = HashMap::new();
+    for (object_type, _, content) in &packfile_objects {
+        if *object_type == ObjectType::Tree {
+            names.extend(tree_entry_names(content));
+        }
+    }
+    packfile_objects.sort_by(|(type_a, hash_a, content_a), (type_b, hash_b, content_b)| {
+        type_a
+            .to_string()
+            .cmp(&type_b.to_string())
+            .then_with(|| names.get(hash_a).cmp(&names.get(hash_b)))
+            .then_with(|| content_b.len().cmp(&content_a.len()))
+    });
+    packfile_objects
+}
+
+// [mode name\0hash_en_binario] -> (hash, name)
+// submodule entries (gitlinks) are skipped, they point to commits of another repository
+pub(crate) fn tree_entry_names(content: &[u8]) -> Vec<(GitHash, String)> {
+    let mut names = Vec::new();
+    let mut rest = content;
+    while let Some(space) = rest.iter().position(|&b| b == b' ') {
+        let is_gitlink = &rest[..space] == GITLINK_MODE;
+        let after_mode = &rest[space + 1..];
+        let Some(null) = after_mode.iter().position(|&b| b == b'\0') else {
+            break;
+        };
+        let Some(hash_bytes) = after_mode.get(null + 1..null + 21) else {
+            break;
+        };
+        if let (false, Ok(hash)) = (is_gitlink, GitHash::from_hex(hash_bytes)) {
+            let name = String::from_utf8_lossy(&after_mode[..null]).to_string();
+            names.push((hash, name));
+        }
+        rest = &after_mode[null + 21..];
+    }
+    names
+}
+
+// For each object tries the previous ones inside the window as delta base and keeps the smallest
+// delta, as long as the base doesn't already have a delta chain as long as the max depth.
+// Returns, for each object, the index of its base and the delta data, or None if stored whole.
+fn find_deltas(
+    packfile_objects: &[(ObjectType, GitHash, Vec<u8>)],
+    options: PackOptions,
+) -> Vec<Option<(usize, Vec<u8>)>> {
+    let mut deltas: Vec<Option<(usize, Vec<u8>)>> = Vec::new();
+    let mut depths: Vec<usize> = Vec::new();
+
+    for (i, (object_type, _, content)) in packfile_objects.iter().enumerate() {
+        let mut best: Option<(usize, Vec<u8>)> = None;
+        for base_index in i.saturating_sub(options.window)..i {
+            let (base_type, _, base_content) = &packfile_objects[base_index];
+            if base_type != object_type || depths[base_index] >= options.depth {
+                continue;
+            }
+            if let Some(delta) = delta::create_delta(base_content, content) {
+                best = match best {
+                    Some((best_index, best_delta)) if best_delta.len() <= delta.len() => {
+                        Some((best_index, best_delta))
+                    }
+                    _ => Some((base_index, delta)),
+                };
+            }
+        }
+        depths.push(match &best {
+            Some((base_index, _)) => depths[*base_index] + 1,
+            None => 0,
+        });
+        deltas.push(best);
+    }
+    deltas
+}
+
 fn usize_to_bytes(object_ammount: usize) -> Result<[u8; 4], ErrorType> {
     if object_ammount > u32::MAX as usize {
         return Err(ErrorType::ProtocolError(format!(
@@ -102,7 +223,7 @@ fn build_packfile_object_entry(
         ObjectType::Commit => 1,
         ObjectType::Tree => 2,
         ObjectType::Blob => 3,
-        // object_type::ObjectType::Tag => 64
+        ObjectType::Tag => 4,
     };
 
     let mut result = generate_packfile_object_header(type_byte, content.len())?; // header (type and size)
@@ -114,6 +235,26 @@ fn build_packfile_object_entry(
     Ok(result)
 }
 
+fn build_packfile_delta_entry(base_distance: usize, delta: Vec<u8>) -> Result<Vec<u8>, ErrorType> {
+    let mut result = generate_packfile_object_header(OBJ_OFS_DELTA, delta.len())?;
+    result.write_all(&encode_base_distance(base_distance))?;
+    result.write_all(&Compressor::compress(delta)?)?;
+    Ok(result)
+}
+
+// inverse of read_base_distance
+fn encode_base_distance(mut distance: usize) -> Vec<u8> {
+    let mut bytes = vec![(distance & 0b01111111) as u8];
+    distance >>= 7;
+    while distance > 0 {
+        distance -= 1;
+        bytes.push(0b10000000 | (distance & 0b01111111) as u8);
+        distance >>= 7;
+    }
+    bytes.reverse();
+    bytes
+}
+
 fn generate_packfile_object_header(typ: u8, size: usize) -> Result<Vec<u8>, ErrorType> {
     if typ > 7 {
         Err(ErrorType::ProtocolError(format!(
@@ -173,19 +314,98 @@ fn generate_packfile_object_header(typ: u8, size: usize) -> Result<Vec<u8>, Erro
 //     Ok(result)
 // }
 
-pub fn read_packfile<R: BufRead>(stream: &mut R) -> Result<Vec<(ObjectType, Vec<u8>)>, ErrorType> {
-    let mut full_content = Vec::new();
+pub fn read_packfile<R: BufRead>(
+    stream: &mut R,
+    path_objects: &Path,
+) -> Result<Vec<(ObjectType, Vec<u8>)>, ErrorType> {
+    let pack = parse_packfile(stream, path_objects)?;
+    Ok(pack
+        .objects
+        .into_iter()
+        .map(|(_, object_type, content)| (object_type, content))
+        .collect())
+}
+
+/// Reads a packfile from the stream and keeps it in objects/pack (with its .idx) instead of
+/// writing a loose file for every object. Returns the amount of objects received.
+pub fn save_packfile<R: BufRead>(stream: &mut R, path_objects: &Path) -> Result<usize, ErrorType> {
+    let mut pack = parse_packfile(stream, path_objects)?;
+    let object_amount = pack.objects.len();
+    if object_amount == 0 {
+        return Ok(0);
+    }
+    if pack.thin {
+        // deltas against local objects can't be read from the packfile alone, it's rebuilt whole
+        let objects = pack
+            .objects
+            .into_iter()
+            .map(|(_, object_type, content)| {
+                (
+                    object_type,
+                    GitHash::hash_object(&content, object_type),
+                    content,
+                )
+            })
+            .collect();
+        let packfile = build_packfile(objects, PackOptions::default())?;
+        pack = parse_packfile(&mut packfile.as_slice(), path_objects)?;
+    }
+    let entries = pack
+        .objects
+        .iter()
+        .map(|(offset, object_type, content)| {
+            (GitHash::hash_object(content, *object_type), *offset)
+        })
+        .collect();
+    pack_index::save_pack(&pack.raw, entries, path_objects)?;
+    Ok(object_amount)
+}
+
+/// Reads the object whose entry starts at offset in a stored packfile, applying its deltas
+pub(crate) fn read_packed_object(
+    path_pack: &Path,
+    offset: usize,
+    path_objects: &Path,
+) -> Result<(ObjectType, Vec<u8>), ErrorType> {
+    let mut file = File::open(path_pack)?;
+    file.seek(SeekFrom::Start(offset as u64))?;
+    let mut reader = BufReader::new(file);
+    let mut stream = PackStream::new(&mut reader);
+    stream.position = offset;
+
+    let (base, delta) = match read_pack_object(&mut stream, offset)? {
+        PackEntry::Base(object_type, content) => return Ok((object_type, content)),
+        PackEntry::Delta(base, delta) => (base, delta),
+    };
+    let (object_type, base_content) = match base {
+        DeltaBase::Offset(base_offset) => read_packed_object(path_pack, base_offset, path_objects)?,
+        DeltaBase::Hash(hash) => GitObject::read_object(&hash, path_objects)?,
+    };
+    Ok((object_type, delta::apply_delta(&base_content, &delta)?))
+}
 
-    let header: [u8; 4] = read_bytes(stream)?;
+/// A packfile already read from the stream and with all its deltas resolved
+struct ParsedPack {
+    raw: Vec<u8>,                               // the packfile as it was received
+    objects: Vec<(usize, ObjectType, Vec<u8>)>, // offset, type and content of each entry
+    thin: bool,                                 // some delta bases are local objects
+}
+
+fn parse_packfile<R: BufRead>(
+    stream: &mut R,
+    path_objects: &Path,
+) -> Result<ParsedPack, ErrorType> {
+    let mut stream = PackStream::new(stream);
+
+    let header: [u8; 4] = read_bytes(&mut stream)?;
     if header != PACKFILE_HEADER {
         return Err(ErrorType::ProtocolError(format!(
             "invalid packfile header {:?}, expected {:?}  (PACK)",
             header, PACKFILE_HEADER
         )));
     }
-    full_content.extend_from_slice(&header);
 
-    let version: [u8; 4] = read_bytes(stream)?;
+    let version: [u8; 4] = read_bytes(&mut stream)?;
 
     if version != PACKFILE_VERSION {
         return Err(ErrorType::ProtocolError(format!(
@@ -193,52 +413,43 @@ pub fn read_packfile<R: BufRead>(stream: &mut R) -> Result<Vec<(ObjectType, Vec<
             version, PACKFILE_VERSION
         )));
     }
-    full_content.extend_from_slice(&version);
 
-    let object_ammount_bytes: [u8; 4] = read_bytes(stream)?;
+    let object_ammount_bytes: [u8; 4] = read_bytes(&mut stream)?;
     let object_amount = u32::from_be_bytes(object_ammount_bytes);
 
-    full_content.extend_from_slice(&object_ammount_bytes);
-
-    let mut packfile_objects = Vec::new();
+    let mut pack_entries = Vec::new();
 
     for _ in 0..object_amount {
-        let (object_type, uncompressed_content, compressed_content) = read_pack_object(stream)?;
-        full_content.extend_from_slice(&compressed_content);
-        packfile_objects.push((object_type, uncompressed_content));
+        let offset = stream.position();
+        let entry = read_pack_object(&mut stream, offset)?;
+        pack_entries.push((offset, entry));
     }
 
-    let checksum: [u8; 20] = read_bytes(stream)?;
-
-    let _checksum = GitHash::from_hex(&checksum)?;
-    let _hash = GitHash::hash_sha1(&full_content);
+    // the checksum covers every byte read so far, but not itself
+    let hash = stream.hash()?;
+    let checksum: [u8; 20] = read_bytes(&mut stream)?;
+    let checksum = GitHash::from_hex(&checksum)?;
 
-    //TODO :  VERIFY CHECKSUM (posible solution below)
-
-    // this failed because aparently uncompressing and then recompressing with zlib might not be deterministic
-    // (a few bytes are changed. tested by calling read_to_end instead and then parsing)
-    // possible solution below
-    // if checksum != hash {
-    //     return Err(ErrorType::ProtocolError(format!("packfile checksum ({checksum}) didn't match its content checksum ({hash})")))
-    // }
+    if checksum != hash {
+        return Err(ErrorType::ProtocolError(format!(
+            "packfile checksum ({checksum}) didn't match its content checksum ({hash})"
+        )));
+    }
 
-    Ok(packfile_objects)
+    let offsets: Vec<usize> = pack_entries.iter().map(|(offset, _)| *offset).collect();
+    let (objects, thin) = resolve_deltas(pack_entries, path_objects)?;
+
+    Ok(ParsedPack {
+        raw: stream.raw,
+        objects: offsets
+            .into_iter()
+            .zip(objects)
+            .map(|(offset, (object_type, content))| (offset, object_type, content))
+            .collect(),
+        thin,
+    })
 }
 
-// solucion al checksum
-
-// entro a la funcion de leer objeto
-// leo con stream.take(tamanio descomprimido)
-//     guardo lo leido como bytes originales
-//     con lo leido descomprimo hasta llenar el tamanio descomprimido
-//         si no llena -> packfile corrupto
-//     si lee bien muy probablemente el take haya leido de más
-//     entonces tengo que guardar esos extras y ponerlos en la proxima lectura
-//     (afuera de esta funcion) antes de seguir leyendo del stream
-//
-// Entonces tengo que armar struct wrapper horrible que tenga el stream y
-// una cola y que lea de la cola si tiene elementos y sino del stream?
-
 fn _parse_packfile_header(reader: &mut Cursor<Vec<u8>>) -> Result<u32, ErrorType> {
     let header: [u8; 4] = read_bytes(reader)?;
     if header != PACKFILE_HEADER {
@@ -258,11 +469,80 @@ fn _parse_packfile_header(reader: &mut Cursor<Vec<u8>>) -> Result<u32, ErrorType
     Ok(u32::from_be_bytes(object_ammount_bytes))
 }
 
-type UncompressedObject = Vec<u8>;
-type CompressedBytes = Vec<u8>;
+/// Where to find the base object of a deltified entry
+enum DeltaBase {
+    Offset(usize), // OFS_DELTA: position of the base entry in this same packfile
+    Hash(GitHash), // REF_DELTA: hash of the base, in this packfile or in the local objects
+}
+
+/// A packfile entry as it is read from the stream, before applying deltas
+enum PackEntry {
+    Base(ObjectType, Vec<u8>),
+    Delta(DeltaBase, Vec<u8>),
+}
+
+/// Wrapper over the packfile stream that keeps track of how many bytes were consumed.
+/// It is needed to know the offset of each entry, which OFS_DELTA entries use to find their base.
+/// It also keeps the consumed bytes and their sha1, to verify and store the packfile as received.
+struct PackStream<'a, R: BufRead> {
+    inner: &'a mut R,
+    position: usize,
+    hasher: Sha1,
+    raw: Vec<u8>,
+}
+
+impl<'a, R: BufRead> PackStream<'a, R> {
+    fn new(inner: &'a mut R) -> Self {
+        Self {
+            inner,
+            position: 0,
+            hasher: Sha1::new(),
+            raw: Vec::new(),
+        }
+    }
+
+    fn position(&self) -> usize {
+        self.position
+    }
+
+    // sha1 of all the bytes consumed from the stream up to now
+    fn hash(&self) -> Result<GitHash, ErrorType> {
+        GitHash::from_hex(&self.hasher.clone().finalize())
+    }
+}
+
+impl<R: BufRead> Read for PackStream<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.hasher.update(&buf[..n]);
+        self.raw.extend_from_slice(&buf[..n]);
+        self.position += n;
+        Ok(n)
+    }
+}
+
+impl<R: BufRead> BufRead for PackStream<'_, R> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        self.inner.fill_buf()
+    }
+
+    // the zlib decoder reads through fill_buf/consume, so the consumed bytes are recorded here.
+    // consume always follows a fill_buf, which leaves those bytes buffered in the inner reader
+    fn consume(&mut self, amt: usize) {
+        if let Ok(buffer) = self.inner.fill_buf() {
+            let consumed = &buffer[..amt.min(buffer.len())];
+            self.hasher.update(consumed);
+            self.raw.extend_from_slice(consumed);
+        }
+        self.inner.consume(amt);
+        self.position += amt;
+    }
+}
+
 fn read_pack_object<R: BufRead>(
-    stream: &mut R,
-) -> Result<(ObjectType, UncompressedObject, CompressedBytes), ErrorType> {
+    stream: &mut PackStream<R>,
+    offset: usize,
+) -> Result<PackEntry, ErrorType> {
     let mut header_bytes = Vec::new();
     loop {
         let [byte] = read_bytes(stream)?;
@@ -272,15 +552,23 @@ fn read_pack_object<R: BufRead>(
         }
     }
 
-    let (object_type, size) = parse_object_size_and_type(header_bytes.clone())?;
-
-    let object_type = match object_type {
-        1 => ObjectType::Commit,
-        2 => ObjectType::Tree,
-        3 => ObjectType::Blob,
-        //   4 => Base(Tag),
-        //   6 => OffsetDelta,
-        //   7 => HashDelta,
+    let (object_type, size) = parse_object_size_and_type(header_bytes)?;
+
+    let delta_base = match object_type {
+        1..=4 => None,
+        OBJ_OFS_DELTA => {
+            let distance = read_base_distance(stream)?;
+            let base_offset = offset
+                .checked_sub(distance)
+                .ok_or(ErrorType::ProtocolError(format!(
+                    "corrupt packfile: delta at offset {offset} points before the packfile start"
+                )))?;
+            Some(DeltaBase::Offset(base_offset))
+        }
+        OBJ_REF_DELTA => {
+            let hash: [u8; 20] = read_bytes(stream)?;
+            Some(DeltaBase::Hash(GitHash::from_hex(&hash)?))
+        }
         _ => {
             return Err(ErrorType::RepositoryError(format!(
                 "Invalid object type: {}",
@@ -289,8 +577,9 @@ fn read_pack_object<R: BufRead>(
         }
     };
 
-    let mut uncompressed_object: Vec<u8> = vec![0; size];
-    ZlibDecoder::new(stream).read_exact(&mut uncompressed_object)?;
+    let mut uncompressed_object: Vec<u8> = Vec::with_capacity(size);
+    // read_to_end so the decoder also consumes the zlib trailer
+    ZlibDecoder::new(&mut *stream).read_to_end(&mut uncompressed_object)?;
 
     if uncompressed_object.len() != size {
         return Err(ErrorType::ProtocolError(
@@ -298,13 +587,114 @@ fn read_pack_object<R: BufRead>(
         ));
     }
 
-    let compressed_bytes = [
-        header_bytes,
-        Compressor::compress(uncompressed_object.clone())?,
-    ]
-    .concat();
+    let entry = match delta_base {
+        Some(base) => PackEntry::Delta(base, uncompressed_object),
+        None => {
+            let object_type = match object_type {
+                1 => ObjectType::Commit,
+                2 => ObjectType::Tree,
+                3 => ObjectType::Blob,
+                _ => ObjectType::Tag,
+            };
+            PackEntry::Base(object_type, uncompressed_object)
+        }
+    };
+
+    Ok(entry)
+}
 
-    Ok((object_type, uncompressed_object, compressed_bytes))
+// the distance to the base of an OFS_DELTA is a big endian base 128 number where
+// each continuation adds one, so that there aren't two encodings for the same value
+fn read_base_distance<R: Read>(stream: &mut R) -> Result<usize, ErrorType> {
+    let [mut byte] = read_bytes(stream)?;
+    let mut distance = (byte & 0b01111111) as usize;
+    while byte & 0b10000000 != 0 {
+        [byte] = read_bytes(stream)?;
+        distance = ((distance + 1) << 7) | (byte & 0b01111111) as usize;
+    }
+    Ok(distance)
+}
+
+type Objects = Vec<(ObjectType, Vec<u8>)>;
+
+/// Applies every delta entry over its base until all the packfile objects are whole.
+/// Bases can be other entries of the packfile (even deltified ones) or objects already
+/// stored in the local repository (REF_DELTA against a "thin" packfile).
+/// Also returns whether some base was a local object.
+fn resolve_deltas(
+    pack_entries: Vec<(usize, PackEntry)>,
+    path_objects: &Path,
+) -> Result<(Objects, bool), ErrorType> {
+    let mut thin = false;
+    let mut resolved: Vec<Option<(ObjectType, Vec<u8>)>> = Vec::new();
+    let mut by_offset: HashMap<usize, usize> = HashMap::new();
+    let mut by_hash: HashMap<GitHash, usize> = HashMap::new();
+    let mut pending = Vec::new();
+
+    for (i, (offset, entry)) in pack_entries.into_iter().enumerate() {
+        by_offset.insert(offset, i);
+        match entry {
+            PackEntry::Base(object_type, content) => {
+                by_hash.insert(GitHash::hash_object(&content, object_type), i);
+                resolved.push(Some((object_type, content)));
+            }
+            PackEntry::Delta(base, delta) => {
+                resolved.push(None);
+                pending.push((i, base, delta));
+            }
+        }
+    }
+
+    while !pending.is_empty() {
+        let pending_amount = pending.len();
+        let mut still_pending = Vec::new();
+
+        for (i, base, delta) in pending {
+            let local_base;
+            let base_object = match &base {
+                DeltaBase::Offset(base_offset) => {
+                    let base_index =
+                        by_offset
+                            .get(base_offset)
+                            .ok_or(ErrorType::ProtocolError(format!(
+                                "corrupt packfile: no entry at delta base offset {base_offset}"
+                            )))?;
+                    resolved[*base_index].as_ref()
+                }
+                DeltaBase::Hash(hash) => match by_hash.get(hash) {
+                    Some(base_index) => resolved[*base_index].as_ref(),
+                    None if GitObject::contains(hash, path_objects) => {
+                        thin = true;
+                        local_base = GitObject::read_object(hash, path_objects)?;
+                        Some(&local_base)
+                    }
+                    None => None,
+                },
+            };
+
+            let (object_type, content) = match base_object {
+                Some((object_type, base_content)) => {
+                    (*object_type, delta::apply_delta(base_content, &delta)?)
+                }
+                None => {
+                    // the base may be a delta that wasn't resolved yet
+                    still_pending.push((i, base, delta));
+                    continue;
+                }
+            };
+            by_hash.insert(GitHash::hash_object(&content, object_type), i);
+            resolved[i] = Some((object_type, content));
+        }
+
+        if still_pending.len() == pending_amount {
+            return Err(ErrorType::ProtocolError(format!(
+                "corrupt packfile: couldn't find the base object of {pending_amount} deltified entries"
+            )));
+        }
+        pending = still_pending;
+    }
+
+    Ok((resolved.into_iter().flatten().collect(), thin))
 }
 
 fn parse_object_size_and_type(mut object_header_bytes: Vec<u8>) -> Result<(u8, usize), ErrorType> {
@@ -427,3 +817,234 @@ mod tests_generate_packfile_object_header {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests_read_packfile {
+    use super::*;
+
+    // packfile entry for an already deltified object, the delta base reference goes right after the header
+    fn delta_entry(typ: u8, base_reference: &[u8], delta: &[u8]) -> Result<Vec<u8>, ErrorType> {
+        let mut entry = generate_packfile_object_header(typ, delta.len())?;
+        entry.extend_from_slice(base_reference);
+        entry.extend(Compressor::compress(delta.to_vec())?);
+        Ok(entry)
+    }
+
+    fn packfile_from_entries(entries: Vec<Vec<u8>>) -> Result<Vec<u8>, ErrorType> {
+        let mut packfile: Vec<u8> = [PACKFILE_HEADER, PACKFILE_VERSION].concat();
+        packfile.extend(usize_to_bytes(entries.len())?);
+        for entry in entries {
+            packfile.extend(entry);
+        }
+        let checksum = GitHash::hash_sha1(&packfile);
+        packfile.extend(checksum.to_hex()?);
+        Ok(packfile)
+    }
+
+    #[test]
+    fn whole_objects() -> Result<(), ErrorType> {
+        let objects = vec![
+            (
+                ObjectType::Blob,
+                GitHash::hash_blob(&b"one".to_vec()),
+                b"one".to_vec(),
+            ),
+            (
+                ObjectType::Blob,
+                GitHash::hash_blob(&b"two".to_vec()),
+                b"two".to_vec(),
+            ),
+        ];
+        let packfile = build_packfile(objects, PackOptions::default())?;
+
+        let result = read_packfile(&mut packfile.as_slice(), Path::new("does/not/exist"))?;
+
+        assert_eq!(
+            result,
+            vec![
+                (ObjectType::Blob, b"one".to_vec()),
+                (ObjectType::Blob, b"two".to_vec())
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn ofs_delta() -> Result<(), ErrorType> {
+        let base = build_packfile_object_entry((
+            ObjectType::Blob,
+            GitHash::hash_blob(&b"hello world".to_vec()),
+            b"hello world".to_vec(),
+        ))?;
+        // copy "hello " from base and insert "rust"
+        let delta = [11, 10, 0b10010000, 6, 4, b'r', b'u', b's', b't'];
+        let distance = base.len() as u8; // base starts right after the 12 bytes header
+        let delta = delta_entry(OBJ_OFS_DELTA, &[distance], &delta)?;
+        let packfile = packfile_from_entries(vec![base, delta])?;
+
+        let result = read_packfile(&mut packfile.as_slice(), Path::new("does/not/exist"))?;
+
+        assert_eq!(result[1], (ObjectType::Blob, b"hello rust".to_vec()));
+        Ok(())
+    }
+
+    #[test]
+    fn ref_delta_before_its_base() -> Result<(), ErrorType> {
+        let base_hash = GitHash::hash_blob(&b"hello world".to_vec());
+        let delta = [11, 5, 0b10010001, 6, 5];
+        let delta = delta_entry(OBJ_REF_DELTA, &base_hash.to_hex()?, &delta)?;
+        let base =
+            build_packfile_object_entry((ObjectType::Blob, base_hash, b"hello world".to_vec()))?;
+        let packfile = packfile_from_entries(vec![delta, base])?;
+
+        let result = read_packfile(&mut packfile.as_slice(), Path::new("does/not/exist"))?;
+
+        assert!(result.contains(&(ObjectType::Blob, b"world".to_vec())));
+        assert!(result.contains(&(ObjectType::Blob, b"hello world".to_vec())));
+        Ok(())
+    }
+
+    #[test]
+    fn ref_delta_missing_base() -> Result<(), ErrorType> {
+        let base_hash = GitHash::hash_blob(&b"hello world".to_vec());
+        let delta = delta_entry(
+            OBJ_REF_DELTA,
+            &base_hash.to_hex()?,
+            &[11, 5, 0b10010001, 6, 5],
+        )?;
+        let packfile = packfile_from_entries(vec![delta])?;
+
+        assert!(read_packfile(&mut packfile.as_slice(), Path::new("does/not/exist")).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn corrupt_checksum() -> Result<(), ErrorType> {
+        let objects = vec![(
+            ObjectType::Blob,
+            GitHash::hash_blob(&b"one".to_vec()),
+            b"one".to_vec(),
+        )];
+        let mut packfile = build_packfile(objects, PackOptions::default())?;
+        let last = packfile.len() - 1;
+        packfile[last] ^= 0xff;
+
+        let result = read_packfile(&mut packfile.as_slice(), Path::new("does/not/exist"));
+
+        assert!(matches!(result, Err(ErrorType::ProtocolError(_))));
+        Ok(())
+    }
+
+    #[test]
+    fn checksum_with_small_reads() -> Result<(), ErrorType> {
+        // a tiny buffer makes the zlib decoder and read_bytes split their reads at every byte
+        let content = "some content to compress\n".repeat(50).into_bytes();
+        let objects = vec![(ObjectType::Blob, GitHash::hash_blob(&content), content)];
+        let packfile = build_packfile(objects, PackOptions::default())?;
+        let mut reader = io::BufReader::with_capacity(3, packfile.as_slice());
+
+        assert!(read_packfile(&mut reader, Path::new("does/not/exist")).is_ok());
+        Ok(())
+    }
+
+    #[test]
+    fn base_distance_encoding() -> Result<(), ErrorType> {
+        assert_eq!(read_base_distance(&mut [0x05u8].as_slice())?, 5);
+        // 0x81 0x00 -> ((1 + 1) << 7) | 0 = 256
+        assert_eq!(read_base_distance(&mut [0x81u8, 0x00].as_slice())?, 256);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests_build_packfile {
+    use super::*;
+
+    fn blob(content: String) -> (ObjectType, GitHash, Vec<u8>) {
+        let content = content.into_bytes();
+        (ObjectType::Blob, GitHash::hash_blob(&content), content)
+    }
+
+    #[test]
+    fn similar_objects_are_deltified() -> Result<(), ErrorType> {
+        let base: String = (0..100).map(|i| format!("line {i}\n")).collect();
+        let objects = vec![
+            blob(base.clone()),
+            blob(base.replace("line 20\n", "")),
+            blob(base.replace("line 50", "line fifty")),
+        ];
+        let whole = build_packfile(objects.clone(), PackOptions::new(0, 0))?;
+        let packfile = build_packfile(objects.clone(), PackOptions::default())?;
+        assert!(packfile.len() < whole.len());
+
+        let result = read_packfile(&mut packfile.as_slice(), Path::new("does/not/exist"))?;
+
+        assert_eq!(result.len(), objects.len());
+        for (object_type, _, content) in objects {
+            assert!(result.contains(&(object_type, content)));
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn delta_chains_respect_depth() -> Result<(), ErrorType> {
+        let base = "some text to make a long enough object\n".repeat(10);
+        let objects: Vec<_> = (0..5).map(|i| blob(format!("{base}{i}"))).collect();
+
+        let deltas = find_deltas(&objects, PackOptions::new(1, 2));
+
+        assert!(deltas[0].is_none());
+        assert_eq!(deltas[1].as_ref().map(|(base, _)| *base), Some(0));
+        assert_eq!(deltas[2].as_ref().map(|(base, _)| *base), Some(1));
+        assert!(deltas[3].is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn base_distance_roundtrip() -> Result<(), ErrorType> {
+        for distance in [0, 5, 127, 128, 256, 16511, 16512, 1 << 30] {
+            let bytes = encode_base_distance(distance);
+            assert_eq!(read_base_distance(&mut bytes.as_slice())?, distance);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests_save_packfile {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn saved_objects_are_read_from_the_pack() -> Result<(), ErrorType> {
+        let path_objects = std::env::temp_dir().join("git_rustico_tests_save_packfile");
+        let _ = fs::remove_dir_all(&path_objects);
+
+        let base: String = (0..100).map(|i| format!("line {i}\n")).collect();
+        let objects: Vec<_> = [
+            base.clone(),
+            base.replace("line 50", "line fifty"),
+            base + "end",
+        ]
+        .into_iter()
+        .map(|content| {
+            let content = content.into_bytes();
+            (ObjectType::Blob, GitHash::hash_blob(&content), content)
+        })
+        .collect();
+        let packfile = build_packfile(objects.clone(), PackOptions::default())?;
+
+        let amount = save_packfile(&mut packfile.as_slice(), &path_objects)?;
+
+        assert_eq!(amount, objects.len());
+        for (object_type, hash, content) in objects {
+            assert!(GitObject::contains(&hash, &path_objects));
+            assert_eq!(
+                GitObject::read_object(&hash, &path_objects)?,
+                (object_type, content)
+            );
+        }
+        fs::remove_dir_all(&path_objects)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,340 @@
+use crate::git_errors::errors::ErrorType;
+use crate::hash::GitHash;
+use flate2::Crc;
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{Cursor, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+
+////////////////////////////////////////////////////////////////////////////////////////
+// PACK INDEX (.idx version 2)
+////////////////////////////////////////////////////////////////////////////////////////
+
+// external docs:
+// https://git-scm.com/docs/pack-format#_version_2_pack_idx_files_support_packs_larger_than_4_gib_and
+
+// header                 -> \377tOc + version (2)
+// fanout      256 * 4    -> amount of objects whose first hash byte is <= i
+// names         n * 20   -> sorted object hashes
+// crc32         n * 4    -> crc32 of the packed entry of each object
+// offsets       n * 4    -> offset of each object in the packfile. If the msb is set,
+//                           the rest is an index into the large offsets table
+// large offsets m * 8
+// packfile checksum, idx checksum
+
+const IDX_HEADER: [u8; 4] = [0xff, b't', b'O', b'c'];
+const IDX_VERSION: [u8; 4] = [0, 0, 0, 2];
+const FANOUT_ENTRIES: usize = 256;
+const HASH_SIZE: usize = 20;
+const LARGE_OFFSET: u32 = 0x80000000;
+const PACK_DIR: &str = "pack";
+const PACK_EXTENSION: &str = "pack";
+const IDX_EXTENSION: &str = "idx";
+
+// indexes already read for each objects directory, with the modification time of objects/pack
+// when they were read. They are read again when a packfile is added or removed
+type CachedIndexes = (Option<SystemTime>, Arc<Vec<PackIndex>>);
+static PACK_INDEXES: Mutex<BTreeMap<PathBuf, CachedIndexes>> = Mutex::new(BTreeMap::new());
+
+// content of a .idx file and the path of its packfile
+struct PackIndex {
+    path_pack: PathBuf,
+    content: Vec<u8>,
+}
+
+/// Saves the packfile as objects/pack/pack-<checksum>.pack and writes its .idx next to it.
+/// entries are the hash and offset of every object in the packfile.
+pub(crate) fn save_pack(
+    pack: &[u8],
+    entries: Vec<(GitHash, usize)>,
+    path_objects: &Path,
+) -> Result<(), ErrorType> {
+    let checksum = pack
+        .len()
+        .checked_sub(HASH_SIZE)
+        .map(|start| &pack[start..])
+        .ok_or(ErrorType::ProtocolError(
+            "packfile too short to have a checksum".to_string(),
+        ))?;
+
+    let path_pack_dir = get_pack_dir(path_objects);
+    fs::create_dir_all(&path_pack_dir)?;
+    let name = format!("pack-{}", GitHash::from_hex(checksum)?);
+
+    let idx = build_index(pack, entries)?;
+    fs::write(
+        path_pack_dir.join(&name).with_extension(PACK_EXTENSION),
+        pack,
+    )?;
+    fs::write(path_pack_dir.join(name).with_extension(IDX_EXTENSION), idx)?;
+    forget_indexes(path_objects);
+    Ok(())
+}
+
+fn build_index(pack: &[u8], entries: Vec<(GitHash, usize)>) -> Result<Vec<u8>, ErrorType> {
+    // an entry ends where the next one (by offset) starts, the last one where the checksum starts
+    let mut ends: Vec<usize> = entries.iter().map(|(_, offset)| *offset).collect();
+    ends.sort();
+    ends.push(pack.len() - HASH_SIZE);
+
+    let mut objects = Vec::new();
+    for (hash, offset) in entries {
+        let end = ends[ends.partition_point(|&o| o <= offset)];
+        let mut crc = Crc::new();
+        crc.update(&pack[offset..end]);
+        objects.push((hash.to_hex()?, crc.sum(), offset));
+    }
+    objects.sort();
+
+    let mut idx: Vec<u8> = [IDX_HEADER, IDX_VERSION].concat();
+
+    let mut fanout = [0u32; FANOUT_ENTRIES];
+    for (hash, _, _) in &objects {
+        fanout[hash[0] as usize] += 1;
+    }
+    let mut amount = 0;
+    for count in fanout {
+        amount += count;
+        idx.write_all(&amount.to_be_bytes())?;
+    }
+
+    for (hash, _, _) in &objects {
+        idx.write_all(hash)?;
+    }
+    for (_, crc, _) in &objects {
+        idx.write_all(&crc.to_be_bytes())?;
+    }
+    let mut large_offsets = Vec::new();
+    for (_, _, offset) in &objects {
+        match u32::try_from(*offset) {
+            Ok(offset) if offset < LARGE_OFFSET => idx.write_all(&offset.to_be_bytes())?,
+            _ => {
+                let index = LARGE_OFFSET | large_offsets.len() as u32;
+                idx.write_all(&index.to_be_bytes())?;
+                large_offsets.push(*offset as u64);
+            }
+        }
+    }
+    for offset in large_offsets {
+        idx.write_all(&offset.to_be_bytes())?;
+    }
+
+    idx.write_all(&pack[pack.len() - HASH_SIZE..])?;
+    let checksum = GitHash::hash_sha1(&idx);
+    idx.write_all(&checksum.to_hex()?)?;
+    Ok(idx)
+}
+
+/// Searches the object in the indexes of objects/pack.
+/// Returns the path of the packfile that contains it and its offset inside it.
+pub(crate) fn find_object(
+    hash: &GitHash,
+    path_objects: &Path,
+) -> Result<Option<(PathBuf, usize)>, ErrorType> {
+    let hash = hash.to_hex()?;
+    for index in load_indexes(path_objects)?.iter() {
+        if let Some(offset) = find_in_index(&mut Cursor::new(&index.content), &hash)? {
+            return Ok(Some((index.path_pack.clone(), offset)));
+        }
+    }
+    Ok(None)
+}
+
+/// Hashes of every object stored in the packfiles of objects/pack
+pub(crate) fn list_objects(path_objects: &Path) -> Result<Vec<GitHash>, ErrorType> {
+    let mut objects = Vec::new();
+    for index in load_indexes(path_objects)?.iter() {
+        let mut idx = Cursor::new(&index.content);
+        read_header(&mut idx)?;
+        idx.seek(SeekFrom::Current(((FANOUT_ENTRIES - 1) * 4) as i64))?;
+        let total = u32::from_be_bytes(read_array(&mut idx)?);
+        for _ in 0..total {
+            let name: [u8; HASH_SIZE] = read_array(&mut idx)?;
+            objects.push(GitHash::from_hex(&name)?);
+        }
+    }
+    Ok(objects)
+}
+
+pub(crate) fn get_pack_dir(path_objects: &Path) -> PathBuf {
+    path_objects.join(PACK_DIR)
+}
+
+/// Drops the indexes kept in memory for the objects directory, after its packfiles changed
+pub(crate) fn forget_indexes(path_objects: &Path) {
+    let mut cache = PACK_INDEXES
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    cache.remove(path_objects);
+}
+
+// indexes of objects/pack, read from disk only the first time or if a packfile was added or removed
+fn load_indexes(path_objects: &Path) -> Result<Arc<Vec<PackIndex>>, ErrorType> {
+    let modified = fs::metadata(get_pack_dir(path_objects))
+        .and_then(|metadata| metadata.modified())
+        .ok();
+    // every entry is inserted whole, so the cache is still valid if another thread panicked
+    let mut cache = PACK_INDEXES
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    if let Some((cached_modified, indexes)) = cache.get(path_objects) {
+        if *cached_modified == modified {
+            return Ok(indexes.clone());
+        }
+    }
+
+    let mut indexes = Vec::new();
+    for path in list_indexes(path_objects)? {
+        indexes.push(PackIndex {
+            content: fs::read(&path)?,
+            path_pack: path.with_extension(PACK_EXTENSION),
+        });
+    }
+    let indexes = Arc::new(indexes);
+    cache.insert(path_objects.to_path_buf(), (modified, indexes.clone()));
+    Ok(indexes)
+}
+
+fn list_indexes(path_objects: &Path) -> Result<Vec<PathBuf>, ErrorType> {
+    let path_pack_dir = get_pack_dir(path_objects);
+    if !path_pack_dir.is_dir() {
+        return Ok(Vec::new());
+    }
+    let mut indexes = Vec::new();
+    for entry in fs::read_dir(path_pack_dir)? {
+        let path = entry?.path();
+        if path.extension().and_then(|e| e.to_str()) == Some(IDX_EXTENSION) {
+            indexes.push(path);
+        }
+    }
+    Ok(indexes)
+}
+
+// binary search between the hashes that share the first byte, reading only what is needed
+fn find_in_index<R: Read + Seek>(idx: &mut R, hash: &[u8]) -> Result<Option<usize>, ErrorType> {
+    read_header(idx)?;
+    let mut fanout = [0u32; FANOUT_ENTRIES];
+    for count in fanout.iter_mut() {
+        *count = u32::from_be_bytes(read_array(idx)?);
+    }
+    let total = fanout[FANOUT_ENTRIES - 1] as u64;
+    let names_start = (8 + FANOUT_ENTRIES * 4) as u64;
+
+    let first = hash[0] as usize;
+    let mut low = match first {
+        0 => 0,
+        _ => fanout[first - 1] as u64,
+    };
+    let mut high = fanout[first] as u64;
+
+    while low < high {
+        let middle = (low + high) / 2;
+        idx.seek(SeekFrom::Start(names_start + middle * HASH_SIZE as u64))?;
+        let name: [u8; HASH_SIZE] = read_array(idx)?;
+        match name.as_slice().cmp(hash) {
+            std::cmp::Ordering::Less => low = middle + 1,
+            std::cmp::Ordering::Greater => high = middle,
+            std::cmp::Ordering::Equal => {
+                let offsets_start = names_start + total * (HASH_SIZE as u64 + 4);
+                idx.seek(SeekFrom::Start(offsets_start + middle * 4))?;
+                let offset = u32::from_be_bytes(read_array(idx)?);
+                if offset & LARGE_OFFSET == 0 {
+                    return Ok(Some(offset as usize));
+                }
+                let large_index = (offset & !LARGE_OFFSET) as u64;
+                idx.seek(SeekFrom::Start(offsets_start + total * 4 + large_index * 8))?;
+                return Ok(Some(u64::from_be_bytes(read_array(idx)?) as usize));
+            }
+        }
+    }
+    Ok(None)
+}
+
+fn read_header<R: Read>(idx: &mut R) -> Result<(), ErrorType> {
+    let header: [u8; 8] = read_array(idx)?;
+    if header[..4] != IDX_HEADER || header[4..] != IDX_VERSION {
+        return Err(ErrorType::FormatError(
+            "invalid pack index, only version 2 is supported".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+pub(crate) fn read_array<R: Read, const N: usize>(stream: &mut R) -> Result<[u8; N], ErrorType> {
+    let mut bytes = [0; N];
+    stream.read_exact(&mut bytes)?;
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    fn hash(first_byte: u8) -> Result<GitHash, ErrorType> {
+        GitHash::from_hex(&[first_byte; HASH_SIZE])
+    }
+
+    fn fake_pack() -> Vec<u8> {
+        let mut pack = vec![0; 100];
+        pack.extend([7; HASH_SIZE]);
+        pack
+    }
+
+    #[test]
+    fn lookup_by_hash() -> Result<(), ErrorType> {
+        let entries = vec![(hash(0xaa)?, 12), (hash(0x01)?, 40), (hash(0xab)?, 70)];
+        let idx = build_index(&fake_pack(), entries)?;
+
+        let mut idx = Cursor::new(idx);
+        assert_eq!(find_in_index(&mut idx, &hash(0xaa)?.to_hex()?)?, Some(12));
+        idx.set_position(0);
+        assert_eq!(find_in_index(&mut idx, &hash(0x01)?.to_hex()?)?, Some(40));
+        idx.set_position(0);
+        assert_eq!(find_in_index(&mut idx, &hash(0xab)?.to_hex()?)?, Some(70));
+        idx.set_position(0);
+        assert_eq!(find_in_index(&mut idx, &hash(0x02)?.to_hex()?)?, None);
+        Ok(())
+    }
+
+    #[test]
+    fn found_objects_include_new_packs() -> Result<(), ErrorType> {
+        let path_objects = std::env::temp_dir().join("git_rustico_tests_pack_index_cache");
+        let _ = fs::remove_dir_all(&path_objects);
+
+        save_pack(&fake_pack(), vec![(hash(0x01)?, 12)], &path_objects)?;
+        assert!(find_object(&hash(0x01)?, &path_objects)?.is_some());
+        assert_eq!(find_object(&hash(0x02)?, &path_objects)?, None);
+
+        let mut other_pack = vec![1; 100];
+        other_pack.extend([8; HASH_SIZE]);
+        save_pack(&other_pack, vec![(hash(0x02)?, 40)], &path_objects)?;
+        let found = find_object(&hash(0x02)?, &path_objects)?;
+        assert_eq!(found.map(|(_, offset)| offset), Some(40));
+        assert_eq!(list_objects(&path_objects)?.len(), 2);
+
+        fs::remove_dir_all(&path_objects)?;
+        Ok(())
+    }
+
+    #[test]
+    fn index_layout() -> Result<(), ErrorType> {
+        let pack = fake_pack();
+        let idx = build_index(&pack, vec![(hash(0x05)?, 12), (hash(0x01)?, 50)])?;
+
+        assert_eq!(idx.len(), 8 + 256 * 4 + 2 * (20 + 4 + 4) + 20 + 20);
+        // fanout: 0 objects before 0x01, 1 up to 0x04, 2 from 0x05 on
+        assert_eq!(idx[8..12], 0u32.to_be_bytes());
+        assert_eq!(idx[12..16], 1u32.to_be_bytes());
+        assert_eq!(idx[8 + 4 * 5..8 + 4 * 6], 2u32.to_be_bytes());
+        assert_eq!(idx[8 + 4 * 255..8 + 4 * 256], 2u32.to_be_bytes());
+        // crc of the first object (by hash) covers the bytes from 50 to the checksum
+        let mut crc = Crc::new();
+        crc.update(&pack[50..100]);
+        let crc_start = 8 + 256 * 4 + 2 * 20;
+        assert_eq!(idx[crc_start..crc_start + 4], crc.sum().to_be_bytes());
+        Ok(())
+    }
+}
@@ -4,6 +4,7 @@ use crate::git_errors::errors::ErrorType;
 use crate::http::http_error::HTTPError::{BadRequest, MethodNotAllowed, NotFound};
 use crate::merge::Merge;
 use crate::repo_paths::RepoPaths;
+use crate::rev_walk::RevWalk;
 use crate::server_paths::ServerPaths;
 use crate::user::User;
 use serde::{Deserialize, Serialize};
@@ -69,10 +70,9 @@ impl PullRequest {
         let base_commit = base.get_last_commit(&path_objects)?;
         let target_commmit = target.get_last_commit(&path_objects)?;
 
-        let last_common_ancestor =
-            target_commmit.last_common_ancestor(&base_commit, &path_objects)?;
+        let merge_bases = target_commmit.merge_bases(&base_commit, &path_objects)?;
 
-        if last_common_ancestor.is_none() {
+        if merge_bases.is_empty() {
             return Err(ErrorType::HTTPError(BadRequest(
                 "Can't create PullRequest for branches with no common ancestor".to_string(),
             )));
@@ -168,9 +168,11 @@ impl PullRequest {
         let target = pr.open_branch_target(&repo_paths.get_refs_heads())?;
 
         let commit = target.get_last_commit(&repo_paths.get_objects())?;
-        let vec = commit.get_commits_history(&repo_paths.get_objects())?;
+        let mut walk = RevWalk::new(&repo_paths.get_objects());
+        walk.push(commit.get_hash());
         let mut list_commits = Vec::new();
-        for commit in vec {
+        for commit in walk {
+            let commit = commit?;
             let hash = commit.get_hash().to_string();
             list_commits.push(commit);
             if hash == pr.hash_creation_commit {
@@ -239,25 +241,29 @@ impl PullRequest {
     ) -> Result<(), ErrorType> {
         let path_objects = repo_paths.get_objects();
 
-        let base_files = base.get_last_commit(&path_objects)?.get_files_vec();
-        let target_files = target.get_last_commit(&path_objects)?.get_files_vec();
+        let base_files = base.get_last_commit(&path_objects)?.get_files_vec()?;
+        let target_files = target.get_last_commit(&path_objects)?.get_files_vec()?;
 
-        let last_common_ancestor = match target
-            .get_last_commit(&repo_paths.get_objects())?
-            .last_common_ancestor(
-                &base.get_last_commit(&repo_paths.get_objects())?,
-                &repo_paths.get_objects(),
-            )? {
+        let last_common_ancestor = match Merge::merge_base(
+            &base.get_last_commit(&path_objects)?,
+            &target.get_last_commit(&path_objects)?,
+            repo_paths,
+        )? {
             Some(lca) => lca,
             None => return Err(ErrorType::HTTPError(BadRequest(
                 "Can't merge PullRequest, branches base and target have no common commit ancestor"
                     .to_string(),
             ))),
         };
-        let lca_files = last_common_ancestor.get_files_vec();
-
-        let (_, conflict_files) =
-            Merge::compare_files(base_files, target_files, lca_files, repo_paths)?;
+        let lca_files = last_common_ancestor.get_files_vec()?;
+
+        let (_, conflict_files) = Merge::compare_files(
+            base_files,
+            target_files,
+            lca_files,
+            &target.get_name(),
+            repo_paths,
+        )?;
 
         if !conflict_files.is_empty() {
             let files = conflict_files
@@ -2,6 +2,8 @@ use crate::branch::Branch;
 use crate::commit::Commit;
 use crate::git_errors::errors::ErrorType;
 use crate::repo_paths::RepoPaths;
+use crate::rev_parse::RevParse;
+use crate::rev_walk::{RevWalk, WalkOrder};
 use std::cmp::min;
 use std::fs;
 
@@ -40,19 +42,20 @@ impl Rebase {
             true => fs::read_to_string(repo_paths.get_head())?,
             false => other,
         };
-        let base_branch = Branch::open(&repo_paths.get_refs_heads(), &base)?;
+        // la base puede ser cualquier revision, la que se mueve tiene que ser una branch
+        let last_commit_base = RevParse::resolve_commit(&repo_paths, &base)?;
         let mut other_branch = Branch::open(&repo_paths.get_refs_heads(), &other)?;
-        let last_commit_base = base_branch.get_last_commit(&repo_paths.get_objects())?;
         let last_commit_other = other_branch.get_last_commit(&repo_paths.get_objects())?;
-        let mut commits_history_base =
-            last_commit_base.get_commits_history_rec(&repo_paths.get_objects())?;
-        let mut commits_history_other =
-            last_commit_other.get_commits_history_rec(&repo_paths.get_objects())?;
+        let mut walk = RevWalk::new(&repo_paths.get_objects());
+        walk.set_order(WalkOrder::Topological);
+        walk.push(last_commit_base.get_hash());
+        let mut commits_history_base = walk.commits()?;
+        walk.reset();
+        walk.push(last_commit_other.get_hash());
+        let mut commits_history_other = walk.commits()?;
         commits_history_base.reverse();
         commits_history_other.reverse();
         let val_diff = Self::get_pos_for_first_diff(&commits_history_other, &commits_history_base);
-        let mut commits_for_delete = commits_history_other.clone();
-        commits_for_delete.drain(0..val_diff);
         for j in val_diff..commits_history_other.len() {
             let before_commit: Commit = match j == val_diff {
                 false => commits_history_other[j - 1].clone(),
@@ -67,9 +70,7 @@ impl Rebase {
             commits_history_other[commits_history_other.len() - 1].get_hash(),
         );
         other_branch.save()?;
-        for commit in commits_for_delete {
-            commit.delete(&repo_paths.get_objects())?;
-        }
+        // los commits anteriores al rebase quedan sin referencias, gc se encarga de borrarlos
         Ok(format!("Branch base: {} for: {}.", base, other))
     }
 
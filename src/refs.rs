@@ -1,18 +1,30 @@
-use crate::{branch::Branch, commit::Commit, index::Index, repo_paths::RepoPaths};
+use crate::{
+    blob::Blob, branch::Branch, commit::Commit, ignore::Ignore, index::Index, repo_paths::RepoPaths,
+};
 use std::{
+    collections::BTreeSet,
     fs::{self, File},
     path::{Path, PathBuf},
 };
 
 use crate::{
-    git_errors::{command_error::CommandError::IncorrectAmount, errors::ErrorType},
+    git_errors::{
+        command_error::CommandError::{IncorrectAmount, UnknownOption},
+        errors::ErrorType,
+    },
     git_object::GitObject,
     hash::GitHash,
+    rev_parse::RevParse,
+    rev_walk::{RevWalk, WalkOrder},
 };
 
+const FORCE_OPTION: &str = "--force";
+const FORCE_SHORT_OPTION: &str = "-f";
+
 pub struct BranchRef {
     branch: Option<Branch>,
-    path_ref: PathBuf, // podria guardar path branches
+    path_ref: PathBuf,         // podria guardar path branches
+    detached: Option<GitHash>, // HEAD apunta directo a un commit
 }
 
 impl BranchRef {
@@ -28,43 +40,84 @@ impl BranchRef {
             )));
         }
         let branch_name = fs::read_to_string(&path_ref)?;
-        if !path_branches.join(&branch_name).exists() {
+        if !path_branches.join(&branch_name).is_file() {
+            // si no es una branch puede ser un hash (detached HEAD)
+            let detached = match branch_name.trim() {
+                hash if hash.len() == 40 => GitHash::new(hash).ok(),
+                _ => None,
+            };
             return Ok(Self {
                 branch: None,
                 path_ref,
+                detached,
             });
         }
         let branch = Branch::open(path_branches, &branch_name)?;
         Ok(Self {
             branch: Some(branch),
             path_ref,
+            detached: None,
         })
     }
 
     pub fn new(branch: Option<Branch>, path_ref: PathBuf) -> Self {
         // meter save adentro?
-        Self { branch, path_ref }
+        Self {
+            branch,
+            path_ref,
+            detached: None,
+        }
     }
 
     // obtiene el commmit al que apunta head. None si no existe (cuando no hay commits head no apunta a nada)
     pub fn get_last_commit(&self, path_objects: &Path) -> Result<Option<Commit>, ErrorType> {
-        if let Some(branch) = &self.branch {
-            let hash = branch.get_last_commit_hash();
+        if let Some(hash) = self.get_last_commit_hash() {
             let commit = GitObject::read_commit(&hash, path_objects)?;
             return Ok(Some(commit));
         }
         Ok(None)
     }
 
+    pub fn is_detached(&self) -> bool {
+        self.detached.is_some()
+    }
+
+    /// Hace que HEAD apunte directo al commit en vez de a una branch (falta el save)
+    pub fn detach(&mut self, hash: GitHash) {
+        self.branch = None;
+        self.detached = Some(hash);
+    }
+
     pub fn get_branch_name(&self) -> Option<String> {
         self.branch.as_ref().map(|b| b.get_name())
     }
 
+    /// checkout [--force | -f] <branch | commit | tag>
+    ///
+    /// Si no se pasa una branch HEAD queda apuntando directo al commit (detached HEAD).
+    /// Solo se actualizan los archivos que cambian entre el commit actual y el de la branch. Los
+    /// cambios locales en otros archivos se mantienen. Si un archivo que cambia tiene cambios
+    /// locales, o si hay un archivo sin trackear en el lugar de uno de la branch, no se hace el
+    /// checkout, salvo con --force que los descarta.
     pub fn checkout_command(repo_paths: RepoPaths, args: Vec<String>) -> Result<String, ErrorType> {
-        if args.len() != 1 {
+        let mut force = false;
+        let mut names = Vec::new();
+        for arg in args {
+            match arg.as_str() {
+                FORCE_OPTION | FORCE_SHORT_OPTION => force = true,
+                option if option.starts_with('-') => {
+                    return Err(ErrorType::CommandError(UnknownOption(
+                        FORCE_OPTION.to_string(),
+                        arg,
+                    )))
+                }
+                _ => names.push(arg),
+            }
+        }
+        if names.len() != 1 {
             return Err(ErrorType::CommandError(IncorrectAmount(
                 1.to_string(),
-                args.len(),
+                names.len(),
             )));
         }
 
@@ -73,18 +126,38 @@ impl BranchRef {
         let path_objects = repo_paths.get_objects();
         let path_branches = repo_paths.get_refs_heads();
 
-        let index = Index::new(File::open(&path_index)?)?;
-        index.check_for_changes(&path_home, &repo_paths.get_ignore())?;
-
-        let branch_name: &str = &args[0];
-        let branch = Branch::open(&path_branches, branch_name)?;
+        let index = Index::open(&path_index)?;
 
+        let name: &str = &names[0];
         let mut head = Self::open(repo_paths.get_head(), &path_branches)?;
 
-        head.checkout_to(branch, index, &path_home, &path_objects, &path_index)?;
+        if !path_branches.join(name).is_file() {
+            let commit = RevParse::resolve_commit(&repo_paths, name)?;
+            head.checkout_commit(
+                &commit,
+                index,
+                &path_home,
+                &path_objects,
+                &path_index,
+                force,
+            )?;
+            head.detach(commit.get_hash());
+            head.save()?;
+
+            let result = format!(
+                "Note: switching to '{}'.\nYou are in 'detached HEAD' state. Use 'branch <name>' to keep the commits you create.\nHEAD is now at {}",
+                name,
+                Self::describe_commit(&commit)
+            );
+            println!("{}", result);
+            return Ok(result);
+        }
+        let branch = Branch::open(&path_branches, name)?;
+
+        head.checkout_to(branch, index, &path_home, &path_objects, &path_index, force)?;
         head.save()?;
 
-        let result = format!("checkout successfull to {} branch.", branch_name);
+        let result = format!("checkout successfull to {} branch.", name);
         println!("{}", result);
         Ok(result)
     }
@@ -95,8 +168,7 @@ impl BranchRef {
         let path_index = repo_paths.get_index();
         let path_home = repo_paths.get_home();
 
-        let index = Index::new(File::open(&path_index)?)?;
-        index.check_for_changes(&path_home, &repo_paths.get_ignore())?;
+        let index = Index::open(&path_index)?;
 
         let local_branch_name: &str = &args[0];
 
@@ -125,13 +197,18 @@ impl BranchRef {
         )?;
 
         let mut head = Self::open(repo_paths.get_head(), &repo_paths.get_refs_heads())?;
-        head.checkout_to(
+        if let Err(e) = head.checkout_to(
             local_branch,
             index,
             &path_home,
             &repo_paths.get_objects(),
             &path_index,
-        )?;
+            false,
+        ) {
+            // si no se pudo hacer el checkout la branch nueva no queda creada
+            fs::remove_file(repo_paths.get_refs_heads().join(local_branch_name))?;
+            return Err(e);
+        }
         head.save()?;
 
         let result = format!("switch successfull to {} branch.", local_branch_name);
@@ -139,107 +216,339 @@ impl BranchRef {
         Ok(result)
     }
 
+    /// Actualiza el working directory y el index al ultimo commit de la branch y hace que HEAD
+    /// apunte a ella (falta el save). Ver `update_working_dir`
     // todo : que no reciba index
     pub fn checkout_to(
         &mut self,
         branch: Branch,
+        index: Index,
+        path_home: &Path,
+        path_objects: &Path,
+        path_index: &PathBuf,
+        force: bool,
+    ) -> Result<(), ErrorType> {
+        let commit = branch.get_last_commit(path_objects)?;
+        self.checkout_commit(&commit, index, path_home, path_objects, path_index, force)?;
+        self.set_branch(branch);
+        Ok(())
+    }
+
+    /// Actualiza el working directory y el index al commit, sin mover HEAD
+    fn checkout_commit(
+        &self,
+        commit: &Commit,
         mut index: Index,
         path_home: &Path,
         path_objects: &Path,
         path_index: &PathBuf,
+        force: bool,
     ) -> Result<(), ErrorType> {
-        let files = Self::update_working_dir_files(
-            branch.get_last_commit(path_objects)?,
+        // self todavia apunta al commit anterior, aunque la branch ya se haya movido (merge)
+        let previous = self.get_last_commit(path_objects)?;
+        Self::update_working_dir(
+            &mut index,
+            previous.as_ref(),
+            commit,
             path_home,
             path_objects,
+            force,
         )?;
-        self.set_branch(branch);
 
-        index.update_to_working_dir(files, path_home)?;
         index.reset_previous_blob_hash();
         index.save(&mut File::create(path_index)?)?;
 
         Ok(())
     }
 
-    pub fn set_branch(&mut self, branch: Branch) {
-        self.branch = Some(branch);
+    /// reset [--soft | --mixed | --hard] [commit]
+    ///
+    /// Mueve la branch actual al commit (HEAD si no se pasa ninguno).
+    /// --soft solo mueve la branch, --mixed (por defecto) ademas vuelve el index al commit y
+    /// --hard tambien el working directory. Los archivos sin trackear no se tocan.
+    pub fn reset_command(repo_paths: RepoPaths, args: Vec<String>) -> Result<String, ErrorType> {
+        let mut mode = None;
+        let mut targets = Vec::new();
+        for arg in args {
+            match arg.as_str() {
+                "--soft" | "--mixed" | "--hard" if mode.is_none() => mode = Some(arg),
+                option if option.starts_with('-') => {
+                    return Err(ErrorType::CommandError(UnknownOption(
+                        "one of --soft, --mixed, --hard".to_string(),
+                        arg,
+                    )))
+                }
+                _ => targets.push(arg),
+            }
+        }
+        if targets.len() > 1 {
+            return Err(ErrorType::CommandError(IncorrectAmount(
+                "0 or 1".to_string(),
+                targets.len(),
+            )));
+        }
+        let mode = mode.unwrap_or("--mixed".to_string());
+        let target = targets.pop().unwrap_or("HEAD".to_string());
+
+        let path_objects = repo_paths.get_objects();
+        let path_branches = repo_paths.get_refs_heads();
+        let mut head = Self::open(repo_paths.get_head(), &path_branches)?;
+        let Some(current_commit) = head.get_last_commit(&path_objects)? else {
+            return Err(ErrorType::RepositoryError(
+                "your current branch doesnt have commits yet".to_string(),
+            ));
+        };
+        let commit = RevParse::resolve_commit(&repo_paths, &target)?;
+
+        if repo_paths.get_head_merge().exists() {
+            if mode == "--soft" {
+                return Err(ErrorType::RepositoryError(
+                    "Cannot do a soft reset in the middle of a merge.".to_string(),
+                ));
+            }
+            // igual que git, el reset descarta el merge abierto
+            fs::remove_file(repo_paths.get_head_merge())?;
+        }
+        head.set_last_commit(commit.get_hash(), &path_branches)?;
+
+        if mode != "--soft" {
+            let path_index = repo_paths.get_index();
+            let mut index = Index::open(&path_index)?;
+            match mode.as_str() {
+                "--hard" => Self::reset_working_dir(
+                    &mut index,
+                    &commit,
+                    &current_commit,
+                    &repo_paths.get_home(),
+                    &path_objects,
+                )?,
+                _ => index.reset_to(commit.get_files_map()?),
+            }
+            index.save(&mut File::create(path_index)?)?;
+        }
+
+        let result = format!("HEAD is now at {}", Self::describe_commit(&commit));
+        println!("{}", result);
+        Ok(result)
     }
 
-    // borra todo el working directory y arma uno nuevo a partir de un commit
-    fn update_working_dir_files(
-        branch_commit: Commit,
+    // hash corto y primera linea del mensaje
+    fn describe_commit(commit: &Commit) -> String {
+        let message = commit.get_message();
+        format!(
+            "{} {}",
+            &commit.get_hash().as_str()[..7],
+            message.lines().next().unwrap_or_default()
+        )
+    }
+
+    /// Vuelve el index y el working directory al commit (reset --hard), descartando los cambios
+    /// locales. Los archivos sin trackear no se tocan
+    pub(crate) fn reset_working_dir(
+        index: &mut Index,
+        commit: &Commit,
+        previous: &Commit,
         path_home: &Path,
         path_objects: &Path,
-    ) -> Result<Vec<(PathBuf, GitHash)>, ErrorType> {
-        Self::delete_directory_contents_rec(path_home.to_path_buf())?;
-
-        let files = branch_commit.get_files_vec();
+    ) -> Result<(), ErrorType> {
+        Self::update_working_dir(index, Some(previous), commit, path_home, path_objects, true)?;
+        index.clear_unmerged();
+        index.reset_previous_blob_hash();
+        Ok(())
+    }
 
-        for (local_path, hash) in &files {
-            let global_path = path_home.join(local_path);
-            if let Some(parent) = global_path.parent() {
-                fs::create_dir_all(parent)?;
+    // Pasa el working directory y el index del tree actual (el del index) al del commit, tocando
+    // solo los paths que cambian. `previous` es el commit de HEAD, para detectar cambios en el index.
+    // Sin force falla, sin tocar nada, si tendria que pisar cambios locales, archivos sin trackear
+    // o conflictos de un merge abierto. Igual que git, los archivos ignorados se pisan sin preguntar
+    fn update_working_dir(
+        index: &mut Index,
+        previous: Option<&Commit>,
+        commit: &Commit,
+        path_home: &Path,
+        path_objects: &Path,
+        force: bool,
+    ) -> Result<(), ErrorType> {
+        let target = commit.get_files_map()?;
+        let previous = previous
+            .map(|c| c.get_files_map())
+            .transpose()?
+            .unwrap_or_default();
+        let unmerged: BTreeSet<PathBuf> = index.unmerged_paths().into_iter().collect();
+        let mut paths: BTreeSet<PathBuf> = index.get_paths().into_iter().collect();
+        paths.extend(unmerged.iter().cloned());
+        paths.extend(target.keys().cloned());
+        let mut ignore = Ignore::open(&RepoPaths::new(path_home.to_path_buf())?);
+
+        let mut changes = Vec::new();
+        let mut modified = Vec::new();
+        let mut untracked = Vec::new();
+        for path in paths {
+            let current = index.get_entry(&path);
+            let wanted = target.get(&path);
+            let dirty = match &current {
+                _ if unmerged.contains(&path) => true,
+                Some(entry) => {
+                    previous.get(&path) != Some(entry)
+                        || index.has_local_changes(&path, path_home)?
+                }
+                None => Self::has_untracked(&path, index, &mut ignore, path_home)?,
+            };
+            if current.as_ref() == wanted && !(force && dirty) {
+                // no cambia: se mantienen los cambios locales, como hace git
+                continue;
             }
-            let blob_object = GitObject::read_blob(hash, path_objects)?;
-            blob_object.write_to_working_directory(&global_path)?;
+            if dirty && !force {
+                match current.is_some() || unmerged.contains(&path) {
+                    true => modified.push(path.display().to_string()),
+                    false => untracked.push(path.display().to_string()),
+                }
+                continue;
+            }
+            changes.push((path, wanted.cloned()));
+        }
+
+        let mut errors = Vec::new();
+        if !modified.is_empty() {
+            errors.push(format!(
+                "Your local changes to the following files would be overwritten by checkout:\n\t{}\nPlease commit your changes or stash them before you switch branches.",
+                modified.join("\n\t")
+            ));
+        }
+        if !untracked.is_empty() {
+            errors.push(format!(
+                "The following untracked working tree files would be overwritten by checkout:\n\t{}\nPlease move or remove them before you switch branches.",
+                untracked.join("\n\t")
+            ));
+        }
+        if !errors.is_empty() {
+            errors.push("Use --force to discard them.".to_string());
+            return Err(ErrorType::RepositoryError(errors.join("\n")));
+        }
+
+        // primero se borra todo: un archivo dir puede reemplazar a dir/x, o al reves
+        let (writes, removals): (Vec<_>, Vec<_>) = changes
+            .into_iter()
+            .partition(|(_, wanted)| wanted.is_some());
+        for (path, _) in removals {
+            Self::remove_from_working_dir(&path_home.join(&path), path_home)?;
+            index.remove(path)?;
         }
-        Ok(files)
+        for (path, wanted) in writes {
+            let Some((mode, hash)) = wanted else {
+                continue;
+            };
+            Blob::checkout(&hash, mode, &path_home.join(&path), path_objects)?;
+            index.checkout_entry(path, mode, hash, path_home)?;
+        }
+        Ok(())
     }
 
-    // todo : usar remove_dir_all. se puede ignorando el .git?
-    fn delete_directory_contents_rec(directory_path: PathBuf) -> Result<(), ErrorType> {
-        if directory_path.is_dir() {
-            let entries = fs::read_dir(directory_path)?;
-            for entry in entries {
-                let entry = entry?;
-
-                if entry.file_type()?.is_dir() {
-                    let entry_name = entry.file_name();
-                    if entry_name == *".git-rustico" {
-                        continue;
-                    }
-                    let subdirectory = entry.path();
-                    Self::delete_directory_contents_rec(subdirectory)?;
-                } else {
-                    let file_name = entry.file_name();
-                    if file_name == *".gitignore" {
-                        continue;
-                    }
-                    fs::remove_file(entry.path())?;
-                }
+    // si en el path hay un archivo sin trackear que no este ignorado. Si es un directorio se
+    // revisa su contenido, puede tener solo archivos trackeados que se van a borrar
+    fn has_untracked(
+        path: &Path,
+        index: &Index,
+        ignore: &mut Ignore,
+        path_home: &Path,
+    ) -> Result<bool, ErrorType> {
+        let global_path = path_home.join(path);
+        if global_path.is_symlink() || !global_path.is_dir() {
+            return Ok((global_path.exists() || global_path.is_symlink())
+                && index.get_entry(path).is_none()
+                && !ignore.is_ignored(path, false)?);
+        }
+        if ignore.is_ignored(path, true)? {
+            return Ok(false);
+        }
+        for entry in fs::read_dir(&global_path)? {
+            if Self::has_untracked(&path.join(entry?.file_name()), index, ignore, path_home)? {
+                return Ok(true);
             }
         }
+        Ok(false)
+    }
 
+    // borra un archivo trackeado y los directorios que quedan vacios
+    fn remove_from_working_dir(global_path: &Path, path_home: &Path) -> Result<(), ErrorType> {
+        match fs::symlink_metadata(global_path) {
+            Ok(metadata) if metadata.is_dir() => fs::remove_dir_all(global_path)?,
+            Ok(_) => fs::remove_file(global_path)?,
+            Err(_) => return Ok(()),
+        }
+        for parent in global_path.ancestors().skip(1) {
+            if parent == path_home || fs::remove_dir(parent).is_err() {
+                break;
+            }
+        }
         Ok(())
     }
 
-    // todo (nice to have) imprmir a donde se encuentra cada branch y ver que pasa cuando hay open merge
-    pub fn log_command(repo_paths: RepoPaths) -> Result<usize, ErrorType> {
+    pub fn set_branch(&mut self, branch: Branch) {
+        self.branch = Some(branch);
+        self.detached = None;
+    }
+
+    /// log [--date-order | --topo-order] [<revision> | <a>..<b> | <a>...<b> | ^<revision>]...
+    ///
+    /// Muestra los commits alcanzables desde las revisiones, sin los excluidos por los rangos.
+    /// Sin revisiones muestra la historia de HEAD. Por defecto ordena por fecha.
+    pub fn log_command(repo_paths: RepoPaths, args: Vec<String>) -> Result<usize, ErrorType> {
         let path_objects = repo_paths.get_objects();
         let head = BranchRef::open(repo_paths.get_head(), &repo_paths.get_refs_heads())?;
         let branches = Branch::list_branches(&repo_paths.get_refs_heads())?;
 
-        if let Some(commit) = head.get_last_commit(&repo_paths.get_objects())? {
-            let commit_history = commit.get_commits_history(&path_objects)?;
-            let number_of_commits = commit_history.len();
-            for commit in commit_history {
-                let mut refs = Vec::new();
-                if let Some(hash) = head.get_last_commit_hash() {
-                    if hash == commit.get_hash() {
-                        refs.push("HEAD".to_string());
+        let mut walk = RevWalk::new(&path_objects);
+        let mut revisions = 0;
+        for arg in &args {
+            match arg.as_str() {
+                "--date-order" => walk.set_order(WalkOrder::Date),
+                "--topo-order" => walk.set_order(WalkOrder::Topological),
+                option if option.starts_with("--") => {
+                    return Err(ErrorType::CommandError(UnknownOption(
+                        "--date-order, --topo-order".to_string(),
+                        option.to_string(),
+                    )))
+                }
+                range => {
+                    let (include, exclude) = RevParse::resolve_range(&repo_paths, range)?;
+                    for hash in include {
+                        walk.push(RevParse::peel(&repo_paths, &hash, "commit")?);
                     }
+                    for hash in exclude {
+                        walk.hide(RevParse::peel(&repo_paths, &hash, "commit")?);
+                    }
+                    revisions += 1;
                 }
-                if let Some(branch_name) = branches.iter().find(|b| *b.1 == commit.get_hash()) {
-                    refs.push(branch_name.0.clone());
+            }
+        }
+        if revisions == 0 {
+            let Some(hash) = head.get_last_commit_hash() else {
+                return Err(ErrorType::RepositoryError(
+                    "your current branch doesnt have commits yet".to_string(),
+                ));
+            };
+            if head.is_detached() {
+                println!("HEAD detached at {}", &hash.as_str()[..7]);
+            }
+            walk.push(hash);
+        }
+        let commit_history = walk.commits()?;
+
+        let number_of_commits = commit_history.len();
+        for commit in commit_history {
+            let mut refs = Vec::new();
+            if let Some(hash) = head.get_last_commit_hash() {
+                if hash == commit.get_hash() {
+                    refs.push("HEAD".to_string());
                 }
-                commit.log_display(refs);
             }
-            return Ok(number_of_commits);
+            if let Some(branch_name) = branches.iter().find(|b| *b.1 == commit.get_hash()) {
+                refs.push(branch_name.0.clone());
+            }
+            commit.log_display(refs);
         }
-        Err(ErrorType::RepositoryError(
-            "your current branch doesnt have commits yet".to_string(),
-        ))
+        Ok(number_of_commits)
     }
 
     pub fn set_last_commit(
@@ -250,6 +559,9 @@ impl BranchRef {
         if let Some(branch) = &mut self.branch {
             branch.set_last_commit_hash(hash);
             branch.save()?;
+        } else if self.detached.is_some() {
+            self.detached = Some(hash);
+            self.save()?;
         } else {
             self.branch = Some(Branch::new("master", path_branches, hash)?);
         }
@@ -264,13 +576,17 @@ impl BranchRef {
     }
 
     pub fn get_last_commit_hash(&self) -> Option<GitHash> {
-        self.branch.as_ref().map(|c| c.get_last_commit_hash())
-        // self.get_last_commit().map(|x| x.get_hash())
+        match &self.branch {
+            Some(branch) => Some(branch.get_last_commit_hash()),
+            None => self.detached.clone(),
+        }
     }
 
     pub fn save(&self) -> Result<(), ErrorType> {
         if let Some(branch) = &self.branch {
             fs::write(&self.path_ref, branch.get_name())?;
+        } else if let Some(hash) = &self.detached {
+            fs::write(&self.path_ref, hash.as_str())?;
         }
         Ok(())
     }
@@ -279,3 +595,210 @@ impl BranchRef {
         self.branch.as_ref().cloned()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        merge::Merge,
+        test_utils::{commit_file, init_repo},
+    };
+
+    fn checkout(repo_paths: &RepoPaths, args: &[&str]) -> Result<String, ErrorType> {
+        let args = args.iter().map(|arg| arg.to_string()).collect();
+        BranchRef::checkout_command(repo_paths.clone(), args)
+    }
+
+    #[test]
+    fn checkout_only_touches_changed_paths() -> Result<(), ErrorType> {
+        let repo_paths = init_repo("git_rustico_tests_checkout")?;
+        let home = repo_paths.get_home();
+        fs::write(home.join(".gitignore"), "build/\n")?;
+        commit_file(&repo_paths, "a.txt", "a")?;
+        commit_file(&repo_paths, "same.txt", "same")?;
+        Branch::branch_command(&repo_paths, vec!["dev".to_string()])?;
+        checkout(&repo_paths, &["dev"])?;
+        fs::create_dir_all(home.join("dir"))?;
+        commit_file(&repo_paths, "a.txt", "dev")?;
+        commit_file(&repo_paths, "dir/only_dev.txt", "dev")?;
+
+        fs::create_dir_all(home.join("build"))?;
+        fs::write(home.join("build/output"), "ignored")?;
+        fs::write(home.join("untracked.txt"), "u")?;
+        checkout(&repo_paths, &["master"])?;
+        assert_eq!(fs::read_to_string(home.join("a.txt"))?, "a");
+        assert!(!home.join("dir").exists());
+        assert!(home.join("build/output").exists());
+        assert!(home.join("untracked.txt").exists());
+
+        // los cambios en archivos que no cambian entre las branches se mantienen
+        fs::write(home.join("same.txt"), "local")?;
+        checkout(&repo_paths, &["dev"])?;
+        assert_eq!(fs::read_to_string(home.join("same.txt"))?, "local");
+
+        // no se pisan cambios locales ni archivos sin trackear, salvo con --force
+        fs::write(home.join("a.txt"), "local")?;
+        assert!(checkout(&repo_paths, &["master"]).is_err());
+        assert_eq!(fs::read_to_string(home.join("a.txt"))?, "local");
+        checkout(&repo_paths, &["--force", "master"])?;
+        assert_eq!(fs::read_to_string(home.join("a.txt"))?, "a");
+        assert_eq!(fs::read_to_string(home.join("same.txt"))?, "same");
+
+        fs::create_dir_all(home.join("dir"))?;
+        fs::write(home.join("dir/only_dev.txt"), "mine")?;
+        assert!(checkout(&repo_paths, &["dev"]).is_err());
+        assert_eq!(fs::read_to_string(home.join("dir/only_dev.txt"))?, "mine");
+        checkout(&repo_paths, &["-f", "dev"])?;
+        assert_eq!(fs::read_to_string(home.join("dir/only_dev.txt"))?, "dev");
+
+        let status = Index::open(&repo_paths.get_index())?.status(&repo_paths)?;
+        assert!(status.staged.is_empty() && status.not_staged.is_empty());
+
+        fs::remove_dir_all(&home)?;
+        Ok(())
+    }
+
+    #[test]
+    fn checkout_swaps_files_and_directories() -> Result<(), ErrorType> {
+        let repo_paths = init_repo("git_rustico_tests_checkout_file_dir")?;
+        let home = repo_paths.get_home();
+        commit_file(&repo_paths, "a.txt", "a")?;
+        Branch::branch_command(&repo_paths, vec!["dev".to_string()])?;
+        checkout(&repo_paths, &["dev"])?;
+        commit_file(&repo_paths, "dir", "file on dev")?;
+        commit_file(&repo_paths, "out.log", "dev")?;
+        checkout(&repo_paths, &["master"])?;
+        fs::create_dir_all(home.join("dir"))?;
+        commit_file(&repo_paths, "dir/x", "x")?;
+
+        // los archivos ignorados no frenan el checkout, se pisan
+        fs::write(home.join(".gitignore"), "*.log\n")?;
+        fs::write(home.join("out.log"), "local build output")?;
+        checkout(&repo_paths, &["dev"])?;
+        assert_eq!(fs::read_to_string(home.join("dir"))?, "file on dev");
+        assert_eq!(fs::read_to_string(home.join("out.log"))?, "dev");
+
+        checkout(&repo_paths, &["master"])?;
+        assert_eq!(fs::read_to_string(home.join("dir/x"))?, "x");
+        let status = Index::open(&repo_paths.get_index())?.status(&repo_paths)?;
+        assert!(status.staged.is_empty() && status.not_staged.is_empty());
+
+        fs::remove_dir_all(&home)?;
+        Ok(())
+    }
+
+    #[test]
+    fn commit_on_detached_head() -> Result<(), ErrorType> {
+        let repo_paths = init_repo("git_rustico_tests_detached")?;
+        let home = repo_paths.get_home();
+        commit_file(&repo_paths, "a.txt", "one")?;
+        let first = RevParse::resolve(&repo_paths, "HEAD")?;
+        commit_file(&repo_paths, "a.txt", "two")?;
+
+        checkout(&repo_paths, &[first.as_str()])?;
+        assert_eq!(fs::read_to_string(repo_paths.get_head())?, first.as_str());
+        assert_eq!(fs::read_to_string(home.join("a.txt"))?, "one");
+
+        commit_file(&repo_paths, "b.txt", "detached")?;
+        let head = BranchRef::open(repo_paths.get_head(), &repo_paths.get_refs_heads())?;
+        assert!(head.is_detached());
+        let detached = head.get_last_commit_hash();
+        assert_ne!(detached, Some(first));
+
+        // branch <name> guarda el trabajo hecho sobre el detached HEAD
+        Branch::branch_command(&repo_paths, vec!["rescue".to_string()])?;
+        checkout(&repo_paths, &["master"])?;
+        assert!(!home.join("b.txt").exists());
+        assert_eq!(fs::read_to_string(repo_paths.get_head())?, "master");
+        let rescue = Branch::open(&repo_paths.get_refs_heads(), "rescue")?;
+        assert_eq!(Some(rescue.get_last_commit_hash()), detached);
+
+        assert!(checkout(&repo_paths, &["nothing"]).is_err());
+
+        fs::remove_dir_all(&home)?;
+        Ok(())
+    }
+
+    #[test]
+    fn reset_modes() -> Result<(), ErrorType> {
+        let repo_paths = init_repo("git_rustico_tests_reset")?;
+        let home = repo_paths.get_home();
+
+        commit_file(&repo_paths, "a.txt", "a")?;
+        let head = BranchRef::open(repo_paths.get_head(), &repo_paths.get_refs_heads())?;
+        let first = head.get_last_commit_hash().ok_or(ErrorType::FormatError(
+            "the first commit was not created".to_string(),
+        ))?;
+        commit_file(&repo_paths, "b.txt", "b")?;
+        fs::write(home.join("untracked.txt"), "u")?;
+
+        let head_hash = |repo_paths: &RepoPaths| -> Result<Option<GitHash>, ErrorType> {
+            let head = BranchRef::open(repo_paths.get_head(), &repo_paths.get_refs_heads())?;
+            Ok(head.get_last_commit_hash())
+        };
+        let second = head_hash(&repo_paths)?.ok_or(ErrorType::FormatError(
+            "the second commit was not created".to_string(),
+        ))?;
+
+        // --soft: b.txt queda en el index como archivo nuevo
+        let args = vec!["--soft".to_string(), first.to_string()];
+        BranchRef::reset_command(repo_paths.clone(), args)?;
+        assert_eq!(head_hash(&repo_paths)?, Some(first.clone()));
+        let status = Index::open(&repo_paths.get_index())?.status(&repo_paths)?;
+        assert_eq!(status.staged.len(), 1);
+
+        // --mixed: b.txt sale del index pero sigue en el working directory
+        BranchRef::reset_command(repo_paths.clone(), vec![])?;
+        let status = Index::open(&repo_paths.get_index())?.status(&repo_paths)?;
+        assert!(status.staged.is_empty());
+        assert!(status.untracked.contains(&PathBuf::from("b.txt")));
+
+        // --hard: vuelve al segundo commit, sin tocar los archivos sin trackear
+        fs::write(home.join("a.txt"), "changed")?;
+        let args = vec!["--hard".to_string(), second.to_string()];
+        BranchRef::reset_command(repo_paths.clone(), args)?;
+        assert_eq!(head_hash(&repo_paths)?, Some(second));
+        assert_eq!(fs::read_to_string(home.join("a.txt"))?, "a");
+        assert!(home.join("b.txt").exists());
+        assert!(home.join("untracked.txt").exists());
+        let status = Index::open(&repo_paths.get_index())?.status(&repo_paths)?;
+        assert!(status.staged.is_empty() && status.not_staged.is_empty());
+
+        fs::remove_dir_all(&home)?;
+        Ok(())
+    }
+
+    #[test]
+    fn hard_reset_discards_a_conflicted_merge() -> Result<(), ErrorType> {
+        let repo_paths = init_repo("git_rustico_tests_reset_conflicts")?;
+        let home = repo_paths.get_home();
+
+        let first = commit_file(&repo_paths, "a.txt", "a")?;
+        Branch::branch_command(&repo_paths, vec!["dev".to_string()])?;
+        commit_file(&repo_paths, "f.txt", "master\n")?;
+        checkout(&repo_paths, &["dev"])?;
+        commit_file(&repo_paths, "f.txt", "dev\n")?;
+        checkout(&repo_paths, &["master"])?;
+        Merge::merge_command(repo_paths.clone(), vec!["dev".to_string()])?;
+        assert!(!Index::open(&repo_paths.get_index())?
+            .unmerged_paths()
+            .is_empty());
+        assert!(checkout(&repo_paths, &["dev"]).is_err());
+
+        // f.txt no esta en el commit del reset: se borra con sus marcas de conflicto
+        let args = vec!["--hard".to_string(), first.to_string()];
+        BranchRef::reset_command(repo_paths.clone(), args)?;
+        assert!(Index::open(&repo_paths.get_index())?
+            .unmerged_paths()
+            .is_empty());
+        assert!(!home.join("f.txt").exists());
+        assert!(!repo_paths.get_head_merge().exists());
+
+        let hash = commit_file(&repo_paths, "a.txt", "after reset")?;
+        let commit = GitObject::read_commit(&hash, &repo_paths.get_objects())?;
+        assert_eq!(commit.get_parents(), vec![first]);
+
+        fs::remove_dir_all(&home)?;
+        Ok(())
+    }
+}
@@ -14,8 +14,8 @@ const DEFAULT_REMOTE_PATH: &str = ".git-rustico/remote";
 const DEFAULT_REFS_REMOTE: &str = ".git-rustico/refs/remote/";
 const DEFAULT_LOG_FILE: &str = ".git-rustico/LOG";
 const DEFAULT_LOG_SERVER_FILE: &str = ".LOG_SERVER";
-const DEFAULT_REFS_TAGS: &str = ".git-rustico/refs/";
-const IGNORE_FILE: &str = ".gitignore";
+const DEFAULT_REFS_TAGS: &str = ".git-rustico/refs/tags";
+const DEFAULT_STASH_PATH: &str = ".git-rustico/refs/stash";
 
 #[derive(Clone)]
 pub struct RepoPaths {
@@ -86,11 +86,11 @@ impl RepoPaths {
         self.home.join(DEFAULT_LOG_SERVER_FILE)
     }
 
-    pub(crate) fn get_ignore(&self) -> std::path::PathBuf {
-        self.home.join(IGNORE_FILE)
-    }
-
     pub fn get_refs_tags(&self) -> PathBuf {
         self.home.join(DEFAULT_REFS_TAGS)
     }
+
+    pub fn get_stash(&self) -> PathBuf {
+        self.home.join(DEFAULT_STASH_PATH)
+    }
 }
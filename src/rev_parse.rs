@@ -0,0 +1,414 @@
+use crate::commit::Commit;
+use crate::files::object_type::ObjectType;
+use crate::git_errors::command_error::CommandError::{IncorrectAmount, InvalidArgument};
+use crate::git_errors::errors::ErrorType;
+use crate::git_object::GitObject;
+use crate::hash::GitHash;
+use crate::protocol::pack_index;
+use crate::refs::BranchRef;
+use crate::remote::Remote;
+use crate::repo_paths::RepoPaths;
+use crate::tag::Tag;
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::PathBuf;
+
+const HEAD: &str = "HEAD";
+const HEAD_SHORT: &str = "@";
+const UPSTREAM_SUFFIXES: [&str; 2] = ["@{upstream}", "@{u}"];
+const MIN_ABBREV_LEN: usize = 4;
+
+// Revisiones soportadas (ver `git help revisions`):
+//
+//   <hash>                      completo o abreviado (al menos 4 caracteres, tiene que ser unico)
+//   HEAD, @                     commit actual
+//   <branch>, <tag>             tambien refs/heads/<branch>, refs/tags/<tag>
+//   <remote>/<branch>           branch remota, tambien refs/remotes/<remote>/<branch>
+//   [<branch>]@{upstream}       upstream de la branch (de la actual si no se indica), o @{u}
+//   <rev>~<n>                   n-esimo ancestro siguiendo siempre el primer padre
+//   <rev>^<n>                   n-esimo padre, ^0 es el commit mismo
+//   <rev>^{<type>}, <rev>^{}    sigue los tags (y commit -> tree) hasta un object del tipo
+//
+// Ademas log y rev-parse aceptan rangos: <a>..<b>, <a>...<b> y ^<rev>
+
+/// Resuelve expresiones de revisiones a hashes de objects.
+pub struct RevParse;
+
+impl RevParse {
+    /// rev-parse <revision | range>...
+    ///
+    /// Imprime el hash de cada revision. Los rangos imprimen los commits incluidos y los
+    /// excluidos con `^` adelante, igual que git.
+    pub fn rev_parse_command(
+        args: Vec<String>,
+        repo_paths: &RepoPaths,
+    ) -> Result<String, ErrorType> {
+        if args.is_empty() {
+            return Err(ErrorType::CommandError(IncorrectAmount(
+                "at least 1".to_string(),
+                0,
+            )));
+        }
+        let mut lines = Vec::new();
+        for arg in args {
+            let (include, exclude) = Self::resolve_range(repo_paths, &arg)?;
+            lines.extend(include.iter().map(|hash| hash.to_string()));
+            lines.extend(exclude.iter().map(|hash| format!("^{hash}")));
+        }
+        let result = lines.join("\n");
+        println!("{}", result);
+        Ok(result)
+    }
+
+    /// Hash del object al que se refiere la revision (puede no ser un commit)
+    pub fn resolve(repo_paths: &RepoPaths, revision: &str) -> Result<GitHash, ErrorType> {
+        // el nombre termina en el primer operador, los nombres de refs no pueden tener ~ ni ^
+        let split = revision.find(['~', '^']).unwrap_or(revision.len());
+        let (name, mut suffix) = revision.split_at(split);
+        let mut hash = Self::resolve_name(repo_paths, name)?;
+
+        while let Some(operator) = suffix.chars().next() {
+            suffix = &suffix[operator.len_utf8()..];
+            if operator == '^' && suffix.starts_with('{') {
+                let end = suffix
+                    .find('}')
+                    .ok_or_else(|| Self::unknown_revision(revision))?;
+                hash = Self::peel(repo_paths, &hash, &suffix[1..end])?;
+                suffix = &suffix[end + 1..];
+                continue;
+            }
+            let digits = suffix.chars().take_while(|c| c.is_ascii_digit()).count();
+            let n = match digits {
+                0 => 1,
+                _ => suffix[..digits]
+                    .parse::<usize>()
+                    .map_err(|_| Self::unknown_revision(revision))?,
+            };
+            suffix = &suffix[digits..];
+
+            hash = match (operator, n) {
+                ('^', 0) => Self::peel(repo_paths, &hash, "commit")?,
+                ('^', n) => Self::parents(repo_paths, &hash)?
+                    .into_iter()
+                    .nth(n - 1)
+                    .ok_or_else(|| Self::unknown_revision(revision))?,
+                ('~', n) => {
+                    for _ in 0..n {
+                        hash = Self::parents(repo_paths, &hash)?
+                            .into_iter()
+                            .next()
+                            .ok_or_else(|| Self::unknown_revision(revision))?;
+                    }
+                    hash
+                }
+                _ => return Err(Self::unknown_revision(revision)),
+            };
+        }
+        Ok(hash)
+    }
+
+    /// Commit al que se refiere la revision, siguiendo los tags
+    pub fn resolve_commit(repo_paths: &RepoPaths, revision: &str) -> Result<Commit, ErrorType> {
+        let hash = Self::resolve(repo_paths, revision)?;
+        let hash = Self::peel(repo_paths, &hash, "commit")?;
+        GitObject::read_commit(&hash, &repo_paths.get_objects())
+    }
+
+    /// Commits incluidos y excluidos de un rango. `<a>..<b>` son los alcanzables desde b pero
+    /// no desde a, `<a>...<b>` los alcanzables desde uno solo de los dos. Si falta uno de los
+    /// lados se usa HEAD. Una revision sola se devuelve sin seguir los tags.
+    pub(crate) fn resolve_range(
+        repo_paths: &RepoPaths,
+        range: &str,
+    ) -> Result<(Vec<GitHash>, Vec<GitHash>), ErrorType> {
+        let commit_hash = |revision: &str| -> Result<GitHash, ErrorType> {
+            let revision = if revision.is_empty() { HEAD } else { revision };
+            Ok(Self::resolve_commit(repo_paths, revision)?.get_hash())
+        };
+        if let Some((from, to)) = range.split_once("...") {
+            let (from, to) = (commit_hash(from)?, commit_hash(to)?);
+            let path_objects = repo_paths.get_objects();
+            let from_commit = GitObject::read_commit(&from, &path_objects)?;
+            let to_commit = GitObject::read_commit(&to, &path_objects)?;
+            let bases = from_commit
+                .merge_bases(&to_commit, &path_objects)?
+                .iter()
+                .map(|base| base.get_hash())
+                .collect();
+            return Ok((vec![to, from], bases));
+        }
+        if let Some((from, to)) = range.split_once("..") {
+            return Ok((vec![commit_hash(to)?], vec![commit_hash(from)?]));
+        }
+        match range.strip_prefix('^') {
+            Some(revision) => Ok((Vec::new(), vec![commit_hash(revision)?])),
+            None => Ok((vec![Self::resolve(repo_paths, range)?], Vec::new())),
+        }
+    }
+
+    /// Sigue el object hasta uno del tipo pedido: los tags hasta lo que apuntan y los commits
+    /// hasta su tree. Con "" sigue los tags hasta el primer object que no es un tag.
+    pub(crate) fn peel(
+        repo_paths: &RepoPaths,
+        hash: &GitHash,
+        object_type: &str,
+    ) -> Result<GitHash, ErrorType> {
+        let path_objects = repo_paths.get_objects();
+        let (current_type, content) = GitObject::read_object(hash, &path_objects)?;
+        match object_type {
+            "" => return Tag::peel(hash, &path_objects),
+            "object" => return Ok(hash.clone()),
+            _ if current_type.to_string() == object_type => return Ok(hash.clone()),
+            _ => {}
+        }
+        match current_type {
+            ObjectType::Tag => {
+                let target = Tag::peel(hash, &path_objects)?;
+                Self::peel(repo_paths, &target, object_type)
+            }
+            ObjectType::Commit if object_type == "tree" => {
+                Ok(Self::commit_headers(hash, &content, "tree")?.remove(0))
+            }
+            _ => Err(ErrorType::ObjectType(
+                object_type.to_string(),
+                current_type.to_string(),
+            )),
+        }
+    }
+
+    // padres del commit (o del commit al que apunta el tag), sin leer el tree
+    fn parents(repo_paths: &RepoPaths, hash: &GitHash) -> Result<Vec<GitHash>, ErrorType> {
+        let hash = Self::peel(repo_paths, hash, "commit")?;
+        let (_, content) = GitObject::read_object(&hash, &repo_paths.get_objects())?;
+        Self::commit_headers(&hash, &content, "parent")
+    }
+
+    // valores de los headers `<name> <hash>` del commit, en orden
+    fn commit_headers(
+        hash: &GitHash,
+        content: &[u8],
+        name: &str,
+    ) -> Result<Vec<GitHash>, ErrorType> {
+        let content = String::from_utf8_lossy(content);
+        let mut values = Vec::new();
+        for line in content.lines().take_while(|line| !line.is_empty()) {
+            if let Some((header, value)) = line.split_once(' ') {
+                if header == name {
+                    values.push(GitHash::new(value)?);
+                }
+            }
+        }
+        if name == "tree" && values.is_empty() {
+            return Err(ErrorType::FormatError(format!(
+                "commit {hash} doesn't have a tree"
+            )));
+        }
+        Ok(values)
+    }
+
+    // nombre sin operadores: HEAD, upstream, hash o ref
+    fn resolve_name(repo_paths: &RepoPaths, name: &str) -> Result<GitHash, ErrorType> {
+        if name == HEAD || name == HEAD_SHORT {
+            let head = BranchRef::open(repo_paths.get_head(), &repo_paths.get_refs_heads())?;
+            return head
+                .get_last_commit_hash()
+                .ok_or(ErrorType::RepositoryError(
+                    "your current branch doesnt have commits yet".to_string(),
+                ));
+        }
+        if let Some(branch) = UPSTREAM_SUFFIXES
+            .iter()
+            .find_map(|suffix| name.strip_suffix(suffix))
+        {
+            return Self::resolve_upstream(repo_paths, branch);
+        }
+        if name.len() == 40 {
+            if let Ok(hash) = GitHash::new(name) {
+                return Ok(hash);
+            }
+        }
+        for path in Self::ref_candidates(repo_paths, name)? {
+            if path.is_file() {
+                return GitHash::new(fs::read_to_string(path)?.trim());
+            }
+        }
+        if let Some(hash) = Self::resolve_abbreviated(repo_paths, name)? {
+            return Ok(hash);
+        }
+        Err(Self::unknown_revision(name))
+    }
+
+    // archivos donde puede estar guardada la ref, en orden de prioridad
+    fn ref_candidates(repo_paths: &RepoPaths, name: &str) -> Result<Vec<PathBuf>, ErrorType> {
+        if name.is_empty() || name.starts_with('/') {
+            return Ok(Vec::new());
+        }
+        let path_heads = repo_paths.get_refs_heads();
+        let path_tags = repo_paths.get_refs_tags();
+        let mut candidates = Vec::new();
+        if let Some(branch) = name.strip_prefix("refs/heads/") {
+            candidates.push(path_heads.join(branch));
+        }
+        if let Some(tag) = name.strip_prefix("refs/tags/") {
+            candidates.push(path_tags.join(tag));
+        }
+        candidates.push(path_heads.join(name));
+        candidates.push(path_tags.join(name));
+
+        // las branches remotas se guardan sin el nombre del remote (refs/remote/<branch>)
+        let remote_name = name.strip_prefix("refs/remotes/").unwrap_or(name);
+        if let Some((remote, branch)) = remote_name.split_once('/') {
+            if repo_paths.get_remote().exists()
+                && Remote::get_remotes(repo_paths.get_remote())?.contains_key(remote)
+            {
+                candidates.push(repo_paths.get_refs_remote().join(branch));
+            }
+        }
+        Ok(candidates)
+    }
+
+    fn resolve_upstream(repo_paths: &RepoPaths, branch: &str) -> Result<GitHash, ErrorType> {
+        let branch = match branch {
+            "" | HEAD | HEAD_SHORT => {
+                let head = BranchRef::open(repo_paths.get_head(), &repo_paths.get_refs_heads())?;
+                head.get_branch_name().ok_or(ErrorType::RepositoryError(
+                    "HEAD does not point to a branch".to_string(),
+                ))?
+            }
+            branch => branch.to_string(),
+        };
+        let upstream = match repo_paths.get_remote().exists() {
+            true => Remote::get_upstream(repo_paths.get_remote(), branch.clone())?,
+            false => None,
+        };
+        let Some((_, remote_branch)) = upstream else {
+            return Err(ErrorType::RepositoryError(format!(
+                "no upstream configured for branch '{branch}'"
+            )));
+        };
+        let path = repo_paths.get_refs_remote().join(&remote_branch);
+        if !path.is_file() {
+            return Err(ErrorType::RepositoryError(format!(
+                "upstream branch '{remote_branch}' of '{branch}' doesn't exist, fetch first"
+            )));
+        }
+        GitHash::new(fs::read_to_string(path)?.trim())
+    }
+
+    // hash abreviado, busca entre los objects sueltos y los de los packfiles
+    fn resolve_abbreviated(
+        repo_paths: &RepoPaths,
+        prefix: &str,
+    ) -> Result<Option<GitHash>, ErrorType> {
+        if prefix.len() < MIN_ABBREV_LEN
+            || prefix.len() > 40
+            || !prefix.chars().all(|c| c.is_ascii_hexdigit())
+        {
+            return Ok(None);
+        }
+        let prefix = prefix.to_ascii_lowercase();
+        let path_objects = repo_paths.get_objects();
+        let mut matches = BTreeSet::new();
+
+        let (dir, file_prefix) = prefix.split_at(2);
+        let path_dir = path_objects.join(dir);
+        if path_dir.is_dir() {
+            for entry in fs::read_dir(path_dir)? {
+                let file_name = entry?.file_name().to_string_lossy().to_string();
+                if file_name.starts_with(file_prefix) {
+                    if let Ok(hash) = GitHash::new(&format!("{dir}{file_name}")) {
+                        matches.insert(hash.to_string());
+                    }
+                }
+            }
+        }
+        for hash in pack_index::list_objects(&path_objects)? {
+            if hash.as_str().starts_with(&prefix) {
+                matches.insert(hash.to_string());
+            }
+        }
+
+        match matches.len() {
+            0 => Ok(None),
+            1 => Ok(matches
+                .pop_first()
+                .map(|hash| GitHash::new(&hash))
+                .transpose()?),
+            _ => Err(ErrorType::CommandError(InvalidArgument(format!(
+                "short object ID {} is ambiguous, candidates are: {}",
+                prefix,
+                matches.into_iter().collect::<Vec<String>>().join(", ")
+            )))),
+        }
+    }
+
+    fn unknown_revision(revision: &str) -> ErrorType {
+        ErrorType::CommandError(InvalidArgument(format!("unknown revision '{revision}'")))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::branch::Branch;
+    use crate::merge::Merge;
+    use crate::test_utils::{commit_file, init_repo};
+
+    #[test]
+    fn ancestors_parents_and_ranges() -> Result<(), ErrorType> {
+        let repo_paths = init_repo("git_rustico_tests_rev_parse")?;
+        let first = commit_file(&repo_paths, "a.txt", "1")?;
+        let second = commit_file(&repo_paths, "a.txt", "2")?;
+        Branch::branch_command(&repo_paths, vec!["dev".to_string()])?;
+        let third = commit_file(&repo_paths, "a.txt", "3")?;
+        BranchRef::checkout_command(repo_paths.clone(), vec!["dev".to_string()])?;
+        let dev = commit_file(&repo_paths, "b.txt", "dev")?;
+        BranchRef::checkout_command(repo_paths.clone(), vec!["master".to_string()])?;
+        Merge::merge_command(repo_paths.clone(), vec!["dev".to_string()])?;
+
+        let resolve = |revision: &str| RevParse::resolve(&repo_paths, revision);
+        assert_eq!(resolve("master^1")?, third);
+        assert_eq!(resolve("master^2")?, dev);
+        assert_eq!(resolve("@~2")?, second);
+        assert_eq!(resolve("HEAD~1~1^")?, first);
+        assert_eq!(resolve("dev~")?, second);
+        assert_eq!(resolve("refs/heads/dev^0")?, dev);
+        assert_eq!(resolve(&first.as_str()[..7])?, first);
+        assert!(resolve("HEAD~4").is_err());
+        assert!(resolve("master^3").is_err());
+        assert!(resolve("nothing").is_err());
+        assert!(resolve("HEAD^é").is_err());
+        assert!(resolve("HEAD~2é").is_err());
+
+        let tree = resolve("dev^{tree}")?;
+        assert_eq!(
+            tree,
+            GitObject::read_commit(&dev, &repo_paths.get_objects())?.get_tree_hash()
+        );
+
+        Tag::command_tag(
+            vec![
+                "v1".to_string(),
+                "-m".to_string(),
+                "one".to_string(),
+                "HEAD~2".to_string(),
+            ],
+            &repo_paths,
+        )?;
+        assert_ne!(resolve("v1")?, second);
+        assert_eq!(resolve("v1^{commit}")?, second);
+        assert_eq!(resolve("v1^{}")?, second);
+        assert_eq!(resolve("v1~1")?, first);
+
+        let (include, exclude) = RevParse::resolve_range(&repo_paths, "dev..master")?;
+        assert_eq!(
+            (include, exclude),
+            (vec![resolve("HEAD")?], vec![dev.clone()])
+        );
+        let (include, exclude) = RevParse::resolve_range(&repo_paths, "dev...master~1")?;
+        assert_eq!((include, exclude), (vec![third, dev], vec![second]));
+
+        fs::remove_dir_all(repo_paths.get_home())?;
+        Ok(())
+    }
+}
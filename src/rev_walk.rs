@@ -0,0 +1,407 @@
+use crate::commit::Commit;
+use crate::commit_graph::{CommitGraph, GraphEntry, GENERATION_INFINITY};
+use crate::git_errors::errors::ErrorType;
+use crate::git_object::GitObject;
+use crate::hash::GitHash;
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+/// Orden en que `RevWalk` devuelve los commits
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum WalkOrder {
+    /// Por fecha del committer, el mas nuevo primero
+    Date,
+    /// Ningun commit aparece antes que sus hijos, entre los disponibles va el mas nuevo
+    Topological,
+}
+
+// (fecha, orden de llegada, hash): sale primero el mas nuevo y, con la misma fecha, el que
+// se agrego antes
+type QueueEntry = (i64, Reverse<usize>, GitHash);
+
+// marcas para calcular las bases de un merge
+const FROM_ONE: u8 = 1;
+const FROM_OTHER: u8 = 2;
+const STALE: u8 = 4;
+const RESULT: u8 = 8;
+
+/// Recorre la historia desde los commits agregados con `push`, sin los alcanzables desde los
+/// agregados con `hide` (como `git log <push> ^<hide>`).
+///
+/// Es iterativo (una cola de prioridad por fecha y un set de visitados) y guarda los commits
+/// leidos, asi que se puede volver a usar con `reset` sin leerlos de nuevo. Los parents y
+/// fechas salen del commit-graph cuando el commit esta ahi, y los commits no cargan su tree
+/// hasta que se lo pide.
+pub struct RevWalk {
+    path_objects: PathBuf,
+    order: WalkOrder,
+    graph: CommitGraph,
+    // commits que no estan en el commit-graph
+    entries: HashMap<GitHash, GraphEntry>,
+    cache: HashMap<GitHash, Commit>,
+    tips: Vec<GitHash>,
+    hidden: Vec<GitHash>,
+    started: bool,
+    queue: BinaryHeap<QueueEntry>,
+    queued: usize,
+    seen: HashSet<GitHash>,
+    // solo en orden topologico: hijos de cada commit que todavia no salieron
+    pending_children: HashMap<GitHash, usize>,
+}
+
+impl RevWalk {
+    pub fn new(path_objects: &Path) -> Self {
+        Self {
+            path_objects: path_objects.to_path_buf(),
+            order: WalkOrder::Date,
+            // el commit-graph es solo para ir mas rapido, si no se puede leer se usan los objects
+            graph: CommitGraph::open(path_objects).unwrap_or_default(),
+            entries: HashMap::new(),
+            cache: HashMap::new(),
+            tips: Vec::new(),
+            hidden: Vec::new(),
+            started: false,
+            queue: BinaryHeap::new(),
+            queued: 0,
+            seen: HashSet::new(),
+            pending_children: HashMap::new(),
+        }
+    }
+
+    pub fn set_order(&mut self, order: WalkOrder) {
+        self.order = order;
+    }
+
+    /// Agrega un commit desde el que se recorre la historia
+    pub fn push(&mut self, hash: GitHash) {
+        self.tips.push(hash);
+    }
+
+    /// Excluye el commit y todos sus ancestros
+    pub fn hide(&mut self, hash: GitHash) {
+        self.hidden.push(hash);
+    }
+
+    /// Vuelve a empezar sin commits agregados, mantiene los commits ya leidos
+    pub fn reset(&mut self) {
+        self.tips.clear();
+        self.hidden.clear();
+        self.started = false;
+        self.queue.clear();
+        self.queued = 0;
+        self.seen.clear();
+        self.pending_children.clear();
+    }
+
+    /// Todos los commits que quedan por recorrer
+    pub fn commits(&mut self) -> Result<Vec<Commit>, ErrorType> {
+        self.by_ref().collect()
+    }
+
+    /// Hashes de todos los commits que quedan por recorrer, sin leer los que estan en el
+    /// commit-graph
+    pub fn hashes(&mut self) -> Result<Vec<GitHash>, ErrorType> {
+        let mut hashes = Vec::new();
+        while let Some(hash) = self.next_hash()? {
+            hashes.push(hash);
+        }
+        Ok(hashes)
+    }
+
+    /// Si ancestor es alcanzable desde descendant (o es el mismo commit)
+    pub fn is_ancestor(
+        &mut self,
+        ancestor: &GitHash,
+        descendant: &GitHash,
+    ) -> Result<bool, ErrorType> {
+        let generation = self.entry(ancestor)?.get_generation();
+        let mut visited = HashSet::new();
+        let mut pending = vec![descendant.clone()];
+        while let Some(hash) = pending.pop() {
+            if hash == *ancestor {
+                return Ok(true);
+            }
+            if !visited.insert(hash.clone()) {
+                continue;
+            }
+            let entry = self.entry(&hash)?;
+            // los ancestros tienen generacion menor, no hace falta bajar mas alla de la de
+            // ancestor. Un commit que no esta en el commit-graph no es ancestro de uno que si
+            if entry.get_generation() != GENERATION_INFINITY && entry.get_generation() <= generation
+            {
+                continue;
+            }
+            pending.extend(entry.get_parents().iter().cloned());
+        }
+        Ok(false)
+    }
+
+    /// Mejores ancestros comunes de los dos commits: los comunes que no son ancestros de otro
+    /// comun, del mas nuevo al mas viejo.
+    ///
+    /// Baja desde los dos commits marcando de cual viene cada ancestro, primero los de mayor
+    /// generacion. Los ancestros de un comun ya no pueden ser base y se deja de bajar cuando
+    /// solo quedan esos.
+    pub fn merge_bases(
+        &mut self,
+        one: &GitHash,
+        other: &GitHash,
+    ) -> Result<Vec<GitHash>, ErrorType> {
+        if one == other {
+            return Ok(vec![one.clone()]);
+        }
+        self.common_ancestors(std::slice::from_ref(one), std::slice::from_ref(other))
+    }
+
+    /// Como merge_bases pero entre dos grupos de commits: los mejores ancestros de algun
+    /// commit de cada grupo. Sirve para la base de un merge virtual, que no esta en objects
+    /// pero tiene como ancestros a los commits reales que junta.
+    pub(crate) fn common_ancestors(
+        &mut self,
+        ones: &[GitHash],
+        others: &[GitHash],
+    ) -> Result<Vec<GitHash>, ErrorType> {
+        let mut flags: HashMap<GitHash, u8> = HashMap::new();
+        let mut queue = BinaryHeap::new();
+        let mut queued = 0;
+        for (hashes, flag) in [(ones, FROM_ONE), (others, FROM_OTHER)] {
+            for hash in hashes {
+                let hash_flags = flags.entry(hash.clone()).or_default();
+                let first = *hash_flags == 0;
+                *hash_flags |= flag;
+                if !first {
+                    continue;
+                }
+                let entry = self.entry(hash)?;
+                queue.push((
+                    entry.get_generation(),
+                    entry.get_date(),
+                    Reverse(queued),
+                    hash.clone(),
+                ));
+                queued += 1;
+            }
+        }
+
+        let mut candidates = Vec::new();
+        while queue.iter().any(|(_, _, _, hash)| flags[hash] & STALE == 0) {
+            let Some((_, _, _, hash)) = queue.pop() else {
+                break;
+            };
+            let mut current = flags[&hash] & (FROM_ONE | FROM_OTHER | STALE);
+            if current & (FROM_ONE | FROM_OTHER) == FROM_ONE | FROM_OTHER {
+                if flags[&hash] & RESULT == 0 {
+                    flags.insert(hash.clone(), flags[&hash] | RESULT);
+                    candidates.push(hash.clone());
+                }
+                current |= STALE;
+            }
+            for parent in self.entry(&hash)?.get_parents().to_vec() {
+                let parent_flags = flags.entry(parent.clone()).or_default();
+                if *parent_flags & current == current {
+                    continue;
+                }
+                *parent_flags |= current;
+                let entry = self.entry(&parent)?;
+                queue.push((
+                    entry.get_generation(),
+                    entry.get_date(),
+                    Reverse(queued),
+                    parent,
+                ));
+                queued += 1;
+            }
+        }
+
+        // sin commit-graph se ordena por fecha y un candidato puede ser ancestro de otro
+        candidates.retain(|hash| flags[hash] & STALE == 0);
+        let mut bases = Vec::new();
+        for candidate in &candidates {
+            let mut redundant = false;
+            for other in &candidates {
+                if other != candidate && self.is_ancestor(candidate, other)? {
+                    redundant = true;
+                    break;
+                }
+            }
+            if !redundant {
+                bases.push(candidate.clone());
+            }
+        }
+        let mut dates = HashMap::new();
+        for hash in &bases {
+            dates.insert(hash.clone(), self.entry(hash)?.get_date());
+        }
+        bases.sort_by_key(|hash| Reverse(dates[hash]));
+        Ok(bases)
+    }
+
+    /// Commit leido de objects, o del cache si ya se leyo
+    pub(crate) fn read(&mut self, hash: &GitHash) -> Result<&Commit, ErrorType> {
+        if !self.cache.contains_key(hash) {
+            let commit = GitObject::read_commit(hash, &self.path_objects)?;
+            self.cache.insert(hash.clone(), commit);
+        }
+        Ok(&self.cache[hash])
+    }
+
+    // parents, fecha y generacion del commit, del commit-graph o leyendo el commit
+    fn entry(&mut self, hash: &GitHash) -> Result<&GraphEntry, ErrorType> {
+        if self.graph.get(hash).is_none() && !self.entries.contains_key(hash) {
+            let entry = GraphEntry::from_commit(self.read(hash)?, GENERATION_INFINITY);
+            self.entries.insert(hash.clone(), entry);
+        }
+        match self.graph.get(hash) {
+            Some(entry) => Ok(entry),
+            None => Ok(&self.entries[hash]),
+        }
+    }
+
+    fn parents(&mut self, hash: &GitHash) -> Result<Vec<GitHash>, ErrorType> {
+        Ok(self.entry(hash)?.get_parents().to_vec())
+    }
+
+    fn start(&mut self) -> Result<(), ErrorType> {
+        self.started = true;
+
+        // los ancestros de los commits ocultos se marcan como vistos para no recorrerlos
+        let mut pending = std::mem::take(&mut self.hidden);
+        while let Some(hash) = pending.pop() {
+            if self.seen.insert(hash.clone()) {
+                pending.extend(self.parents(&hash)?);
+            }
+        }
+
+        let tips = std::mem::take(&mut self.tips);
+        if self.order == WalkOrder::Topological {
+            self.count_children(&tips)?;
+        }
+        for tip in tips {
+            if !self.pending_children.contains_key(&tip) {
+                self.enqueue(tip)?;
+            }
+        }
+        Ok(())
+    }
+
+    // cuantos hijos tiene cada commit entre los que se van a recorrer
+    fn count_children(&mut self, tips: &[GitHash]) -> Result<(), ErrorType> {
+        let mut visited = HashSet::new();
+        let mut pending = tips.to_vec();
+        while let Some(hash) = pending.pop() {
+            if self.seen.contains(&hash) || !visited.insert(hash.clone()) {
+                continue;
+            }
+            for parent in self.parents(&hash)? {
+                if !self.seen.contains(&parent) {
+                    *self.pending_children.entry(parent.clone()).or_default() += 1;
+                    pending.push(parent);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn enqueue(&mut self, hash: GitHash) -> Result<(), ErrorType> {
+        if !self.seen.insert(hash.clone()) {
+            return Ok(());
+        }
+        let date = self.entry(&hash)?.get_date();
+        self.queue.push((date, Reverse(self.queued), hash));
+        self.queued += 1;
+        Ok(())
+    }
+
+    fn next_hash(&mut self) -> Result<Option<GitHash>, ErrorType> {
+        if !self.started {
+            self.start()?;
+        }
+        let Some((_, _, hash)) = self.queue.pop() else {
+            return Ok(None);
+        };
+        for parent in self.parents(&hash)? {
+            if let Some(children) = self.pending_children.get_mut(&parent) {
+                // en orden topologico el padre sale recien despues de todos sus hijos
+                *children -= 1;
+                if *children > 0 {
+                    continue;
+                }
+            }
+            self.enqueue(parent)?;
+        }
+        Ok(Some(hash))
+    }
+}
+
+impl Iterator for RevWalk {
+    type Item = Result<Commit, ErrorType>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.next_hash() {
+            Ok(Some(hash)) => Some(self.read(&hash).cloned()),
+            Ok(None) => None,
+            Err(error) => Some(Err(error)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{commit_with_parents, init_repo};
+    use std::fs;
+
+    fn messages(walk: &mut RevWalk) -> Result<Vec<String>, ErrorType> {
+        let commits = walk.commits()?;
+        Ok(commits.iter().map(|commit| commit.get_message()).collect())
+    }
+
+    #[test]
+    fn date_and_topological_order() -> Result<(), ErrorType> {
+        let repo_paths = init_repo("git_rustico_tests_rev_walk")?;
+        let path_objects = repo_paths.get_objects();
+
+        //   a - b - d - m
+        //    \         /
+        //     c ------+
+        let a = commit_with_parents(&path_objects, &[], "a")?.get_hash();
+        let b = commit_with_parents(&path_objects, &[&a], "b")?.get_hash();
+        let c = commit_with_parents(&path_objects, &[&a], "c")?.get_hash();
+        let d = commit_with_parents(&path_objects, &[&b], "d")?.get_hash();
+        let m = commit_with_parents(&path_objects, &[&d, &c], "m")?.get_hash();
+
+        let mut walk = RevWalk::new(&path_objects);
+        walk.push(m.clone());
+        assert_eq!(messages(&mut walk)?, ["m", "d", "c", "b", "a"]);
+
+        walk.reset();
+        walk.set_order(WalkOrder::Topological);
+        walk.push(a.clone());
+        walk.push(m.clone());
+        let order = messages(&mut walk)?;
+        assert_eq!(order.len(), 5);
+        assert_eq!(order.first().map(|m| m.as_str()), Some("m"));
+        assert_eq!(order.last().map(|m| m.as_str()), Some("a"));
+        let position = |name: &str| order.iter().position(|m| m == name);
+        assert!(position("d") < position("b"));
+
+        walk.reset();
+        walk.push(m.clone());
+        walk.hide(b.clone());
+        assert_eq!(messages(&mut walk)?, ["m", "d", "c"]);
+
+        // con el commit-graph el recorrido y las consultas dan lo mismo
+        CommitGraph::update(&path_objects, std::slice::from_ref(&m))?;
+        let mut walk = RevWalk::new(&path_objects);
+        walk.push(m.clone());
+        assert_eq!(messages(&mut walk)?, ["m", "d", "c", "b", "a"]);
+        assert!(walk.is_ancestor(&a, &m)?);
+        assert!(!walk.is_ancestor(&c, &d)?);
+        assert_eq!(walk.merge_bases(&d, &c)?, [a]);
+        assert_eq!(walk.merge_bases(&m, &b)?, [b]);
+
+        fs::remove_dir_all(repo_paths.get_home())?;
+        Ok(())
+    }
+}
@@ -1,12 +1,14 @@
 use git_rustico::branch::Branch;
+use git_rustico::commit_graph::CommitGraph;
+use git_rustico::config::RepoConfig;
+use git_rustico::gc::Gc;
 use git_rustico::git_errors::errors::ErrorType;
-use git_rustico::git_object::GitObject;
 use git_rustico::hash::GitHash;
 use git_rustico::http::http_protocol::HTTPRequest;
 use git_rustico::init::git_init;
 use git_rustico::log_file::{send_info_from_server, LogFile};
 use git_rustico::network_commands::get_packfile_objects;
-use git_rustico::protocol::pack_file::{build_packfile, read_packfile};
+use git_rustico::protocol::pack_file::{build_packfile, save_packfile};
 use git_rustico::protocol::pkt_line::{self, create_pkt_line, read_pkt_line};
 use git_rustico::refs::BranchRef;
 use git_rustico::repo_paths::RepoPaths;
@@ -18,7 +20,7 @@ use std::collections::HashSet;
 use std::fs;
 // use std::fs;
 use std::io::{BufReader, Read, Write};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::sync::mpsc::Sender;
 use std::sync::{mpsc, RwLock};
 use std::sync::{Arc, Mutex};
@@ -40,6 +42,7 @@ const TYPE_MESSAGE_INFO: &str = "INFO";
 const USER_CLIENT: &str = "CLIENT";
 const USER_BASE: &str = "SERVER";
 const USER_LISTENER: &str = "LISTENER";
+const CONSOLE_COMMAND_GC: &str = "gc ";
 
 fn main() {
     use std::env;
@@ -74,18 +77,26 @@ fn main() {
     // hilo de aceptacion de clientes
     let accept_new_clients = Arc::new(Mutex::new(true));
     let accept_new_clients_for_listener = accept_new_clients.clone();
+
+    // bloqueador de repositorios esto debe evitar que dos clientes accedan al mismo repo a la vez
+    let repository_permission: Arc<RwLock<RepositoryAccessPermission>> = Arc::new(RwLock::new(
+        RepositoryAccessPermission::init_repository_access_permission(),
+    ));
+    let repository_permission_for_listener = repository_permission.clone();
+    let base_path_for_listener = base_path.clone();
     let thread = thread::spawn(move || {
         server_listener(
             accept_new_clients_for_listener,
             sender_permission_to_clients,
-            base_path,
+            base_path_for_listener,
+            repository_permission_for_listener,
         );
     });
 
     // comandos para servidor
     loop {
-        let command = match input_by_console() {
-            Ok(input) => input.to_lowercase(),
+        let input = match input_by_console() {
+            Ok(input) => input,
             Err(_) => {
                 let error_message = "Error in read input command".to_string();
                 println!("{}", error_message.clone());
@@ -98,12 +109,28 @@ fn main() {
                 break;
             }
         };
+        let command = input.to_lowercase();
         send_info_from_server(
             TYPE_MESSAGE_CONSOLE.to_string(),
             format!("Receive command {}.", command),
             USER_BASE.to_string(),
             &sender_permission,
         );
+        if let Some(repo) = input.strip_prefix(CONSOLE_COMMAND_GC) {
+            let (message_type, message) =
+                match gc_repository(&base_path, repo.trim(), &repository_permission) {
+                    Ok(result) => (TYPE_MESSAGE_CONSOLE, result),
+                    Err(e) => (TYPE_MESSAGE_CONSOLE_ERROR, e.to_string()),
+                };
+            println!("{}", message);
+            send_info_from_server(
+                message_type.to_string(),
+                message,
+                USER_BASE.to_string(),
+                &sender_permission,
+            );
+            continue;
+        }
         if command == *"quit" || command == *"q" {
             let mut new_clients = match accept_new_clients.lock() {
                 Ok(new_clients) => new_clients,
@@ -145,6 +172,35 @@ fn main() {
     println!("{}", finish_message);
 }
 
+// gc del repositorio del servidor, con el lock de escritura para que no haya clientes usandolo
+fn gc_repository(
+    base_path: &Path,
+    repo: &str,
+    repository_permission: &Arc<RwLock<RepositoryAccessPermission>>,
+) -> Result<String, ErrorType> {
+    let server_repo_path = base_path.join(repo);
+    let server_paths = ServerPaths::new(&server_repo_path);
+    if repo.is_empty() || !server_paths.get_repo_path().join(".git-rustico").exists() {
+        return Err(ErrorType::RepositoryError(format!(
+            "repository '{}' not found in base path",
+            repo
+        )));
+    }
+    let lock_repository = get_permision_for_reposiory_from_repository_access_permission(
+        repository_permission,
+        server_repo_path,
+    )?;
+    let _write_guard = match lock_repository.write() {
+        Ok(guard) => guard,
+        Err(_) => {
+            return Err(ErrorType::ConfigError(
+                "Error in lock write repository.".to_string(),
+            ))
+        }
+    };
+    Gc::gc_command(&server_paths.get_repo_paths()?, Vec::new())
+}
+
 fn create_empty_repo(server_path: PathBuf, arg: String) -> Result<(), ErrorType> {
     let repo_path = server_path.join(arg);
 
@@ -176,6 +232,7 @@ fn server_listener(
     accept_new_clients: Arc<Mutex<bool>>,
     sender: Arc<Mutex<Sender<String>>>,
     base_path: PathBuf,
+    repository_permission: Arc<RwLock<RepositoryAccessPermission>>,
 ) {
     let config_path = base_path.join(".config");
     let port = match read_config_file(config_path) {
@@ -208,12 +265,6 @@ fn server_listener(
         &sender,
     );
 
-    // bloqueador de repositorios esto debe evitar que dos clientes accedan al mismo repo a la vez
-    let repository_permission: Arc<RwLock<RepositoryAccessPermission>> = Arc::new(RwLock::new(
-        RepositoryAccessPermission::init_repository_access_permission(),
-    ));
-    //
-
     let mut threads = Vec::new();
     for stream in listener.incoming() {
         let new_clients = match accept_new_clients.lock() {
@@ -467,7 +518,8 @@ fn upload_pack(stream: &mut TcpStream, repo_paths: RepoPaths) -> Result<(), Erro
     let packfile_objects =
         get_packfile_objects(commits_to_update, HashSet::new(), &repo_paths.get_objects())?;
     println!("Enumerating objects: {}", packfile_objects.len());
-    let packfile = build_packfile(packfile_objects)?;
+    let options = RepoConfig::open(repo_paths.get_config())?.get_pack_options();
+    let packfile = build_packfile(packfile_objects, options)?;
 
     stream.write_all(create_pkt_line("NAK")?.as_bytes())?;
     stream.write_all(&packfile)?;
@@ -481,6 +533,11 @@ fn receive_pack(stream: &mut TcpStream, repo_paths: RepoPaths) -> Result<(), Err
 
     let commands = read_commands(stream)?;
 
+    // the packfile is read (and its checksum verified) before touching any ref
+    let mut reader = BufReader::new(stream);
+    // todo : verify valid objects are being received and that all commands are satisfied
+    save_packfile(&mut reader, &repo_paths.get_objects())?;
+
     let mut head_command: Option<(GitHash, GitHash, String)> = None;
 
     //todo : usarlo para verificar que lleguen todos los objetos necesarios
@@ -489,6 +546,8 @@ fn receive_pack(stream: &mut TcpStream, repo_paths: RepoPaths) -> Result<(), Err
     let mut head = BranchRef::open(repo_paths.get_head(), &repo_paths.get_refs_heads())?;
 
     let server_refs = Branch::list_branches(&repo_paths.get_refs_heads())?;
+    let tips: Vec<GitHash> = server_refs.values().cloned().collect();
+    CommitGraph::update(&repo_paths.get_objects(), &tips)?;
 
     if let Some(ref head_command) = head_command {
         if let Some(branch_name) = head.get_branch_name() {
@@ -504,22 +563,6 @@ fn receive_pack(stream: &mut TcpStream, repo_paths: RepoPaths) -> Result<(), Err
         }
     }
 
-    let mut reader = BufReader::new(stream);
-    let packfile_objects = read_packfile(&mut reader)?;
-
-    for (object_type, content) in packfile_objects {
-        // todo : verify valid objects are being received and that all commands are satisfied
-        // match object_type{
-        //     ObjectType::Commit => {
-        //         let hash = GitHash::hash_commit(&content);
-        //         let commit = Commit::from_object(&hash, content, &repo_paths.get_objects())?;
-        //     },
-        //     ObjectType::Blob => todo!(),
-        //     ObjectType::Tree => todo!(),
-        // }
-        GitObject::save_object(content, object_type, &repo_paths.get_objects())?;
-    }
-
     reader.get_mut().shutdown(std::net::Shutdown::Both)?;
 
     Ok(())
@@ -555,7 +598,7 @@ fn commits_to_update(
     Ok(commits_to_update)
 }
 
-fn read_commands(stream: &mut TcpStream) -> Result<Vec<(GitHash, GitHash, String)>, ErrorType> {
+fn read_commands<R: Read>(stream: &mut R) -> Result<Vec<(GitHash, GitHash, String)>, ErrorType> {
     let mut commands = Vec::new();
 
     while let Some(line) = read_pkt_line(stream)? {
@@ -569,22 +612,26 @@ fn read_commands(stream: &mut TcpStream) -> Result<Vec<(GitHash, GitHash, String
         let previous_hash = GitHash::new(parts[0])?;
         let new_hash = GitHash::new(parts[1])?;
 
-        let mut ref_name = match parts[2].split_once('\0') {
+        let ref_name = match parts[2].split_once('\0') {
             Some((n, _)) => n,
             None => parts[2],
-        };
-
-        let ref_parts: Vec<&str> = ref_name.trim().split('/').collect();
+        }
+        .trim();
 
-        if ref_parts.len() == 1 && ref_parts[0] == "HEAD" {
-            ref_name = "HEAD";
-        } else if ref_parts.len() != 3 || ref_parts.first() != Some(&"refs") {
+        // branch names may be hierarchical (refs/heads/feature/login)
+        let ref_name = match ref_name.strip_prefix("refs/heads/") {
+            _ if ref_name == "HEAD" => ref_name,
+            Some(branch_name) if !branch_name.is_empty() => branch_name,
             // tags are unsupported yet
-            return Err(ErrorType::ProtocolError(format!(
-                "invalid reference: {ref_name}"
-            )));
-        } else {
-            ref_name = ref_parts[2];
+            _ => {
+                return Err(ErrorType::ProtocolError(format!(
+                    "invalid reference: {ref_name}"
+                )))
+            }
+        };
+        // the name is joined onto refs/heads, it can't point outside of it
+        if ref_name != "HEAD" {
+            Branch::validate_name(ref_name)?;
         }
         commands.push((previous_hash, new_hash, ref_name.to_string()));
     }
@@ -625,3 +672,31 @@ fn send_finish_conecction() -> Result<(), ErrorType> {
     let _ = TcpStream::connect(address)?;
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn commands(ref_name: &str) -> Result<Vec<(GitHash, GitHash, String)>, ErrorType> {
+        let zero = "0".repeat(40);
+        let hash = "10500012fca9b4425b50de67a7258a12cba0c076";
+        let mut lines = create_pkt_line(&format!("{zero} {hash} {ref_name}\0report-status"))?;
+        lines.push_str("0000");
+        read_commands(&mut lines.as_bytes())
+    }
+
+    #[test]
+    fn pushed_refs_must_stay_inside_refs_heads() -> Result<(), ErrorType> {
+        let received = commands("refs/heads/feature/login")?;
+        assert_eq!(received[0].2, "feature/login");
+
+        for ref_name in [
+            "refs/heads/../../../other/.git-rustico/refs/heads/master",
+            "refs/heads//master",
+            "refs/heads/a/./b",
+        ] {
+            assert!(commands(ref_name).is_err(), "{ref_name}");
+        }
+        Ok(())
+    }
+}
@@ -86,25 +86,22 @@ impl ShowRef {
         }
     }
 
+    // los tags anotados agregan la linea <name>^{} con el object al que apunta el tag
     fn references_tags(&self) -> Result<Option<String>, ErrorType> {
-        let tags = Tag::get_tags_for_show_refs(&self.repo_paths)?;
-        if tags.is_empty() {
-            return Ok(None);
-        }
-        let mut result = Vec::new();
-        match self.hash {
-            true => {
-                for (_, hash) in tags {
-                    result.push(hash);
-                }
-            }
-            false => {
-                for (name, hash) in tags {
-                    result.push(format!("{} {}", hash, name));
-                }
+        let path_objects = self.repo_paths.get_objects();
+        let mut result = String::new();
+        for (name, hash) in Tag::get_tags(&self.repo_paths)? {
+            let peeled = Tag::peel(&hash, &path_objects)?;
+            result.push_str(&self.hashes_references(hash.to_string(), name.clone()));
+            if peeled != hash {
+                result
+                    .push_str(&self.hashes_references(peeled.to_string(), format!("{name}^{{}}")));
             }
         }
-        Ok(Some(result.join("\n")))
+        match result.is_empty() {
+            true => Ok(None),
+            false => Ok(Some(result)),
+        }
     }
 
     fn references_remote(&self) -> Result<Option<String>, ErrorType> {
@@ -133,7 +130,11 @@ impl ShowRef {
 
 #[cfg(test)]
 mod list_dir_file_paths_tests {
+    use crate::git_errors::errors::ErrorType;
     use crate::show_ref::ShowRef;
+    use crate::tag::Tag;
+    use crate::test_utils::{commit_file, init_repo};
+    use std::fs;
 
     #[test]
     fn options_test() {
@@ -159,4 +160,36 @@ mod list_dir_file_paths_tests {
         assert!(!basic_only_hash.heads);
         assert!(!basic_only_hash.tags);
     }
+
+    #[test]
+    fn tags_show_the_peeled_object_of_annotated_tags() -> Result<(), ErrorType> {
+        let repo_paths = init_repo("git_rustico_tests_show_ref_tags")?;
+        let commit = commit_file(&repo_paths, "a.txt", "a")?;
+        let tag = |args: &[&str]| {
+            let args = args.iter().map(|arg| arg.to_string()).collect();
+            Tag::command_tag(args, &repo_paths)
+        };
+        tag(&["light"])?;
+        tag(&["-a", "release/v1", "-m", "first release"])?;
+        let tag_object = fs::read_to_string(repo_paths.get_refs_tags().join("release/v1"))?;
+
+        let show_ref = ShowRef {
+            head: false,
+            heads: false,
+            tags: true,
+            hash: false,
+            repo_paths: repo_paths.clone(),
+        };
+        assert_eq!(
+            show_ref.references_tags()?,
+            Some(format!(
+                "{commit} light\n{tag_object} release/v1\n{commit} release/v1^{{}}\n"
+            ))
+        );
+
+        tag(&["-d", "release/v1"])?;
+        assert!(!repo_paths.get_refs_tags().join("release").exists());
+        fs::remove_dir_all(repo_paths.get_home())?;
+        Ok(())
+    }
 }
@@ -0,0 +1,450 @@
+use crate::blob::Blob;
+use crate::commit::Commit;
+use crate::config::RepoConfig;
+use crate::git_errors::command_error::CommandError::{
+    IncorrectAmount, InvalidArgument, UnknownOption,
+};
+use crate::git_errors::errors::ErrorType;
+use crate::git_object::GitObject;
+use crate::hash::GitHash;
+use crate::index::Index;
+use crate::refs::BranchRef;
+use crate::repo_paths::RepoPaths;
+use crate::tree::Tree;
+use std::collections::BTreeSet;
+use std::fs::{self, File};
+use std::path::PathBuf;
+
+const MESSAGE_OPTION: &str = "-m";
+
+// Cada entrada del stash es un commit con el working directory, como hace git:
+//
+//        HEAD <-- index on <branch>: ...      (tree del index)
+//          ^             ^
+//          |             |
+//          +---- WIP on <branch>: ...         (tree del working directory)
+//
+// refs/stash tiene los hashes de esos commits, uno por linea, el mas nuevo primero.
+// Los archivos sin trackear no se guardan.
+
+/// Pila de cambios guardados con `stash push`.
+pub struct Stash {
+    path: PathBuf,
+    entries: Vec<GitHash>,
+}
+
+impl Stash {
+    /// Abre la pila del repositorio, vacia si todavia no se uso stash
+    pub fn open(repo_paths: &RepoPaths) -> Result<Self, ErrorType> {
+        let path = repo_paths.get_stash();
+        let mut entries = Vec::new();
+        if path.exists() {
+            for line in fs::read_to_string(&path)?.lines() {
+                entries.push(GitHash::new(line.trim())?);
+            }
+        }
+        Ok(Self { path, entries })
+    }
+
+    fn save(&self) -> Result<(), ErrorType> {
+        if self.entries.is_empty() {
+            if self.path.exists() {
+                fs::remove_file(&self.path)?;
+            }
+            return Ok(());
+        }
+        let lines: Vec<&str> = self.entries.iter().map(|hash| hash.as_str()).collect();
+        fs::write(&self.path, format!("{}\n", lines.join("\n")))?;
+        Ok(())
+    }
+
+    /// Commits del stash, el mas nuevo primero
+    pub(crate) fn get_entries(&self) -> Vec<GitHash> {
+        self.entries.clone()
+    }
+
+    /// stash [push [-m <message>]] | list | show [<stash>] | apply [<stash>] | pop [<stash>] | drop [<stash>]
+    ///
+    /// `<stash>` puede ser `stash@{n}` o solo `n`. Si no se indica se usa el ultimo guardado.
+    pub fn stash_command(args: Vec<String>, repo_paths: &RepoPaths) -> Result<String, ErrorType> {
+        let mut args = args.into_iter().peekable();
+        // sin subcomando (o solo con opciones) es un push
+        let subcommand = match args.next_if(|arg| !arg.starts_with('-')) {
+            Some(subcommand) => subcommand,
+            None => "push".to_string(),
+        };
+        let args: Vec<String> = args.collect();
+
+        let result = match subcommand.as_str() {
+            "push" => Self::push(repo_paths, Self::parse_message(args)?)?,
+            "list" => {
+                Self::check_no_arguments(&args)?;
+                Self::list(repo_paths)?
+            }
+            "show" => {
+                let stash = Self::open(repo_paths)?;
+                let position = stash.parse_position(&args)?;
+                Self::show(repo_paths, &stash.entries[position])?
+            }
+            "apply" => {
+                let stash = Self::open(repo_paths)?;
+                let position = stash.parse_position(&args)?;
+                Self::apply(repo_paths, &stash.entries[position])?;
+                format!("Applied stash@{{{position}}}")
+            }
+            "pop" => {
+                let mut stash = Self::open(repo_paths)?;
+                let position = stash.parse_position(&args)?;
+                Self::apply(repo_paths, &stash.entries[position])?;
+                stash.drop(position)?
+            }
+            "drop" => {
+                let mut stash = Self::open(repo_paths)?;
+                let position = stash.parse_position(&args)?;
+                stash.drop(position)?
+            }
+            _ => {
+                return Err(ErrorType::CommandError(UnknownOption(
+                    "push, list, show, apply, pop, drop".to_string(),
+                    subcommand,
+                )))
+            }
+        };
+        println!("{}", result);
+        Ok(result)
+    }
+
+    fn parse_message(args: Vec<String>) -> Result<Option<String>, ErrorType> {
+        match args.as_slice() {
+            [] => Ok(None),
+            [option, message] if option == MESSAGE_OPTION => Ok(Some(message.clone())),
+            _ => Err(ErrorType::CommandError(InvalidArgument(format!(
+                "stash push only accepts {MESSAGE_OPTION} <message>"
+            )))),
+        }
+    }
+
+    fn check_no_arguments(args: &[String]) -> Result<(), ErrorType> {
+        if !args.is_empty() {
+            return Err(ErrorType::CommandError(IncorrectAmount(
+                "0".to_string(),
+                args.len(),
+            )));
+        }
+        Ok(())
+    }
+
+    // posicion en la pila de stash@{n} o n
+    fn parse_position(&self, args: &[String]) -> Result<usize, ErrorType> {
+        let position = match args {
+            [] => 0,
+            [name] => {
+                let number = name
+                    .strip_prefix("stash@{")
+                    .and_then(|rest| rest.strip_suffix('}'))
+                    .unwrap_or(name);
+                number.parse().map_err(|_| {
+                    ErrorType::CommandError(InvalidArgument(format!("'{name}' is not a stash")))
+                })?
+            }
+            _ => {
+                return Err(ErrorType::CommandError(IncorrectAmount(
+                    "0 or 1".to_string(),
+                    args.len(),
+                )))
+            }
+        };
+        if position >= self.entries.len() {
+            return Err(ErrorType::RepositoryError(match self.entries.is_empty() {
+                true => "No stash entries found.".to_string(),
+                false => format!("stash@{{{position}}} is not a valid reference"),
+            }));
+        }
+        Ok(position)
+    }
+
+    /// Guarda los cambios del index y del working directory y los descarta (reset --hard a HEAD)
+    fn push(repo_paths: &RepoPaths, message: Option<String>) -> Result<String, ErrorType> {
+        let path_index = repo_paths.get_index();
+        let path_home = repo_paths.get_home();
+        let path_objects = repo_paths.get_objects();
+
+        let head = BranchRef::open(repo_paths.get_head(), &repo_paths.get_refs_heads())?;
+        let Some(head_commit) = head.get_last_commit(&path_objects)? else {
+            return Err(ErrorType::RepositoryError(
+                "You do not have the initial commit yet".to_string(),
+            ));
+        };
+
+        let mut index = Index::open(&path_index)?;
+        let status = index.status(repo_paths)?;
+        if !status.unmerged.is_empty() {
+            return Err(ErrorType::RepositoryError(
+                "Cannot save the current state while there are unmerged paths".to_string(),
+            ));
+        }
+        if status.staged.is_empty() && status.not_staged.is_empty() {
+            return Ok("No local changes to save".to_string());
+        }
+
+        let user = RepoConfig::open(repo_paths.get_config())?.get_user().ok_or(ErrorType::ConfigError("User name and mail should be set before stashing (use 'git-rustico config --user-name <name> --user-mail <mail>')".to_string()))?;
+        let branch = head.get_branch_name().unwrap_or("(no branch)".to_string());
+        let subject = format!(
+            "{}: {} {}",
+            branch,
+            &head_commit.get_hash().as_str()[..7],
+            head_commit.get_message().lines().next().unwrap_or_default()
+        );
+
+        let index_commit = Commit::new(
+            Tree::from_index(index.as_files_vector())?,
+            Some(head_commit.get_hash()),
+            None,
+            &format!("index on {subject}"),
+            user.clone(),
+        )?;
+        index_commit.save(&path_objects)?;
+
+        // el working directory se guarda agregando a una copia del index los archivos trackeados
+        let mut worktree = Index::open(&path_index)?;
+        for path in worktree.get_paths() {
+            let global_path = path_home.join(&path);
+            match global_path.exists() || global_path.is_symlink() {
+                true => worktree.add(path, &path_home, &path_objects)?,
+                false => worktree.remove(path)?,
+            }
+        }
+        let message = match message {
+            Some(message) => format!("On {branch}: {message}"),
+            None => format!("WIP on {subject}"),
+        };
+        let stash_commit = Commit::new(
+            Tree::from_index(worktree.as_files_vector())?,
+            Some(head_commit.get_hash()),
+            Some(index_commit.get_hash()),
+            &message,
+            user,
+        )?;
+        stash_commit.save(&path_objects)?;
+
+        let mut stash = Self::open(repo_paths)?;
+        stash.entries.insert(0, stash_commit.get_hash());
+        stash.save()?;
+
+        BranchRef::reset_working_dir(
+            &mut index,
+            &head_commit,
+            &head_commit,
+            &path_home,
+            &path_objects,
+        )?;
+        index.save(&mut File::create(path_index)?)?;
+
+        Ok(format!("Saved working directory and index state {message}"))
+    }
+
+    fn list(repo_paths: &RepoPaths) -> Result<String, ErrorType> {
+        let stash = Self::open(repo_paths)?;
+        let mut lines = Vec::new();
+        for (position, hash) in stash.entries.iter().enumerate() {
+            let commit = GitObject::read_commit(hash, &repo_paths.get_objects())?;
+            lines.push(format!(
+                "stash@{{{}}}: {}",
+                position,
+                commit.get_message().trim_end()
+            ));
+        }
+        Ok(lines.join("\n"))
+    }
+
+    /// Archivos que cambia el stash respecto del commit en el que se guardo
+    fn show(repo_paths: &RepoPaths, hash: &GitHash) -> Result<String, ErrorType> {
+        let path_objects = repo_paths.get_objects();
+        let stash_commit = GitObject::read_commit(hash, &path_objects)?;
+        let base_files = Self::read_base(&stash_commit, repo_paths)?.get_files_map()?;
+        let stash_files = stash_commit.get_files_map()?;
+
+        let paths: BTreeSet<&PathBuf> = base_files.keys().chain(stash_files.keys()).collect();
+        let mut lines = Vec::new();
+        for path in paths {
+            let status = match (base_files.get(path), stash_files.get(path)) {
+                (None, Some(_)) => "A",
+                (Some(_), None) => "D",
+                (base, stash) if base != stash => "M",
+                _ => continue,
+            };
+            lines.push(format!("{}\t{}", status, path.display()));
+        }
+        Ok(lines.join("\n"))
+    }
+
+    /// Aplica los cambios del stash sobre HEAD. Si HEAD se movio desde el push, los archivos que
+    /// cambiaron tanto en el stash como en HEAD son un conflicto y no se aplica nada.
+    /// Igual que git, los archivos nuevos quedan en el index y el resto como cambios sin agregar
+    fn apply(repo_paths: &RepoPaths, hash: &GitHash) -> Result<(), ErrorType> {
+        let path_index = repo_paths.get_index();
+        let path_home = repo_paths.get_home();
+        let path_objects = repo_paths.get_objects();
+
+        let mut index = Index::open(&path_index)?;
+        let status = index.status(repo_paths)?;
+        if !status.staged.is_empty() || !status.not_staged.is_empty() {
+            return Err(ErrorType::RepositoryError(
+                "Your local changes would be overwritten by the stash.\nCommit or stash them before applying it".to_string(),
+            ));
+        }
+
+        let head = BranchRef::open(repo_paths.get_head(), &repo_paths.get_refs_heads())?;
+        let head_files = match head.get_last_commit(&path_objects)? {
+            Some(commit) => commit.get_files_map()?,
+            None => Default::default(),
+        };
+        let stash_commit = GitObject::read_commit(hash, &path_objects)?;
+        let base_files = Self::read_base(&stash_commit, repo_paths)?.get_files_map()?;
+        let stash_files = stash_commit.get_files_map()?;
+
+        let paths: BTreeSet<&PathBuf> = base_files
+            .keys()
+            .chain(stash_files.keys())
+            .chain(head_files.keys())
+            .collect();
+        let mut changes = Vec::new();
+        let mut conflicts = Vec::new();
+        for path in paths {
+            let (base, stashed, current) = (
+                base_files.get(path),
+                stash_files.get(path),
+                head_files.get(path),
+            );
+            if stashed == base || stashed == current {
+                continue;
+            }
+            let untracked = current.is_none() && path_home.join(path).exists();
+            if current != base || untracked {
+                conflicts.push(path.display().to_string());
+                continue;
+            }
+            changes.push((path.clone(), stashed.cloned()));
+        }
+        if !conflicts.is_empty() {
+            return Err(ErrorType::RepositoryError(format!(
+                "The stash could not be applied, these files were also changed since it was saved:\n{}",
+                conflicts.join("\n")
+            )));
+        }
+
+        for (path, stashed) in changes {
+            let global_path = path_home.join(&path);
+            match stashed {
+                Some((mode, hash)) => {
+                    Blob::checkout(&hash, mode, &global_path, &path_objects)?;
+                    if !head_files.contains_key(&path) {
+                        index.add(path, &path_home, &path_objects)?;
+                    }
+                }
+                None => fs::remove_file(global_path)?,
+            }
+        }
+        index.save(&mut File::create(path_index)?)?;
+        Ok(())
+    }
+
+    fn drop(&mut self, position: usize) -> Result<String, ErrorType> {
+        let hash = self.entries.remove(position);
+        self.save()?;
+        Ok(format!("Dropped stash@{{{position}}} ({hash})"))
+    }
+
+    // commit sobre el que se hizo el stash
+    fn read_base(stash_commit: &Commit, repo_paths: &RepoPaths) -> Result<Commit, ErrorType> {
+        let parent = stash_commit
+            .get_parent_hash()
+            .ok_or(ErrorType::FormatError(format!(
+                "stash commit {} has no parent",
+                stash_commit.get_hash()
+            )))?;
+        GitObject::read_commit(&parent, &repo_paths.get_objects())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{commit_files, init_repo};
+    use std::path::Path;
+
+    fn read(home: &Path, path: &str) -> Result<String, ErrorType> {
+        Ok(fs::read_to_string(home.join(path))?)
+    }
+
+    fn stash(repo_paths: &RepoPaths, args: &[&str]) -> Result<String, ErrorType> {
+        let args = args.iter().map(|arg| arg.to_string()).collect();
+        Stash::stash_command(args, repo_paths)
+    }
+
+    #[test]
+    fn push_and_pop_on_a_new_commit() -> Result<(), ErrorType> {
+        let repo_paths = init_repo("git_rustico_tests_stash_pop")?;
+        let home = repo_paths.get_home();
+        commit_files(
+            &repo_paths,
+            &[("a.txt", "a"), ("b.txt", "b"), ("c.txt", "c")],
+        )?;
+
+        fs::write(home.join("a.txt"), "a2")?;
+        fs::write(home.join("new.txt"), "new")?;
+        Index::add_command(vec!["new.txt".to_string()], &repo_paths)?;
+        fs::remove_file(home.join("b.txt"))?;
+        fs::write(home.join("untracked.txt"), "u")?;
+
+        stash(&repo_paths, &["push", "-m", "work"])?;
+        assert_eq!(read(&home, "a.txt")?, "a");
+        assert_eq!(read(&home, "b.txt")?, "b");
+        assert!(!home.join("new.txt").exists());
+        assert_eq!(read(&home, "untracked.txt")?, "u");
+        assert_eq!(stash(&repo_paths, &["list"])?, "stash@{0}: On master: work");
+        assert_eq!(
+            stash(&repo_paths, &["show"])?,
+            "M\ta.txt\nD\tb.txt\nA\tnew.txt"
+        );
+
+        // un commit que no toca los archivos del stash no impide aplicarlo
+        commit_files(&repo_paths, &[("c.txt", "c2")])?;
+        stash(&repo_paths, &["pop", "stash@{0}"])?;
+        assert_eq!(read(&home, "a.txt")?, "a2");
+        assert!(!home.join("b.txt").exists());
+        assert_eq!(read(&home, "new.txt")?, "new");
+        assert_eq!(read(&home, "c.txt")?, "c2");
+        assert!(Stash::open(&repo_paths)?.get_entries().is_empty());
+
+        let status = Index::open(&repo_paths.get_index())?.status(&repo_paths)?;
+        assert_eq!(status.staged.len(), 1);
+        assert_eq!(status.not_staged.len(), 2);
+
+        fs::remove_dir_all(&home)?;
+        Ok(())
+    }
+
+    #[test]
+    fn conflicting_apply_keeps_the_entry() -> Result<(), ErrorType> {
+        let repo_paths = init_repo("git_rustico_tests_stash_conflict")?;
+        let home = repo_paths.get_home();
+        commit_files(&repo_paths, &[("a.txt", "a")])?;
+        assert_eq!(stash(&repo_paths, &[])?, "No local changes to save");
+
+        fs::write(home.join("a.txt"), "mine")?;
+        stash(&repo_paths, &[])?;
+        commit_files(&repo_paths, &[("a.txt", "theirs")])?;
+
+        assert!(stash(&repo_paths, &["pop"]).is_err());
+        assert_eq!(read(&home, "a.txt")?, "theirs");
+        assert_eq!(Stash::open(&repo_paths)?.get_entries().len(), 1);
+
+        stash(&repo_paths, &["drop", "0"])?;
+        assert!(!repo_paths.get_stash().exists());
+        assert!(stash(&repo_paths, &["drop"]).is_err());
+
+        fs::remove_dir_all(&home)?;
+        Ok(())
+    }
+}
@@ -1,40 +1,53 @@
+use crate::branch::Branch;
+use crate::commit::Commit;
 use crate::config::RepoConfig;
+use crate::files::object_type::ObjectType;
 use crate::git_errors::errors::ErrorType;
+use crate::git_object::GitObject;
+use crate::hash::GitHash;
 use crate::refs::BranchRef;
 use crate::repo_paths::RepoPaths;
-use crate::utils::get_current_date_time;
+use crate::rev_parse::RevParse;
+use crate::user::User;
+use chrono::{DateTime, FixedOffset, Local};
 use std::collections::HashMap;
-use std::fs::{File, OpenOptions};
-use std::io;
-use std::io::{BufRead, Write};
-use std::path::PathBuf;
+use std::fmt::Write;
+use std::fs;
+use std::path::{Path, PathBuf};
 
 const LIST_TAGS_OPTION: &str = "-l";
 const DELETE_TAGS_OPTION: &str = "-d";
 const VERIFY_TAGS_OPTION: &str = "-v";
 const MESSAGE_TAGS_OPTION: &str = "-m";
-const TAG_FILE: &str = "tags";
-const SEPARATOR: &str = ";";
+const ANNOTATED_TAGS_OPTION: &str = "-a";
+// formato anterior: un unico archivo refs/tags con lineas "name;creator;hash;date;message"
+const LEGACY_SEPARATOR: &str = ";";
 
+// tag object:
+//     object <hash>
+//     type <commit|tree|blob|tag>
+//     tag <name>
+//     tagger <name> <<mail>> <timestamp> <offset>
+//
+//     <message>
+
+/// Tag anotado, guardado como object de tipo tag.
+/// Los tags livianos no tienen object, son solo un archivo refs/tags/<name> con el hash del commit.
 pub struct Tag {
     name: String,
-    creator: String,
-    hash_commit: String,
-    message: Option<String>,
-    date_time: String,
+    object: GitHash,
+    object_type: ObjectType,
+    tagger: User,
+    date: DateTime<FixedOffset>,
+    message: String,
 }
 
 impl Tag {
     // Funcion para cliente
     pub fn command_tag(args: Vec<String>, repo_paths: &RepoPaths) -> Result<String, ErrorType> {
-        let pathbuf_file = repo_paths.get_refs_tags().join(TAG_FILE);
-        if !pathbuf_file.exists() {
-            {
-                File::create(pathbuf_file.clone())?;
-            }
-        }
+        let path_tags = Self::open_tags_dir(repo_paths)?;
         if args.is_empty() {
-            Self::list_tag(pathbuf_file)
+            Self::list_tag(&path_tags)
         } else if args.contains(&LIST_TAGS_OPTION.to_string()) {
             let arg = Self::delete_option(&args, &LIST_TAGS_OPTION.to_string());
             if arg.len() != 1 {
@@ -42,7 +55,7 @@ impl Tag {
                     "Error, in argument to list tags.".to_string(),
                 ));
             }
-            Self::list_tag_whit_patron(pathbuf_file, arg[0].clone())
+            Self::list_tag_whit_patron(&path_tags, arg[0].clone())
         } else if args.contains(&VERIFY_TAGS_OPTION.to_string()) {
             let arg = Self::delete_option(&args, &VERIFY_TAGS_OPTION.to_string());
             if arg.len() != 1 {
@@ -50,7 +63,7 @@ impl Tag {
                     "Error, in argument to Verify tags.".to_string(),
                 ));
             }
-            Self::verify_tag(pathbuf_file, arg[0].clone())
+            Self::verify_tag(&path_tags, arg[0].clone(), &repo_paths.get_objects())
         } else if args.contains(&DELETE_TAGS_OPTION.to_string()) {
             let arg = Self::delete_option(&args, &DELETE_TAGS_OPTION.to_string());
             if arg.len() != 1 {
@@ -58,91 +71,153 @@ impl Tag {
                     "Error, in argument to Delete tags.".to_string(),
                 ));
             }
-            Self::delete_tag(pathbuf_file, arg[0].clone())
+            Self::delete_tag(&path_tags, arg[0].clone())
         } else {
-            Self::create_tag(args, pathbuf_file, repo_paths)
+            Self::create_tag(args, &path_tags, repo_paths)
         }
     }
 
+    // ARGS: [name] -> tag liviano
+    //       [name, "-m", message] o ["-a", name, "-m", message] -> tag anotado
+    // Al final se puede agregar la revision a taggear, por defecto HEAD
     fn create_tag(
         args: Vec<String>,
-        pathbuf_file: PathBuf,
+        path_tags: &Path,
         repo_paths: &RepoPaths,
     ) -> Result<String, ErrorType> {
+        let args = Self::delete_option(&args, &ANNOTATED_TAGS_OPTION.to_string());
         if args.is_empty() {
             return Err(ErrorType::RepositoryError(
                 "Error, in argument to create tag, no arguments.".to_string(),
             ));
         }
-        let creator = Self::get_user_data_for_tags(repo_paths.get_config())?; // si no hay usuario cancela ensegida.
-        let hash_commit = Self::get_hash_commit(repo_paths)?;
-        let map = Self::read_file_tags(pathbuf_file.clone())?;
         let name = args[0].clone();
-        if map.contains_key(&name) {
+        Self::validate_name(&name)?;
+        let path_tag = path_tags.join(&name);
+        if path_tag.exists() {
             return Err(ErrorType::RepositoryError(format!(
                 "Error, in name to create tag, tag {} already exist.",
                 name
             )));
         }
-        let message = if args.len() > 2 && args[1] == *MESSAGE_TAGS_OPTION {
-            Some(args[2].clone())
-        } else {
-            None
+        let (message, revision) = match &args[1..] {
+            [] => (None, None),
+            [revision] => (None, Some(revision)),
+            [option, message] if *option == MESSAGE_TAGS_OPTION => (Some(message.clone()), None),
+            [option, message, revision] if *option == MESSAGE_TAGS_OPTION => {
+                (Some(message.clone()), Some(revision))
+            }
+            _ => {
+                return Err(ErrorType::RepositoryError(
+                    "Error, in argument to create tag.".to_string(),
+                ))
+            }
+        };
+        let hash_object = match revision {
+            Some(revision) => RevParse::resolve(repo_paths, revision)?,
+            None => Self::get_hash_commit(repo_paths)?,
         };
-        let date_time = get_current_date_time()?;
-        let tag = Tag {
-            name,
-            creator,
-            hash_commit,
-            message,
-            date_time,
+
+        let hash_ref = match message {
+            None => hash_object,
+            Some(message) => {
+                let (object_type, _) =
+                    GitObject::read_object(&hash_object, &repo_paths.get_objects())?;
+                let tag = Tag {
+                    name: name.clone(),
+                    object: hash_object,
+                    object_type,
+                    tagger: Self::get_user_data_for_tags(repo_paths.get_config())?,
+                    date: Local::now().fixed_offset(),
+                    message,
+                };
+                tag.save(&repo_paths.get_objects())?
+            }
         };
-        Self::append_file_tags(pathbuf_file, tag)?;
-        let text = format!("Tag: {}, created successfully.", args[0].clone());
+        // los nombres como release/v1 se guardan en subdirectorios de refs/tags
+        if let Some(dir) = path_tag.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path_tag, hash_ref.to_string())?;
+
+        let text = format!("Tag: {}, created successfully.", name);
         println!("{}", text);
         Ok(text)
     }
 
-    fn delete_tag(pathbuf_file: PathBuf, tag_to_delete: String) -> Result<String, ErrorType> {
-        let mut map = Self::read_file_tags(pathbuf_file.clone())?;
-        let result = match map.remove(&tag_to_delete) {
-            None => Err(ErrorType::RepositoryError(format!(
+    fn delete_tag(path_tags: &Path, tag_to_delete: String) -> Result<String, ErrorType> {
+        Self::validate_name(&tag_to_delete)?;
+        let path_tag = path_tags.join(&tag_to_delete);
+        if !path_tag.is_file() {
+            return Err(ErrorType::RepositoryError(format!(
                 "Error, Not exist tag: {} to delete.",
-                tag_to_delete.clone()
-            ))),
-            Some(_) => Ok(format!(
-                "Tag {} successfully removed.",
-                tag_to_delete.clone()
-            )),
-        };
-        Self::write_file_tags(pathbuf_file, map)?;
+                tag_to_delete
+            )));
+        }
+        fs::remove_file(&path_tag)?;
+
+        // borra los directorios que quedaron vacios (release/ al borrar release/v1)
+        let mut parent = path_tag.parent();
+        while let Some(dir) = parent.filter(|dir| *dir != path_tags && dir.starts_with(path_tags)) {
+            if fs::read_dir(dir)?.next().is_some() {
+                break;
+            }
+            fs::remove_dir(dir)?;
+            parent = dir.parent();
+        }
         println!("Tag {} successfully removed.", tag_to_delete);
-        result
+        Ok(format!("Tag {} successfully removed.", tag_to_delete))
     }
 
-    fn verify_tag(pathbuf_file: PathBuf, tag_to_verify: String) -> Result<String, ErrorType> {
-        let map = Self::read_file_tags(pathbuf_file)?;
-        let tags_names = map.contains_key(&tag_to_verify);
-        let result = match tags_names {
-            true => format!("Exist tag: {}.", tag_to_verify),
-            false => format!("Not exist tag: {}.", tag_to_verify),
+    // mismas reglas que los nombres de branch: el nombre no puede salir de refs/tags
+    fn validate_name(name: &str) -> Result<(), ErrorType> {
+        if !Branch::is_valid_ref_name(name) {
+            return Err(ErrorType::RepositoryError(format!(
+                "Error, '{}' is not a valid tag name.",
+                name
+            )));
+        }
+        Ok(())
+    }
+
+    fn verify_tag(
+        path_tags: &Path,
+        tag_to_verify: String,
+        path_objects: &Path,
+    ) -> Result<String, ErrorType> {
+        let tags = Self::read_tag_refs(path_tags)?;
+        let result = match tags.get(&tag_to_verify) {
+            None => format!("Not exist tag: {}.", tag_to_verify),
+            Some(hash) => match Self::read_tag_object(hash, path_objects)? {
+                None => format!("Exist tag: {} -> {}.", tag_to_verify, hash),
+                Some(tag) => format!(
+                    "Exist tag: {} -> {} {}\ntagger {} <{}> {}\n\n{}",
+                    tag_to_verify,
+                    tag.object_type,
+                    tag.object,
+                    tag.tagger.get_name(),
+                    tag.tagger.get_mail(),
+                    tag.date,
+                    tag.message
+                ),
+            },
         };
         println!("{}", result);
         Ok(result)
     }
 
-    fn list_tag(pathbuf_file: PathBuf) -> Result<String, ErrorType> {
-        let map = Self::read_file_tags(pathbuf_file)?;
-        let mut tags_names: Vec<String> = map.keys().map(|x| x.to_string()).collect();
+    fn list_tag(path_tags: &Path) -> Result<String, ErrorType> {
+        let tags = Self::read_tag_refs(path_tags)?;
+        let mut tags_names: Vec<String> = tags.keys().map(|x| x.to_string()).collect();
         tags_names.sort();
         println!("{}", tags_names.join("\n"));
         Ok(format!("{} tags were listed", tags_names.len()))
     }
 
-    fn list_tag_whit_patron(pathbuf_file: PathBuf, format: String) -> Result<String, ErrorType> {
-        let map = Self::read_file_tags(pathbuf_file)?;
+    fn list_tag_whit_patron(path_tags: &Path, format: String) -> Result<String, ErrorType> {
+        let tags = Self::read_tag_refs(path_tags)?;
         let mut result = Vec::new();
-        for key in map.keys() {
+        for key in tags.keys() {
             if !key.contains(&format) {
                 continue;
             }
@@ -159,59 +234,145 @@ impl Tag {
 
     ///////////////////////////////////////////////////////////
 
-    fn read_file_tags(file_path: PathBuf) -> Result<HashMap<String, Tag>, ErrorType> {
-        let file = File::open(file_path)?;
-        let reader = io::BufReader::new(file);
-        let mut map = HashMap::new();
-        for line in reader.lines() {
-            let line = line?;
-            let parts: Vec<&str> = line.split(SEPARATOR).collect();
-            if parts.len() != 5 {
-                return Err(ErrorType::FormatError(
-                    "Error, in read file tags, format of file unkown.".to_string(),
-                ));
+    /// Guarda el tag object y devuelve su hash
+    fn save(&self, path_objects: &Path) -> Result<GitHash, ErrorType> {
+        let content = self.generate_content()?;
+        let hash = GitHash::hash_object(&content, ObjectType::Tag);
+        GitObject::save_object(content, ObjectType::Tag, path_objects)?;
+        Ok(hash)
+    }
+
+    fn generate_content(&self) -> Result<Vec<u8>, ErrorType> {
+        let mut result = String::new();
+        writeln!(result, "object {}", self.object)?;
+        writeln!(result, "type {}", self.object_type)?;
+        writeln!(result, "tag {}", self.name)?;
+        let offset: String = self.date.offset().to_string().split(':').collect();
+        writeln!(
+            result,
+            "tagger {} <{}> {} {}",
+            self.tagger.get_name(),
+            self.tagger.get_mail(),
+            self.date.timestamp(),
+            offset
+        )?;
+        writeln!(result)?;
+        writeln!(result, "{}", self.message)?;
+        Ok(result.as_bytes().to_vec())
+    }
+
+    fn from_object(hash: &GitHash, content: Vec<u8>) -> Result<Self, ErrorType> {
+        let content = String::from_utf8(content)
+            .map_err(|_| ErrorType::FormatError(format!("invalid_content in tag object {hash}")))?;
+        let (header, message) = content.split_once("\n\n").unwrap_or((&content, ""));
+
+        let mut found = HashMap::new();
+        for line in header.lines() {
+            if let Some((category, data)) = line.split_once(' ') {
+                found.insert(category, data);
+            }
+        }
+        let field = |name: &str| {
+            found
+                .get(name)
+                .copied()
+                .ok_or(ErrorType::FormatError(format!(
+                    "Tag object '{hash}' is missing a field '{name}'"
+                )))
+        };
+
+        let object = GitHash::new(field("object")?)?;
+        let object_type = match field("type")? {
+            "commit" => ObjectType::Commit,
+            "tree" => ObjectType::Tree,
+            "blob" => ObjectType::Blob,
+            "tag" => ObjectType::Tag,
+            other => {
+                return Err(ErrorType::ObjectType(
+                    "tree, blob, commit or tag".to_string(),
+                    other.to_string(),
+                ))
             }
-            let label_name = parts[0].to_string();
-            let creator = parts[1].to_string();
-            let hash_commit = parts[2].to_string();
-            let date_time = parts[3].to_string();
-            let message = match parts[4].to_string().is_empty() {
-                true => None,
-                false => Some(parts[4].to_string()),
-            };
-            let tag = Tag {
-                name: label_name.clone(),
-                creator,
-                hash_commit,
-                message,
-                date_time,
-            };
-            map.insert(label_name, tag);
+        };
+        let (tagger, date) = Commit::parse_user_line(field("tagger")?)?;
+
+        Ok(Self {
+            name: field("tag")?.to_string(),
+            object,
+            object_type,
+            tagger,
+            date,
+            message: message.strip_suffix('\n').unwrap_or(message).to_string(),
+        })
+    }
+
+    /// Devuelve el tag anotado si el hash es un tag object, o None si es otro tipo de object
+    fn read_tag_object(hash: &GitHash, path_objects: &Path) -> Result<Option<Self>, ErrorType> {
+        let (object_type, content) = GitObject::read_object(hash, path_objects)?;
+        match object_type {
+            ObjectType::Tag => Ok(Some(Self::from_object(hash, content)?)),
+            _ => Ok(None),
         }
-        Ok(map)
     }
 
-    fn write_file_tags(file_path: PathBuf, map: HashMap<String, Tag>) -> Result<(), ErrorType> {
-        let mut file = File::create(file_path)?;
-        let mut to_write = Vec::new();
-        for (_, tag) in map {
-            let line = tag.parse_to_string();
-            to_write.push(line);
+    /// Sigue los tag objects hasta llegar al object al que apuntan (normalmente un commit).
+    /// Si el hash no es de un tag object lo devuelve tal cual.
+    pub fn peel(hash: &GitHash, path_objects: &Path) -> Result<GitHash, ErrorType> {
+        let mut hash = hash.clone();
+        while let Some(tag) = Self::read_tag_object(&hash, path_objects)? {
+            hash = tag.object;
         }
-        let text = to_write.join("\n");
-        file.write_all(text.as_bytes())?;
-        Ok(())
+        Ok(hash)
     }
 
-    fn append_file_tags(file_path: PathBuf, tag: Tag) -> Result<(), ErrorType> {
-        let mut file = OpenOptions::new()
-            .read(true) // Abre el archivo para lectura.
-            .append(true) // Esto deberia hacer que la escritura sea al final del archivo sin rescribir nada de lo anterior
-            .open(file_path)?;
-        writeln!(file, "{}", tag.parse_to_string())?;
+    // name -> hash del archivo refs/tags/<name>. Los nombres con '/' son subdirectorios
+    fn read_tag_refs(path_tags: &Path) -> Result<HashMap<String, GitHash>, ErrorType> {
+        let mut tags = HashMap::new();
+        Self::read_tag_refs_rec(path_tags, "", &mut tags)?;
+        Ok(tags)
+    }
+
+    fn read_tag_refs_rec(
+        path_dir: &Path,
+        prefix: &str,
+        tags: &mut HashMap<String, GitHash>,
+    ) -> Result<(), ErrorType> {
+        for entry in fs::read_dir(path_dir)? {
+            let entry = entry?;
+            let name = format!("{prefix}{}", entry.file_name().to_string_lossy());
+            let file_type = entry.file_type()?;
+            if file_type.is_dir() {
+                Self::read_tag_refs_rec(&entry.path(), &format!("{name}/"), tags)?;
+            } else if file_type.is_file() {
+                let hash = GitHash::new(fs::read_to_string(entry.path())?.trim())?;
+                tags.insert(name, hash);
+            }
+        }
         Ok(())
     }
 
+    // devuelve refs/tags/ creandolo si hace falta. Si todavia existe el archivo de tags
+    // del formato anterior lo convierte en tags livianos
+    fn open_tags_dir(repo_paths: &RepoPaths) -> Result<PathBuf, ErrorType> {
+        let path_tags = repo_paths.get_refs_tags();
+        if path_tags.is_file() {
+            let legacy = fs::read_to_string(&path_tags)?;
+            fs::remove_file(&path_tags)?;
+            fs::create_dir_all(&path_tags)?;
+            for line in legacy.lines() {
+                let parts: Vec<&str> = line.split(LEGACY_SEPARATOR).collect();
+                if parts.len() != 5 {
+                    return Err(ErrorType::FormatError(
+                        "Error, in read file tags, format of file unkown.".to_string(),
+                    ));
+                }
+                fs::write(path_tags.join(parts[0]), parts[2])?;
+            }
+        }
+        fs::create_dir_all(&path_tags)?;
+        Ok(path_tags)
+    }
+
     fn delete_option(vec_origin: &[String], option: &String) -> Vec<String> {
         let result: Vec<String> = vec_origin
             .iter()
@@ -221,20 +382,17 @@ impl Tag {
         result
     }
 
-    fn get_user_data_for_tags(path_config: PathBuf) -> Result<String, ErrorType> {
+    fn get_user_data_for_tags(path_config: PathBuf) -> Result<User, ErrorType> {
         let config = RepoConfig::open(path_config)?;
-        let user = match config.get_user() {
-            None => {
-                return Err(ErrorType::ConfigError(
-                    "Error not data user yet.".to_string(),
-                ))
-            }
-            Some(user) => user,
-        };
-        Ok(format!("{} {}", user.get_name(), user.get_mail()))
+        match config.get_user() {
+            None => Err(ErrorType::ConfigError(
+                "Error not data user yet.".to_string(),
+            )),
+            Some(user) => Ok(user),
+        }
     }
 
-    fn get_hash_commit(repo_paths: &RepoPaths) -> Result<String, ErrorType> {
+    fn get_hash_commit(repo_paths: &RepoPaths) -> Result<GitHash, ErrorType> {
         let branch = BranchRef::open(repo_paths.get_head(), &repo_paths.get_refs_heads())?;
         let last_commit = match branch.get_last_commit(&repo_paths.get_objects())? {
             None => {
@@ -244,58 +402,111 @@ impl Tag {
             }
             Some(commit) => commit,
         };
-        Ok(last_commit.get_hash().to_string())
-    }
-
-    fn parse_to_string(&self) -> String {
-        let message = match &self.message {
-            None => "".to_string(),
-            Some(message) => message.clone(),
-        };
-        [
-            self.name.clone(),
-            self.creator.clone(),
-            self.hash_commit.clone(),
-            self.date_time.clone(),
-            message,
-        ]
-        .join(SEPARATOR)
+        Ok(last_commit.get_hash())
     }
 
     // Funcion para otras funciones
-    pub fn get_tags_for_show_refs(
-        repo_paths: &RepoPaths,
-    ) -> Result<Vec<(String, String)>, ErrorType> {
-        let pathbuf_file = repo_paths.get_refs_tags().join(TAG_FILE);
-        let map = Self::read_file_tags(pathbuf_file)?;
-        let mut result = Vec::new();
-        for (name, tag) in map {
-            let name_for_show_refs = match tag.get_message() {
-                None => name,
-                Some(message) => format!("{} {}", name, message),
-            };
-            result.push((name_for_show_refs, tag.get_hash()))
-        }
-        Ok(result)
-    }
-
-    fn get_hash(&self) -> String {
-        self.hash_commit.clone()
-    }
 
-    fn get_message(&self) -> Option<String> {
-        self.message.clone()
+    /// Devuelve (name, hash) de cada ref de refs/tags, ordenados por nombre. En los tags
+    /// anotados el hash es el del tag object, ver `Tag::peel`.
+    pub fn get_tags(repo_paths: &RepoPaths) -> Result<Vec<(String, GitHash)>, ErrorType> {
+        let path_tags = Self::open_tags_dir(repo_paths)?;
+        let mut tags: Vec<(String, GitHash)> =
+            Self::read_tag_refs(&path_tags)?.into_iter().collect();
+        tags.sort_by(|(a, _), (b, _)| a.cmp(b));
+        Ok(tags)
     }
 
+    /// Devuelve el hash del commit al que apunta el tag (pasando por el tag object si es anotado)
     pub fn get_hash_of_tag(repo_paths: &RepoPaths, name_tag: String) -> Result<String, ErrorType> {
-        let pathbuf_file = repo_paths.get_refs_tags().join(TAG_FILE);
-        let map = Self::read_file_tags(pathbuf_file)?;
-        match map.get(&name_tag) {
+        let path_tags = Self::open_tags_dir(repo_paths)?;
+        match Self::read_tag_refs(&path_tags)?.get(&name_tag) {
             None => Err(ErrorType::RepositoryError(format!(
                 "Error, Not exist tag {}.",
                 name_tag
             ))),
-            Some(tag) => Ok(tag.get_hash()),
+            Some(hash) => Ok(Self::peel(hash, &repo_paths.get_objects())?.to_string()),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{commit_file, init_repo};
+
+    fn tag() -> Result<Tag, ErrorType> {
+        Ok(Tag {
+            name: "v1.0".to_string(),
+            object: GitHash::new("10500012fca9b4425b50de67a7258a12cba0c076")?,
+            object_type: ObjectType::Commit,
+            tagger: User::new("theo", "theo@fi.uba.ar"),
+            date: Local::now().fixed_offset(),
+            message: "first release\nwith two lines".to_string(),
+        })
+    }
+
+    #[test]
+    fn tag_object_format() -> Result<(), ErrorType> {
+        let content = String::from_utf8(tag()?.generate_content()?).unwrap_or_default();
+        let lines: Vec<&str> = content.lines().collect();
+
+        assert_eq!(lines[0], "object 10500012fca9b4425b50de67a7258a12cba0c076");
+        assert_eq!(lines[1], "type commit");
+        assert_eq!(lines[2], "tag v1.0");
+        assert!(lines[3].starts_with("tagger theo <theo@fi.uba.ar> "));
+        assert_eq!(lines[4], "");
+        assert_eq!(lines[5..], ["first release", "with two lines"]);
+        Ok(())
+    }
+
+    #[test]
+    fn tag_object_roundtrip() -> Result<(), ErrorType> {
+        let tag = tag()?;
+        let content = tag.generate_content()?;
+        let hash = GitHash::hash_object(&content, ObjectType::Tag);
+
+        let parsed = Tag::from_object(&hash, content)?;
+
+        assert_eq!(parsed.name, tag.name);
+        assert_eq!(parsed.object, tag.object);
+        assert_eq!(parsed.object_type, tag.object_type);
+        assert_eq!(parsed.tagger, tag.tagger);
+        assert_eq!(parsed.date.timestamp(), tag.date.timestamp());
+        assert_eq!(parsed.message, tag.message);
+        Ok(())
+    }
+
+    #[test]
+    fn tag_object_missing_field() -> Result<(), ErrorType> {
+        let content =
+            b"object 10500012fca9b4425b50de67a7258a12cba0c076\ntype commit\n\nmsg\n".to_vec();
+        let hash = GitHash::hash_object(&content, ObjectType::Tag);
+        assert!(Tag::from_object(&hash, content).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn names_outside_the_tags_dir_are_rejected() -> Result<(), ErrorType> {
+        let repo_paths = init_repo("git_rustico_tests_tag_names")?;
+        commit_file(&repo_paths, "a.txt", "a")?;
+        let tag = |args: &[&str]| {
+            let args = args.iter().map(|arg| arg.to_string()).collect();
+            Tag::command_tag(args, &repo_paths)
+        };
+
+        for name in ["../heads/evil", "/abs", "a//b", "v1^", ""] {
+            assert!(tag(&[name]).is_err());
+        }
+        assert!(!repo_paths.get_refs_heads().join("evil").exists());
+        let victim = repo_paths.get_home().join("victim");
+        fs::write(&victim, "keep")?;
+        assert!(tag(&["-d", "../../../victim"]).is_err());
+        assert!(victim.exists());
+
+        tag(&["release/v1"])?;
+        tag(&["-d", "release/v1"])?;
+        fs::remove_dir_all(repo_paths.get_home())?;
+        Ok(())
+    }
+}
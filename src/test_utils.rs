@@ -0,0 +1,71 @@
+// helpers compartidos por los tests de los distintos modulos
+
+use std::{fs, path::Path};
+
+use crate::{
+    blob::Blob, commit::Commit, config::RepoConfig, git_errors::errors::ErrorType,
+    git_object::GitObject, hash::GitHash, index::Index, init::git_init, repo_paths::RepoPaths,
+    rev_parse::RevParse, tree::Tree, user::User,
+};
+
+/// Repositorio recien inicializado en un directorio temporal, con el usuario configurado
+pub(crate) fn init_repo(name: &str) -> Result<RepoPaths, ErrorType> {
+    let home = std::env::temp_dir().join(name);
+    let _ = fs::remove_dir_all(&home);
+    fs::create_dir_all(&home)?;
+    let repo_paths = RepoPaths::new(home)?;
+    git_init(repo_paths.clone())?;
+    let mut config = RepoConfig::open(repo_paths.get_config())?;
+    config.set_user_name("ada");
+    config.set_user_mail("ada@example.com");
+    config.save()?;
+    Ok(repo_paths)
+}
+
+/// Escribe los archivos en el working directory, los agrega y los commitea.
+/// Devuelve el hash del commit nuevo
+pub(crate) fn commit_files(
+    repo_paths: &RepoPaths,
+    files: &[(&str, &str)],
+) -> Result<GitHash, ErrorType> {
+    let home = repo_paths.get_home();
+    for (path, content) in files {
+        fs::write(home.join(path), content)?;
+    }
+    let paths: Vec<String> = files.iter().map(|(path, _)| path.to_string()).collect();
+    let message = format!("add {}", paths.join(", "));
+    Index::add_command(paths, repo_paths)?;
+    Commit::commit_command(repo_paths, vec![message])?;
+    RevParse::resolve(repo_paths, "HEAD")
+}
+
+/// commit_files con un solo archivo
+pub(crate) fn commit_file(
+    repo_paths: &RepoPaths,
+    path: &str,
+    content: &str,
+) -> Result<GitHash, ErrorType> {
+    commit_files(repo_paths, &[(path, content)])
+}
+
+/// Commit con los padres dados y un unico archivo "file" con el contenido, que tambien es el
+/// mensaje. Se guarda sin pasar por el index y sin mover ninguna ref
+pub(crate) fn commit_with_parents(
+    path_objects: &Path,
+    parents: &[&GitHash],
+    content: &str,
+) -> Result<Commit, ErrorType> {
+    let blob = Blob::new(content.as_bytes().to_vec());
+    GitObject::save_blob(blob.get_content(), path_objects)?;
+    let mut tree = Tree::new();
+    tree.add(Path::new("file"), blob.get_hash());
+    let commit = Commit::new(
+        tree,
+        parents.first().map(|hash| (*hash).clone()),
+        parents.get(1).map(|hash| (*hash).clone()),
+        content,
+        User::new("ada", "ada@example.com"),
+    )?;
+    commit.save(path_objects)?;
+    Ok(commit)
+}
@@ -1,27 +1,31 @@
 use std::{
     collections::HashMap,
+    ffi::OsString,
     fmt::Debug,
     path::{Path, PathBuf},
 };
 
 use crate::{
-    files::index_file_info::IndexFileInfo, git_errors::errors::ErrorType, git_object::GitObject,
+    files::{file_mode::FileMode, index_file_info::IndexFileInfo},
+    git_errors::errors::ErrorType,
+    git_object::GitObject,
     hash::GitHash,
+    utils::{bytes_to_path, path_to_bytes},
 };
 
-const FILE_MODE: &str = "100644";
-const DIR_MODE: &str = "040000";
+// git escribe el modo de los directorios sin el 0 adelante
+const DIR_MODE: &str = "40000";
+const LEGACY_DIR_MODE: &str = "040000";
 
-enum Type {
-    File,
-    Dir,
-}
+// entrada del object: (modo, nombre, hash)
+type TreeEntry<'a> = (&'a str, Vec<u8>, GitHash);
 
 /// Estructura recursiva del objeto Tree. Puede instanciarse desde un object o desde index
 #[derive(Debug, Clone, PartialEq)]
 pub struct Tree {
-    trees: HashMap<String, Tree>,
-    files: HashMap<String, GitHash>,
+    // los nombres se guardan sin convertir a utf-8, pueden tener cualquier byte salvo '/' y '\0'
+    trees: HashMap<OsString, Tree>,
+    files: HashMap<OsString, (FileMode, GitHash)>,
 }
 
 // formato de la linea en el object: [modo path\0hash_en_binario]
@@ -37,12 +41,7 @@ impl Tree {
         let mut tree = Self::new();
 
         for file in index_vec {
-            let path = file.get_path();
-            let path_str = path.to_str().ok_or(ErrorType::InvalidPath(format!(
-                "{} in index file",
-                file.get_path().display()
-            )))?;
-            tree.add(path_str, file.get_hash())
+            tree.add_with_mode(&file.get_path(), file.get_mode(), file.get_hash())
         }
 
         Ok(tree)
@@ -66,27 +65,22 @@ impl Tree {
                 .map(|byte| byte as char)
                 .collect::<String>();
 
-            let path: String = iter
-                .by_ref()
-                .take_while(|&byte| byte != b'\0')
-                .map(|byte| byte as char)
-                .collect::<String>();
-            // let path = PathBuf::from(path);
+            let path_bytes: Vec<u8> = iter.by_ref().take_while(|&byte| byte != b'\0').collect();
+            let path = bytes_to_path(&path_bytes).into_os_string();
 
             let hash_bytes: Vec<u8> = iter.by_ref().take(20).collect();
             let hash_entry = GitHash::from_hex(&hash_bytes)?;
 
-            match mode.as_str() {
-                "100644" => _ = files.insert(path, hash_entry),
-                "040000" => {
-                    _ = trees.insert(path, GitObject::read_tree(&hash_entry, path_objects)?)
-                }
-                _ => {
-                    return Err(ErrorType::FormatError(format!(
-                        "invalid UNIX filesystem mode ('{mode}') in tree '{hash}'"
-                    )))
-                }
-            };
+            if mode == DIR_MODE || mode == LEGACY_DIR_MODE {
+                trees.insert(path, GitObject::read_tree(&hash_entry, path_objects)?);
+                continue;
+            }
+            let mode = FileMode::try_from(mode.as_str()).map_err(|_| {
+                ErrorType::FormatError(format!(
+                    "invalid UNIX filesystem mode ('{mode}') in tree '{hash}'"
+                ))
+            })?;
+            files.insert(path, (mode, hash_entry));
         }
 
         let mut tree = Self::new();
@@ -105,32 +99,38 @@ impl Tree {
     /// El formato de cada linea es el siguiente : [modo path_elemento\0hash_elemento_en_bin]
     /// siendo elemento un tree o un blob
     pub fn generate_content(&self) -> Result<Vec<u8>, ErrorType> {
-        let entries: Result<Vec<(Type, String, GitHash)>, ErrorType> = self
+        let entries: Result<Vec<TreeEntry>, ErrorType> = self
             .trees
             .iter()
-            .map(|(key, tree)| Ok((Type::Dir, key.clone(), tree.get_hash()?)))
+            .map(|(key, tree)| Ok((DIR_MODE, path_to_bytes(Path::new(key)), tree.get_hash()?)))
             .collect();
         let mut entries = entries?;
 
-        let files: Vec<(Type, String, GitHash)> = self
+        let files: Vec<TreeEntry> = self
             .files
             .iter()
-            .map(|f| (Type::File, f.0.clone(), f.1.clone()))
+            .map(|(name, (mode, hash))| {
+                (mode.as_str(), path_to_bytes(Path::new(name)), hash.clone())
+            })
             .collect();
         entries.extend(files);
 
-        entries.sort_by_key(|e| e.1.clone());
+        // orden de git: bytewise por nombre, los directorios se comparan como si terminaran en '/'
+        entries.sort_by_key(|(mode, name, _)| {
+            let mut key = name.clone();
+            if *mode == DIR_MODE {
+                key.push(b'/');
+            }
+            key
+        });
 
         let mut content: Vec<u8> = Vec::new();
 
-        for (tipe, name, hash) in entries {
-            let mode = match tipe {
-                Type::File => FILE_MODE,
-                Type::Dir => DIR_MODE,
-            };
-
-            let mode_path = format!("{mode} {name}\0");
-            content.extend(mode_path.as_bytes());
+        for (mode, name, hash) in entries {
+            content.extend(mode.as_bytes());
+            content.push(b' ');
+            content.extend(name);
+            content.push(b'\0');
 
             let hash_hex = hash.to_hex()?;
             content.extend(hash_hex);
@@ -151,16 +151,26 @@ impl Tree {
         Ok(())
     }
 
+    /// Archivos (blobs) del tree con su path completo. No incluye los submodules (gitlinks)
     pub(crate) fn get_files_vec(&self) -> Vec<(PathBuf, GitHash)> {
+        self.get_files_with_mode_vec()
+            .into_iter()
+            .filter(|(_, mode, _)| *mode != FileMode::Gitlink)
+            .map(|(path, _, hash)| (path, hash))
+            .collect()
+    }
+
+    /// Todas las entradas que no son trees, con su path completo y su modo
+    pub(crate) fn get_files_with_mode_vec(&self) -> Vec<(PathBuf, FileMode, GitHash)> {
         self.get_files_vec_rec(PathBuf::from(""))
     }
 
-    fn get_files_vec_rec(&self, current_dir: PathBuf) -> Vec<(PathBuf, GitHash)> {
+    fn get_files_vec_rec(&self, current_dir: PathBuf) -> Vec<(PathBuf, FileMode, GitHash)> {
         let mut vec = Vec::new();
 
-        for (name, hash) in &self.files {
+        for (name, (mode, hash)) in &self.files {
             let path = current_dir.join(name);
-            vec.push((path, hash.clone()));
+            vec.push((path, *mode, hash.clone()));
         }
         for (dir, tree) in &self.trees {
             let sub_vec = tree.get_files_vec_rec(current_dir.join(dir));
@@ -169,14 +179,41 @@ impl Tree {
         vec
     }
 
-    fn set_trees(&mut self, trees: HashMap<String, Tree>) {
+    fn set_trees(&mut self, trees: HashMap<OsString, Tree>) {
         self.trees = trees;
     }
-    fn set_files(&mut self, files: HashMap<String, GitHash>) {
+    fn set_files(&mut self, files: HashMap<OsString, (FileMode, GitHash)>) {
         self.files = files;
     }
 
-    pub(crate) fn add(&mut self, path: &str, hash: GitHash) {
+    /// Agrega el archivo manteniendo su modo si ya estaba en el tree (sino es un archivo normal)
+    pub(crate) fn add(&mut self, path: &Path, hash: GitHash) {
+        let mode = self.get_mode(path).unwrap_or(FileMode::Regular);
+        self.add_with_mode(path, mode, hash)
+    }
+
+    fn get_mode(&self, path: &Path) -> Option<FileMode> {
+        match Self::split_first(path) {
+            (dir, Some(sub_dir)) => self.trees.get(&dir)?.get_mode(sub_dir),
+            (name, None) => self.files.get(&name).map(|(mode, _)| *mode),
+        }
+    }
+
+    // "dir/sub/file" -> ("dir", Some("sub/file")), "file" -> ("file", None)
+    fn split_first(path: &Path) -> (OsString, Option<&Path>) {
+        let mut components = path.components();
+        let first = components
+            .next()
+            .map(|c| c.as_os_str().to_os_string())
+            .unwrap_or_default();
+        let rest = components.as_path();
+        match rest.as_os_str().is_empty() {
+            true => (first, None),
+            false => (first, Some(rest)),
+        }
+    }
+
+    pub(crate) fn add_with_mode(&mut self, path: &Path, mode: FileMode, hash: GitHash) {
         // match path.split_once('/') {
         //     Some((dir, sub_dir)) => match self.trees.get_mut(dir) {
         //         Some(tree) => tree.add(sub_dir, hash),
@@ -189,13 +226,13 @@ impl Tree {
         //     // Here path is just file name
         //     None => self.files.push((path.to_string(), hash)),
         // }
-        if let Some((dir, sub_dir)) = path.split_once('/') {
-            self.trees
-                .entry(dir.to_string())
+        match Self::split_first(path) {
+            (dir, Some(sub_dir)) => self
+                .trees
+                .entry(dir)
                 .or_insert_with(Self::new)
-                .add(sub_dir, hash);
-        } else {
-            self.files.insert(path.to_string(), hash);
+                .add_with_mode(sub_dir, mode, hash),
+            (name, None) => _ = self.files.insert(name, (mode, hash)),
         }
     }
 }
@@ -387,3 +424,113 @@ impl Tree {
 //         Ok(())
 //     }
 // }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // hashes calculados con git (git write-tree) para los mismos archivos
+    fn git_tree(order: &[usize]) -> Result<Tree, ErrorType> {
+        let entries = [
+            (
+                "a-b",
+                FileMode::Regular,
+                "d00491fd7e5bb6fa28c517a0bb32b8b506539d4d",
+            ),
+            (
+                "a.txt",
+                FileMode::Regular,
+                "0cfbf08886fca9a91cb753ec8734c84fcbe52c9f",
+            ),
+            (
+                "a/b",
+                FileMode::Regular,
+                "00750edc07d6415dcc07ae0351e9397b0222b7ba",
+            ),
+            (
+                "a0",
+                FileMode::Regular,
+                "b8626c4cff2849624fb67f87cd0ad72b163671ad",
+            ),
+            (
+                "run.sh",
+                FileMode::Executable,
+                "7ed6ff82de6bcc2a78243fc9c54d3ef5ac14da69",
+            ),
+        ];
+        let mut tree = Tree::new();
+        for &i in order {
+            let (path, mode, hash) = entries[i];
+            tree.add_with_mode(Path::new(path), mode, GitHash::new(hash)?);
+        }
+        Ok(tree)
+    }
+
+    #[test]
+    fn same_hash_as_git() -> Result<(), ErrorType> {
+        let tree = git_tree(&[0, 1, 2, 3, 4])?;
+        assert_eq!(
+            tree.get_hash()?,
+            GitHash::new("bcb17e0f3bc2007a51885fbc40a2343bdbc140b3")?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn hash_does_not_depend_on_insertion_order() -> Result<(), ErrorType> {
+        let expected = git_tree(&[0, 1, 2, 3, 4])?.get_hash()?;
+        for order in [[4, 3, 2, 1, 0], [2, 0, 4, 1, 3], [3, 2, 1, 4, 0]] {
+            assert_eq!(git_tree(&order)?.get_hash()?, expected);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn directories_sort_as_if_they_had_a_trailing_slash() -> Result<(), ErrorType> {
+        let content = git_tree(&[0, 1, 2, 3, 4])?.generate_content()?;
+        let names: Vec<String> = crate::protocol::pack_file::tree_entry_names(&content)
+            .into_iter()
+            .map(|(_, name)| name)
+            .collect();
+        // '-' (0x2d) < '.' (0x2e) < '/' (0x2f) < '0' (0x30)
+        assert_eq!(names, ["a-b", "a.txt", "a", "a0", "run.sh"]);
+        assert!(content.starts_with(b"100644 a-b\0"));
+        assert!(content
+            .windows(b"40000 a\0".len())
+            .any(|w| w == b"40000 a\0"));
+        Ok(())
+    }
+
+    #[test]
+    fn utf8_names_same_hash_as_git() -> Result<(), ErrorType> {
+        let mut tree = Tree::new();
+        tree.add(
+            Path::new("canción.txt"),
+            GitHash::new("d00491fd7e5bb6fa28c517a0bb32b8b506539d4d")?,
+        );
+        assert_eq!(
+            tree.get_hash()?,
+            GitHash::new("aa056debc705f62b7fd5d1c6ac9493242b90e3da")?
+        );
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn arbitrary_byte_names_roundtrip() -> Result<(), ErrorType> {
+        let name = [b'f', 0xff, b'.', 0xe9];
+        let mut tree = Tree::new();
+        tree.add(
+            &bytes_to_path(&name),
+            GitHash::new("d00491fd7e5bb6fa28c517a0bb32b8b506539d4d")?,
+        );
+        let content = tree.generate_content()?;
+        assert_eq!(content[b"100644 ".len()..][..name.len()], name);
+
+        let hash = tree.get_hash()?;
+        let parsed = Tree::from_object(&hash, content, Path::new("no_objects"))?;
+        assert_eq!(parsed, tree);
+        assert_eq!(parsed.get_files_vec()[0].0, bytes_to_path(&name));
+        Ok(())
+    }
+}
@@ -1,11 +1,11 @@
 use crate::git_errors::errors::ErrorType;
 use chrono::{DateTime, Utc};
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::time::SystemTime;
 
 // todo borrar
 pub fn verify_path_exists(path: &Path) -> Result<(), ErrorType> {
-    if !path.exists() {
+    if !path.exists() && !path.is_symlink() {
         // user passed an inexistent path
         return Err(ErrorType::FileNotFound(format!("{}", path.display())));
     }
@@ -17,3 +17,29 @@ pub fn get_current_date_time() -> Result<String, ErrorType> {
     let date_time: DateTime<Utc> = system_time.into();
     Ok(date_time.format("%d/%m/%Y %T").to_string())
 }
+
+/// Bytes del path tal cual estan en el filesystem. En unix no hay perdida aunque no sea utf-8
+pub(crate) fn path_to_bytes(path: &Path) -> Vec<u8> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::ffi::OsStrExt;
+        path.as_os_str().as_bytes().to_vec()
+    }
+    #[cfg(not(unix))]
+    {
+        path.to_string_lossy().replace('\\', "/").into_bytes()
+    }
+}
+
+/// Inversa de path_to_bytes
+pub(crate) fn bytes_to_path(bytes: &[u8]) -> PathBuf {
+    #[cfg(unix)]
+    {
+        use std::os::unix::ffi::OsStrExt;
+        PathBuf::from(std::ffi::OsStr::from_bytes(bytes))
+    }
+    #[cfg(not(unix))]
+    {
+        PathBuf::from(String::from_utf8_lossy(bytes).to_string())
+    }
+}